@@ -0,0 +1,39 @@
+use crate::story::load_story;
+use clap::{Args, ValueEnum};
+use kataru::{Error, Result, StoryGraph};
+use std::{fs, path::PathBuf, process::ExitCode};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Args)]
+pub struct GraphArgs {
+    /// Story directory, section YAML file or packed MessagePack file.
+    story: PathBuf,
+    /// Output format.
+    #[arg(short, long, value_enum, default_value = "dot")]
+    format: Format,
+    /// File to write the graph to. Defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(args: GraphArgs) -> Result<ExitCode> {
+    let story = load_story(&args.story)?;
+    let graph = StoryGraph::new(&story);
+    let text = match args.format {
+        Format::Dot => graph.to_dot(),
+        Format::Mermaid => graph.to_mermaid(),
+    };
+    match args.output {
+        Some(path) => fs::write(&path, text).map_err(|e| Error::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?,
+        None => print!("{}", text),
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,65 @@
+use clap::{Parser, Subcommand};
+use kataru::Error;
+use std::process::ExitCode;
+
+mod graph;
+mod pack;
+mod play;
+mod stats;
+mod story;
+mod strings;
+mod validate;
+
+/// Command line tools for Kataru stories.
+///
+/// Exits with 0 on success, 1 if the story is invalid or fails while playing,
+/// and 2 for invalid arguments or files that can't be read.
+#[derive(Parser)]
+#[command(name = "kataru", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Report every error and warning in a story.
+    Validate(validate::ValidateArgs),
+    /// Pack a project's story and bookmark into MessagePack files.
+    Pack(pack::PackArgs),
+    /// Play a story interactively in the terminal.
+    Play(play::PlayArgs),
+    /// Export the passage graph of a story as Graphviz DOT or Mermaid.
+    Graph(graph::GraphArgs),
+    /// Print counts of the content in a story.
+    Stats(stats::StatsArgs),
+    /// Export the dialogue and choices of a story for translation as CSV, PO or XLIFF.
+    Strings(strings::StringsArgs),
+}
+
+/// Exit code for errors that stop a subcommand from running.
+fn exit_code(error: &Error) -> ExitCode {
+    match error.inner() {
+        Error::Io { .. } => ExitCode::from(2),
+        _ => ExitCode::FAILURE,
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Validate(args) => validate::run(args),
+        Command::Pack(args) => pack::run(args),
+        Command::Play(args) => play::run(args),
+        Command::Graph(args) => graph::run(args),
+        Command::Stats(args) => stats::run(args),
+        Command::Strings(args) => strings::run(args),
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit_code(&e)
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use clap::Args;
+use kataru::{pack, Error, Result};
+use std::{fs, path::PathBuf, process::ExitCode};
+
+#[derive(Args)]
+pub struct PackArgs {
+    /// Project directory containing a `story` directory and `bookmark.yml`.
+    dir: PathBuf,
+    /// Directory to write the packed `story` and `bookmark` files to.
+    out: PathBuf,
+}
+
+pub fn run(args: PackArgs) -> Result<ExitCode> {
+    fs::create_dir_all(&args.out).map_err(|e| Error::Io {
+        path: args.out.display().to_string(),
+        message: e.to_string(),
+    })?;
+    pack(&args.dir.to_string_lossy(), &args.out.to_string_lossy())?;
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,119 @@
+use crate::story::{load_bookmark, load_locale, load_story};
+use clap::Args;
+use colored::*;
+use kataru::{Choices, Command, Line, Result, Runner, Validator};
+use std::{
+    io::{stdin, stdout, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+};
+
+#[derive(Args)]
+pub struct PlayArgs {
+    /// Story directory, section YAML file or packed MessagePack file.
+    story: PathBuf,
+    /// Bookmark to start playing from.
+    #[arg(short, long)]
+    bookmark: Option<PathBuf>,
+    /// Ignore operations on values of the wrong type instead of stopping with an error.
+    #[arg(long)]
+    lenient: bool,
+    /// Translations to play with, as exported by `strings` and filled in.
+    #[arg(long)]
+    locale: Option<PathBuf>,
+}
+
+/// Reads a line from stdin without its line ending. Returns None at end of input.
+fn get_input() -> Option<String> {
+    let _ = stdout().flush();
+    let mut input = String::new();
+    match stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim_end_matches(&['\r', '\n'][..]).to_string()),
+    }
+}
+
+/// Lets the player pick a choice by its number as well as its text.
+fn choice_input(choices: &Choices, input: String) -> String {
+    match input.trim().parse::<usize>() {
+        Ok(i) if i >= 1 && i <= choices.len() => choices.choices[i - 1].clone(),
+        _ => input,
+    }
+}
+
+fn print_command(command: &Command) {
+    let params: Vec<String> = command
+        .params
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect();
+    println!(
+        "{}",
+        format!("[{} {{ {} }}]", command.name, params.join(", ")).italic()
+    );
+}
+
+pub fn run(args: PlayArgs) -> Result<ExitCode> {
+    let story = load_story(&args.story)?;
+    let mut bookmark = load_bookmark(&args.story, args.bookmark.as_ref(), &story)?;
+    bookmark.lenient = args.lenient;
+    if let Some(path) = &args.locale {
+        bookmark.locale = Arc::new(load_locale(path)?);
+    }
+    if let Err(e) = Validator::new(&story, &mut bookmark).validate() {
+        eprintln!("{}", e);
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let mut runner = Runner::new(&mut bookmark, &story)?;
+    let mut input = String::new();
+    let mut choices = Choices::default();
+    loop {
+        let line = runner.next(&input)?;
+        input.clear();
+        let next_input = match line {
+            Line::Dialogue(dialogue) => {
+                if dialogue.name.is_empty() {
+                    print!("{}", dialogue.text.italic());
+                } else {
+                    print!("{}: {}", dialogue.name.bold().yellow(), dialogue.text);
+                }
+                get_input().map(|_| String::new())
+            }
+            Line::Choices(line_choices) => {
+                choices = line_choices;
+                println!();
+                for (i, choice) in choices.choices.iter().enumerate() {
+                    println!("{} {}", format!("{}.", i + 1).cyan(), choice.cyan());
+                }
+                print!("\n{}", "Enter your choice: ".bold().magenta());
+                get_input().map(|choice| choice_input(&choices, choice))
+            }
+            Line::InvalidChoice => {
+                print!("{}", "Invalid choice, try again: ".magenta());
+                get_input().map(|choice| choice_input(&choices, choice))
+            }
+            Line::Input(input_cmd) => {
+                let mut value = Some(String::new());
+                for prompt in input_cmd.input.values() {
+                    print!("{}: ", prompt.bold().magenta());
+                    value = get_input();
+                }
+                value
+            }
+            Line::Command(command) => {
+                print_command(&command);
+                Some(String::new())
+            }
+            Line::End => {
+                println!("{}", "End of story.".bold());
+                None
+            }
+        };
+        match next_input {
+            Some(next_input) => input = next_input,
+            None => return Ok(ExitCode::SUCCESS),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use crate::story::load_story;
+use clap::Args;
+use kataru::{Result, StoryStats};
+use std::{path::PathBuf, process::ExitCode};
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Story directory, section YAML file or packed MessagePack file.
+    story: PathBuf,
+}
+
+pub fn run(args: StatsArgs) -> Result<ExitCode> {
+    let story = load_story(&args.story)?;
+    println!("{}", StoryStats::new(&story));
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,50 @@
+use kataru::{Bookmark, Error, Load, Locale, Result, Story};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Loads a story from a directory, section YAML file or packed MessagePack file.
+/// A project directory laid out the way `pack` expects, with a `story` subdirectory, also works.
+pub fn load_story(path: &Path) -> Result<Story> {
+    let story_dir = path.join("story");
+    if story_dir.is_dir() {
+        Story::load(story_dir)
+    } else {
+        Story::load(path)
+    }
+}
+
+/// Loads the bookmark at `bookmark`, or the project's `bookmark.yml` if there is one.
+/// Otherwise starts at the global `Start` passage.
+pub fn load_bookmark(
+    story_path: &Path,
+    bookmark: Option<&PathBuf>,
+    story: &Story,
+) -> Result<Bookmark> {
+    let project_bookmark = story_path.join("bookmark.yml");
+    let mut bookmark = match bookmark {
+        Some(path) => Bookmark::load(path)?,
+        None if project_bookmark.is_file() => Bookmark::load(project_bookmark)?,
+        None => {
+            let mut bookmark = Bookmark::default();
+            bookmark.set_passage("Start".to_string());
+            bookmark
+        }
+    };
+    bookmark.init_state(story);
+    Ok(bookmark)
+}
+
+/// Loads translations from a CSV or PO file exported by `strings`, or a YAML or MessagePack table.
+pub fn load_locale(path: &Path) -> Result<Locale> {
+    let read = || {
+        fs::read_to_string(path).map_err(|e| Error::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })
+    };
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Locale::from_csv(&read()?),
+        Some("po") => Locale::from_po(&read()?),
+        _ => Locale::load(path),
+    }
+}
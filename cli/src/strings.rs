@@ -0,0 +1,44 @@
+use crate::story::load_story;
+use clap::{Args, ValueEnum};
+use kataru::{Error, Result, StringTable};
+use std::{fs, path::PathBuf, process::ExitCode};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Csv,
+    Po,
+    Xliff,
+}
+
+#[derive(Args)]
+pub struct StringsArgs {
+    /// Story directory, section YAML file or packed MessagePack file.
+    story: PathBuf,
+    /// Output format.
+    #[arg(short, long, value_enum, default_value = "csv")]
+    format: Format,
+    /// Language the story is written in, for XLIFF.
+    #[arg(long, default_value = "en")]
+    source_language: String,
+    /// File to write the strings to. Defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(args: StringsArgs) -> Result<ExitCode> {
+    let story = load_story(&args.story)?;
+    let table = StringTable::new(&story);
+    let text = match args.format {
+        Format::Csv => table.to_csv(),
+        Format::Po => table.to_po(),
+        Format::Xliff => table.to_xliff(&args.source_language),
+    };
+    match args.output {
+        Some(path) => fs::write(&path, text).map_err(|e| Error::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?,
+        None => print!("{}", text),
+    }
+    Ok(ExitCode::SUCCESS)
+}
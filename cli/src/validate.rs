@@ -0,0 +1,43 @@
+use crate::story::{load_bookmark, load_story};
+use clap::Args;
+use kataru::{Analyzer, Diagnostic, Result, Severity, Validator};
+use std::{path::PathBuf, process::ExitCode};
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// Story directory, section YAML file or packed MessagePack file.
+    story: PathBuf,
+    /// Bookmark to analyze reachability from.
+    #[arg(short, long)]
+    bookmark: Option<PathBuf>,
+    /// Fail if there are any warnings.
+    #[arg(long)]
+    deny_warnings: bool,
+}
+
+pub fn run(args: ValidateArgs) -> Result<ExitCode> {
+    let story = load_story(&args.story)?;
+    let mut bookmark = load_bookmark(&args.story, args.bookmark.as_ref(), &story)?;
+
+    let (mut errors, mut warnings): (Vec<Diagnostic>, Vec<Diagnostic>) =
+        Validator::new(&story, &mut bookmark)
+            .validate_all()
+            .into_iter()
+            .partition(|diagnostic| diagnostic.severity == Severity::Error);
+    warnings.extend(Analyzer::new(&story, &bookmark).analyze());
+    for diagnostics in [&mut errors, &mut warnings] {
+        diagnostics.sort_by(|a: &Diagnostic, b: &Diagnostic| {
+            (&a.namespace, &a.passage, a.line).cmp(&(&b.namespace, &b.passage, b.line))
+        });
+    }
+    for diagnostic in errors.iter().chain(&warnings) {
+        eprintln!("{}", diagnostic);
+    }
+    eprintln!("{} error(s), {} warning(s).", errors.len(), warnings.len());
+
+    if !errors.is_empty() || (args.deny_warnings && !warnings.is_empty()) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,39 @@
+use std::process::Command;
+
+const STORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data/analyzer");
+
+/// Tests exporting a story directory as DOT and Mermaid.
+#[test]
+fn test_graph() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["graph", STORY])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let dot = String::from_utf8(output.stdout).unwrap();
+    assert!(dot.starts_with("digraph story {"));
+    assert!(dot.contains("subgraph \"cluster_chapter\""));
+    assert!(dot.contains("\"global:Start\" -> \"chapter:Begin\" [label = \"Chapter\"];"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["graph", "--format", "mermaid", STORY])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("flowchart TD"));
+}
+
+/// Tests that a missing story exits with an error.
+#[test]
+fn test_graph_missing_story() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["graph", "does/not/exist.mp"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("error:"));
+}
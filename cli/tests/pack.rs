@@ -0,0 +1,16 @@
+use kataru::{Bookmark, FromMessagePack, Story};
+use std::{fs, process::Command};
+
+/// Tests packing a project into MessagePack files.
+#[test]
+fn test_pack() {
+    let project = concat!(env!("CARGO_MANIFEST_DIR"), "/../examples/simple/kataru");
+    let out = concat!(env!("CARGO_TARGET_TMPDIR"), "/pack");
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["pack", project, out])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    Story::from_mp(&fs::read(format!("{}/story", out)).unwrap()).unwrap();
+    Bookmark::from_mp(&fs::read(format!("{}/bookmark", out)).unwrap()).unwrap();
+}
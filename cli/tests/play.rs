@@ -0,0 +1,58 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+const STORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data/analyzer");
+
+/// Tests playing through a story, picking a choice by number.
+#[test]
+fn test_play() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["play", STORY])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"\n\n1\n\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = [
+        "Hi!",
+        "Welcome.",
+        "Good",
+        "Chapter",
+        "The end.",
+        "End of story.",
+    ];
+    let mut rest = stdout.as_str();
+    for text in &expected {
+        let index = rest.find(text).unwrap();
+        rest = &rest[index + text.len()..];
+    }
+}
+
+/// Tests playing a story with translations.
+#[test]
+fn test_play_locale() {
+    let story = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data/locale");
+    let locale = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data/locale/fr.yml");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["play", story, "--locale", locale])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"\n\n1\n\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut rest = stdout.as_str();
+    for text in &["Bonjour Bob.", "Where to?", "Boutique", "Welcome."] {
+        let index = rest.find(text).unwrap();
+        rest = &rest[index + text.len()..];
+    }
+}
@@ -0,0 +1,18 @@
+use std::process::Command;
+
+/// Tests printing content counts for a story.
+#[test]
+fn test_stats() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args([
+            "stats",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data/analyzer"),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Namespaces:     2\n"));
+    assert!(stdout.contains("Passages:       9\n"));
+    assert!(stdout.contains("Choices:        6\n"));
+}
@@ -0,0 +1,41 @@
+use std::process::Command;
+
+const STORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data/locale");
+
+/// Tests exporting a story's strings as CSV, PO and XLIFF.
+#[test]
+fn test_strings() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["strings", STORY])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let csv = String::from_utf8(output.stdout).unwrap();
+    assert!(csv.starts_with("id,namespace,passage,speaker,text,translation\n"));
+    assert!(csv.contains("greeting,global,Start,Alice,Hello {$name}.,\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["strings", "--format", "po", STORY])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("msgctxt \"shop\"\nmsgid \"Shop\"\nmsgstr \"\"\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args([
+            "strings",
+            "--format",
+            "xliff",
+            "--source-language",
+            "ja",
+            STORY,
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("source-language=\"ja\""));
+}
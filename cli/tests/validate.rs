@@ -0,0 +1,62 @@
+use std::process::Command;
+
+const DATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/data");
+
+/// Tests that an invalid story reports every error and exits with 1.
+#[test]
+fn test_validate_errors() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["validate", &format!("{}/validator", DATA)])
+        .args(["--bookmark", &format!("{}/bookmark.yml", DATA)])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("error: ").count(), 5);
+    assert!(stderr.contains("story.yml:13:5: error: Passage 'global:Start' Line 2"));
+    assert!(stderr.ends_with("5 error(s), 1 warning(s).\n"));
+}
+
+/// Tests that warnings only fail validation with `--deny-warnings`.
+#[test]
+fn test_validate_warnings() {
+    let story = format!("{}/analyzer", DATA);
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["validate", &story])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .ends_with("0 error(s), 3 warning(s).\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["validate", "--deny-warnings", &story])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+/// Tests that passages listed under `endings` aren't reported as dead ends.
+#[test]
+fn test_validate_endings() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["validate", "--deny-warnings", &format!("{}/messages", DATA)])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stderr).unwrap().ends_with(
+        "0 error(s), 0 warning(s).
+"
+    ));
+}
+
+/// Tests that a story that can't be read exits with 2.
+#[test]
+fn test_validate_missing_story() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kataru"))
+        .args(["validate", "does/not/exist.mp"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,25 @@
+use std::{env, path::Path};
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let kataru_dir = Path::new(&crate_dir).parent().unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!(
+        "cargo:rerun-if-changed={}",
+        kataru_dir.join("src").display()
+    );
+
+    // Write the header next to the libraries, in `target/<profile>/`,
+    // which is three levels above the `build/kataru-ffi-*/out/` directory.
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let profile_dir = Path::new(&out_dir).ancestors().nth(3).unwrap();
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml"))
+        .expect("Unable to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(kataru_dir)
+        .with_config(config)
+        .generate()
+        .expect("Unable to generate the C header")
+        .write_to_file(profile_dir.join("kataru.h"));
+}
@@ -0,0 +1,2 @@
+//! Kataru's C interface built as a shared and a static library. See `kataru::ffi`.
+pub use kataru::ffi::*;
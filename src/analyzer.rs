@@ -0,0 +1,310 @@
+use crate::{
+    error::Error,
+    graph::{PassageId, StoryGraph},
+    structs::{Bookmark, Story},
+    validator::{Diagnostic, Severity},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Finds structural problems in a story's passage graph:
+/// passages that can't be reached, passages that always end the story without a choice,
+/// and cycles of unconditional calls that can never be left.
+/// Unlike `Validator`, every finding is a warning since the story still runs.
+pub struct Analyzer<'a> {
+    story: &'a Story,
+    graph: StoryGraph,
+    start: Option<PassageId>,
+}
+
+impl<'a> Analyzer<'a> {
+    /// Builds the passage graph for `story`, starting from `bookmark`'s current passage.
+    pub fn new(story: &'a Story, bookmark: &Bookmark) -> Self {
+        let graph = StoryGraph::new(story);
+        let start = graph.resolve(bookmark.namespace(), bookmark.passage());
+        Self {
+            story,
+            graph,
+            start,
+        }
+    }
+
+    pub fn graph(&self) -> &StoryGraph {
+        &self.graph
+    }
+
+    /// Returns every passage reachable from the start passage.
+    /// If the start passage doesn't exist, every passage is considered reachable.
+    pub fn reachable(&self) -> BTreeSet<&PassageId> {
+        let start = match &self.start {
+            Some(start) => start,
+            None => return self.graph.passages.keys().collect(),
+        };
+        let mut reachable = BTreeSet::new();
+        let mut queue = vec![start];
+        while let Some(id) = queue.pop() {
+            if reachable.insert(id) {
+                for edge in self.graph.edges(id) {
+                    queue.push(&edge.to);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Returns passages that can never be reached from the start passage.
+    pub fn unreachable(&self) -> Vec<&PassageId> {
+        let reachable = self.reachable();
+        self.graph
+            .passages
+            .keys()
+            .filter(|id| !reachable.contains(id))
+            .collect()
+    }
+
+    /// Returns passages that can present a choice before they return,
+    /// either directly or through a passage they call.
+    fn reaches_choice(&self) -> BTreeSet<&PassageId> {
+        let mut reaches_choice: BTreeSet<&PassageId> = self
+            .graph
+            .passages
+            .iter()
+            .filter(|(_id, node)| !node.choice_lines.is_empty())
+            .map(|(id, _node)| id)
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (id, node) in &self.graph.passages {
+                if !reaches_choice.contains(id)
+                    && node
+                        .edges
+                        .iter()
+                        .any(|edge| reaches_choice.contains(&edge.to))
+                {
+                    reaches_choice.insert(id);
+                    changed = true;
+                }
+            }
+        }
+        reaches_choice
+    }
+
+    /// Returns passages that can return to a caller that goes on to present a choice.
+    /// Any line after the call site is treated as possibly running afterwards.
+    fn returns_to_choice<'g>(
+        &'g self,
+        reachable: &BTreeSet<&'g PassageId>,
+        reaches_choice: &BTreeSet<&'g PassageId>,
+    ) -> BTreeSet<&'g PassageId> {
+        let mut returns_to_choice: BTreeSet<&PassageId> = BTreeSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (caller, node) in &self.graph.passages {
+                if !reachable.contains(caller) {
+                    continue;
+                }
+                for edge in &node.edges {
+                    if returns_to_choice.contains(&edge.to) {
+                        continue;
+                    }
+                    let resumes_to_choice = returns_to_choice.contains(caller)
+                        || node.choice_lines.iter().any(|line| *line > edge.line)
+                        || node
+                            .edges
+                            .iter()
+                            .any(|next| next.line > edge.line && reaches_choice.contains(&next.to));
+                    if resumes_to_choice {
+                        returns_to_choice.insert(&edge.to);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        returns_to_choice
+    }
+
+    /// Returns reachable passages from which the story always ends without another choice.
+    /// Passages listed in their section's `endings` are meant to end the story, so they are left out.
+    /// Passages in a cycle never end, so they are reported by `cycles` instead.
+    pub fn dead_ends(&self) -> Vec<&PassageId> {
+        let reachable = self.reachable();
+        let reaches_choice = self.reaches_choice();
+        let returns_to_choice = self.returns_to_choice(&reachable, &reaches_choice);
+        let in_cycle: BTreeSet<&PassageId> = self.cycles().into_iter().flatten().collect();
+        reachable
+            .iter()
+            .filter(|id| {
+                !reaches_choice.contains(*id)
+                    && !returns_to_choice.contains(*id)
+                    && !in_cycle.contains(*id)
+                    && !self.is_ending(id)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Whether the passage `id` is configured as an ending of the story.
+    fn is_ending(&self, id: &PassageId) -> bool {
+        self.story
+            .get(&id.namespace)
+            .is_some_and(|section| section.is_ending(&id.passage))
+    }
+
+    /// Returns each group of passages that unconditionally call each other,
+    /// so that entering any of them loops forever.
+    pub fn cycles(&self) -> Vec<Vec<&PassageId>> {
+        let ids: Vec<&PassageId> = self.graph.passages.keys().collect();
+        let indices: BTreeMap<&PassageId, usize> =
+            ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let successors: Vec<Vec<usize>> = self
+            .graph
+            .passages
+            .values()
+            .map(|node| {
+                node.edges
+                    .iter()
+                    .filter(|edge| edge.unconditional)
+                    .map(|edge| indices[&edge.to])
+                    .collect()
+            })
+            .collect();
+
+        let mut tarjan = Tarjan::new(ids.len());
+        for v in 0..ids.len() {
+            if tarjan.index[v].is_none() {
+                tarjan.visit(v, &successors);
+            }
+        }
+
+        let mut cycles: Vec<Vec<&PassageId>> = tarjan
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || successors[component[0]].contains(&component[0])
+            })
+            .map(|component| {
+                let mut cycle: Vec<&PassageId> = component.into_iter().map(|v| ids[v]).collect();
+                cycle.sort();
+                cycle
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    fn warning(&self, id: &PassageId, line: usize, error: Error) -> Diagnostic {
+        let span = self
+            .story
+            .get(&id.namespace)
+            .and_then(|section| section.span(&id.passage, line))
+            .cloned();
+        Diagnostic {
+            namespace: id.namespace.clone(),
+            passage: id.passage.clone(),
+            line,
+            error,
+            severity: Severity::Warning,
+            span,
+        }
+    }
+
+    /// Runs every analysis, returning a warning for each problem found.
+    pub fn analyze(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for id in self.unreachable() {
+            diagnostics.push(self.warning(
+                id,
+                0,
+                Error::UnreachablePassage {
+                    passage: id.passage.clone(),
+                    namespace: id.namespace.clone(),
+                },
+            ));
+        }
+        for id in self.dead_ends() {
+            diagnostics.push(self.warning(
+                id,
+                0,
+                Error::DeadEnd {
+                    passage: id.passage.clone(),
+                    namespace: id.namespace.clone(),
+                },
+            ));
+        }
+        for cycle in self.cycles() {
+            // Point at the call that enters the rest of the cycle.
+            let first = cycle[0];
+            let line = self
+                .graph
+                .edges(first)
+                .iter()
+                .find(|edge| edge.unconditional && cycle.contains(&&edge.to))
+                .map_or(0, |edge| edge.line);
+            diagnostics.push(self.warning(
+                first,
+                line,
+                Error::InescapableCycle {
+                    passages: cycle.iter().map(|id| id.to_string()).collect(),
+                },
+            ));
+        }
+        diagnostics
+    }
+}
+
+/// Tarjan's algorithm for strongly connected components.
+struct Tarjan {
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan {
+    fn new(len: usize) -> Self {
+        Self {
+            index: vec![None; len],
+            low_link: vec![0; len],
+            on_stack: vec![false; len],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, v: usize, successors: &[Vec<usize>]) {
+        self.index[v] = Some(self.next_index);
+        self.low_link[v] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(v);
+        self.on_stack[v] = true;
+
+        for &w in &successors[v] {
+            match self.index[w] {
+                None => {
+                    self.visit(w, successors);
+                    self.low_link[v] = self.low_link[v].min(self.low_link[w]);
+                }
+                Some(w_index) if self.on_stack[w] => {
+                    self.low_link[v] = self.low_link[v].min(w_index);
+                }
+                _ => (),
+            }
+        }
+
+        if Some(self.low_link[v]) == self.index[v] {
+            let mut component = Vec::new();
+            while let Some(w) = self.stack.pop() {
+                self.on_stack[w] = false;
+                component.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
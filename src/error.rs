@@ -1,20 +1,286 @@
+use crate::{structs::Span, Value};
 use std::fmt;
-use std::hash::Hash;
 
 /// Error type for validating the kataru yml script.
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum Error {
     Generic(String),
-    Pest(String),
+    /// A `$variable` that isn't defined in `namespace` or any of its parents.
+    UndefinedVariable {
+        var: String,
+        namespace: String,
+    },
+    /// A namespace that has no section in the story or state in the bookmark.
+    UnknownNamespace {
+        namespace: String,
+    },
+    /// A passage that couldn't be resolved from `namespace`.
+    UnknownPassage {
+        passage: String,
+        namespace: String,
+    },
+    /// A character that couldn't be resolved from `namespace`.
+    UnknownCharacter {
+        character: String,
+        namespace: String,
+    },
+    /// A command that couldn't be resolved from `namespace`.
+    UnknownCommand {
+        command: String,
+        namespace: String,
+    },
+    /// A command named after a kind of variation, such as `once`,
+    /// which lines can't call since they play as a variation instead.
+    ReservedCommand {
+        command: String,
+        namespace: String,
+    },
+    /// A parameter that isn't configured for `command`.
+    BadParameter {
+        command: String,
+        param: String,
+    },
+    /// A parameter that isn't configured for text attribute `attribute`.
+    BadAttributeParameter {
+        attribute: String,
+        param: String,
+    },
+    /// A value that doesn't have the type its context requires.
+    TypeMismatch {
+        expected: String,
+        found: Value,
+    },
+    /// Failure reading or writing the file at `path`.
+    Io {
+        path: String,
+        message: String,
+    },
+    /// Malformed YAML. The location, if known, is attached with `Error::Located`.
+    YamlSyntax {
+        message: String,
+    },
+    /// An expression that couldn't be parsed.
+    ExpressionSyntax {
+        expr: String,
+        column: usize,
+    },
+    /// An index that's out of range for a list, or a key that's missing from a map.
+    BadIndex {
+        value: Value,
+        index: Value,
+    },
+    /// An operator applied to values it doesn't support, such as subtracting a number from a string.
+    InvalidOperation {
+        operator: String,
+        lhs: Value,
+        rhs: Value,
+    },
+    /// A number divided by zero.
+    DivisionByZero,
+    /// A function called in an expression that doesn't exist.
+    UnknownFunction {
+        function: String,
+    },
+    /// A function called with the wrong number of arguments.
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A passage that can never be reached from the start passage.
+    UnreachablePassage {
+        passage: String,
+        namespace: String,
+    },
+    /// A passage from which the story always ends without offering another choice.
+    DeadEnd {
+        passage: String,
+        namespace: String,
+    },
+    /// Passages that unconditionally call each other, so the story can never leave them.
+    InescapableCycle {
+        passages: Vec<String>,
+    },
+    /// An explicit line ID already given to a line in passage `passage` of `namespace`.
+    DuplicateLineId {
+        id: String,
+        namespace: String,
+        passage: String,
+    },
+    /// An error on line `line` (starting at 1) of a passage.
+    InPassage {
+        namespace: String,
+        passage: String,
+        line: usize,
+        error: Box<Error>,
+    },
+    /// An error at a known location in a source file.
+    Located(Span, Box<Error>),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// Attaches the source location `span` to this error.
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            Self::Located(_, error) => Self::Located(span, error),
+            error => Self::Located(span, Box::new(error)),
+        }
+    }
+
+    /// Places this error in `file`, shifting a relative location by `line_offset` lines.
+    /// Errors without a location are located at the file itself.
+    pub fn in_file(self, file: &str, line_offset: usize) -> Self {
+        match self {
+            Self::Located(span, error) => {
+                Self::Located(Span::new(file, span.line + line_offset, span.column), error)
+            }
+            error => error.at(Span::new(file, 0, 0)),
+        }
+    }
+
+    /// Returns the source location of this error, if known.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            Self::Located(span, _) => Some(span),
+            _ => None,
+        }
+    }
+
+    /// Returns this error without its source location or passage.
+    pub fn inner(&self) -> &Self {
+        match self {
+            Self::Located(_, error) | Self::InPassage { error, .. } => error.inner(),
+            error => error,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Generic(message) => write!(f, "{}", message),
-            Self::Pest(message) => write!(f, "{}", message),
+            Self::UndefinedVariable { var, namespace } => write!(
+                f,
+                "Variable '{}' could not be found in namespace '{}' nor any of its parents.",
+                var, namespace
+            ),
+            Self::UnknownNamespace { namespace } => {
+                write!(f, "Namespace '{}' does not exist.", namespace)
+            }
+            Self::UnknownPassage { passage, namespace } => write!(
+                f,
+                "Passage '{}' could not be found from namespace '{}'.",
+                passage, namespace
+            ),
+            Self::UnknownCharacter {
+                character,
+                namespace,
+            } => write!(
+                f,
+                "Character '{}' could not be found from namespace '{}'.",
+                character, namespace
+            ),
+            Self::UnknownCommand { command, namespace } => write!(
+                f,
+                "Command '{}' could not be found from namespace '{}'.",
+                command, namespace
+            ),
+            Self::ReservedCommand { command, namespace } => write!(
+                f,
+                "Command '{}' from namespace '{}' is named after a variation, so this line plays as a variation.",
+                command, namespace
+            ),
+            Self::BadParameter { command, param } => write!(
+                f,
+                "No such parameter '{}' for command '{}'.",
+                param, command
+            ),
+            Self::BadAttributeParameter { attribute, param } => write!(
+                f,
+                "No such parameter '{}' for attribute <{}>.",
+                param, attribute
+            ),
+            Self::TypeMismatch { expected, found } => write!(
+                f,
+                "Expected {} but found '{}' ({}).",
+                expected,
+                found,
+                found.type_name()
+            ),
+            Self::Io { path, message } => write!(f, "Error accessing '{}': {}", path, message),
+            Self::YamlSyntax { message } => write!(f, "Invalid YAML: {}", message),
+            Self::ExpressionSyntax { expr, column } => {
+                write!(f, "Invalid expression '{}' at column {}.", expr, column)
+            }
+            Self::BadIndex { value, index } => {
+                write!(f, "Index '{}' not found in '{}'.", index, value)
+            }
+            Self::InvalidOperation { operator, lhs, rhs } => write!(
+                f,
+                "Cannot apply '{}' to {} '{}' and {} '{}'.",
+                operator,
+                lhs.type_name(),
+                lhs,
+                rhs.type_name(),
+                rhs
+            ),
+            Self::DivisionByZero => write!(f, "Division by zero."),
+            Self::UnknownFunction { function } => {
+                write!(f, "Function '{}' does not exist.", function)
+            }
+            Self::WrongArgumentCount {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function '{}' takes {} argument(s) but {} were given.",
+                function, expected, found
+            ),
+            Self::UnreachablePassage { passage, namespace } => write!(
+                f,
+                "Passage '{}:{}' can never be reached from the start passage.",
+                namespace, passage
+            ),
+            Self::DeadEnd { passage, namespace } => write!(
+                f,
+                "Passage '{}:{}' always ends the story without offering a choice. List it under `endings` if it's meant to.",
+                namespace, passage
+            ),
+            Self::InescapableCycle { passages } => match passages.as_slice() {
+                [passage] => write!(
+                    f,
+                    "Passage '{}' unconditionally calls itself and can never be left.",
+                    passage
+                ),
+                _ => write!(
+                    f,
+                    "Passages '{}' call each other unconditionally and can never be left.",
+                    passages.join("', '")
+                ),
+            },
+            Self::DuplicateLineId {
+                id,
+                namespace,
+                passage,
+            } => write!(
+                f,
+                "Line ID '{}' is already used in passage '{}:{}'.",
+                id, namespace, passage
+            ),
+            Self::InPassage {
+                namespace,
+                passage,
+                line,
+                error,
+            } => write!(
+                f,
+                "Passage '{}:{}' Line {}: {}",
+                namespace, passage, line, error
+            ),
+            Self::Located(span, error) => write!(f, "{}: {}", span, error),
         }
     }
 }
@@ -25,15 +291,6 @@ impl fmt::Debug for Error {
     }
 }
 
-impl<R> From<pest::error::Error<R>> for Error
-where
-    R: Ord + Copy + fmt::Debug + Hash,
-{
-    fn from(pest_error: pest::error::Error<R>) -> Self {
-        Self::Pest(pest_error.to_string())
-    }
-}
-
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => {{
@@ -0,0 +1,598 @@
+//! C interface for embedding Kataru in native engines, enabled by the `ffi` feature.
+//! The `kataru-ffi` crate builds it as a library along with the matching header `kataru.h`.
+//!
+//! Functions that can fail return false or null, after which `kataru_last_error` describes why.
+//! Panics are caught before they reach C and reported the same way.
+//! Strings returned by the line accessors belong to the runner and stay valid until
+//! the next call to `kataru_runner_next` or `kataru_runner_free`.
+use crate::{
+    Bookmark, Error, Line, LineTag, Load, OffsetUnit, OwnedRunner, Result, Save, Story, Value,
+};
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+};
+
+/// A story shared by every runner created from it.
+pub struct KataruStory(Arc<Story>);
+
+/// A save of the state and position in a story.
+pub struct KataruBookmark(Bookmark);
+
+/// A runner with its own copy of a bookmark, along with the line it's on.
+pub struct KataruRunner {
+    runner: OwnedRunner,
+    line: Line,
+    strings: LineStrings,
+}
+
+/// C strings for a name and a value, such as a command parameter.
+struct ParamStrings {
+    name: CString,
+    value: CString,
+}
+
+/// C strings for the current line, kept alive until the runner moves on.
+#[derive(Default)]
+struct LineStrings {
+    speaker: CString,
+    text: CString,
+    attributes: Vec<(CString, Vec<ParamStrings>)>,
+    choices: Vec<CString>,
+    command: CString,
+    params: Vec<ParamStrings>,
+}
+
+impl LineStrings {
+    fn new(line: &Line) -> Self {
+        let params = |params: &crate::Params| -> Vec<ParamStrings> {
+            params
+                .iter()
+                .map(|(name, value)| ParamStrings {
+                    name: c_string(name),
+                    value: c_string(&value.to_string()),
+                })
+                .collect()
+        };
+        match line {
+            Line::Dialogue(dialogue) => Self {
+                speaker: c_string(&dialogue.name),
+                text: c_string(&dialogue.text),
+                attributes: dialogue
+                    .attributes
+                    .iter()
+                    .map(|attribute| (c_string(&attribute.name), params(&attribute.params)))
+                    .collect(),
+                ..Self::default()
+            },
+            Line::Choices(choices) => Self {
+                choices: choices
+                    .choices
+                    .iter()
+                    .map(|choice| c_string(choice))
+                    .collect(),
+                ..Self::default()
+            },
+            Line::Command(command) => Self {
+                command: c_string(&command.name),
+                params: params(&command.params),
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+/// Type of a `KataruValue`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KataruValueKind {
+    String,
+    Int,
+    Number,
+    Bool,
+    List,
+    Map,
+}
+
+/// A value passed to a command or attribute.
+/// `string` holds every kind of value as text, `number` holds integers and numbers,
+/// and `boolean` holds bools.
+#[repr(C)]
+pub struct KataruValue {
+    pub kind: KataruValueKind,
+    pub string: *const c_char,
+    pub number: f64,
+    pub boolean: bool,
+}
+
+/// A named parameter of a command or attribute.
+#[repr(C)]
+pub struct KataruParam {
+    pub name: *const c_char,
+    pub value: KataruValue,
+}
+
+/// A span of dialogue text marked up with an attribute.
+/// Its parameters are read with `kataru_dialogue_attribute_param`.
+#[repr(C)]
+pub struct KataruAttribute {
+    pub name: *const c_char,
+    pub start: usize,
+    pub end: usize,
+    pub params_len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Converts `text` to a C string, dropping any nul bytes inside it.
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+fn set_last_error(message: &str) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(c_string(message)));
+}
+
+/// Returns the value of `result`, or records its error and returns None.
+fn check<T>(result: Result<T>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(error) => {
+            set_last_error(&error.to_string());
+            None
+        }
+    }
+}
+
+/// Runs `body`, or records its panic and returns `fallback`, since unwinding into C is undefined.
+fn guard<T>(fallback: T, body: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(&format!("Kataru panicked: {}", message));
+            fallback
+        }
+    }
+}
+
+/// Reads the C string `text`, treating null as empty.
+unsafe fn read_str<'a>(text: *const c_char) -> Result<&'a str> {
+    if text.is_null() {
+        return Ok("");
+    }
+    CStr::from_ptr(text)
+        .to_str()
+        .map_err(|_| error!("String passed to Kataru is not valid UTF-8"))
+}
+
+/// Moves `value` to the heap for C to own, or returns null if there was an error.
+fn into_raw<T>(value: Option<T>) -> *mut T {
+    match value {
+        Some(value) => Box::into_raw(Box::new(value)),
+        None => ptr::null_mut(),
+    }
+}
+
+fn c_param(strings: &ParamStrings, value: &Value) -> KataruParam {
+    let (kind, number, boolean) = match value {
+        Value::String(_) => (KataruValueKind::String, 0.0, false),
+        Value::Int(n) => (KataruValueKind::Int, *n as f64, false),
+        Value::Number(n) => (KataruValueKind::Number, *n, false),
+        Value::Bool(b) => (KataruValueKind::Bool, 0.0, *b),
+        Value::List(_) => (KataruValueKind::List, 0.0, false),
+        Value::Map(_) => (KataruValueKind::Map, 0.0, false),
+    };
+    KataruParam {
+        name: strings.name.as_ptr(),
+        value: KataruValue {
+            kind,
+            string: strings.value.as_ptr(),
+            number,
+            boolean,
+        },
+    }
+}
+
+fn empty_attribute() -> KataruAttribute {
+    KataruAttribute {
+        name: ptr::null(),
+        start: 0,
+        end: 0,
+        params_len: 0,
+    }
+}
+
+fn empty_param() -> KataruParam {
+    KataruParam {
+        name: ptr::null(),
+        value: KataruValue {
+            kind: KataruValueKind::String,
+            string: ptr::null(),
+            number: 0.0,
+            boolean: false,
+        },
+    }
+}
+
+/// Returns the message of the last error on this thread, or null if there hasn't been one.
+/// The message stays valid until the next error on this thread.
+#[no_mangle]
+pub extern "C" fn kataru_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|last| match &*last.borrow() {
+            Some(message) => message.as_ptr(),
+            None => ptr::null(),
+        })
+    })
+}
+
+/// Loads a story from a YAML directory or file, or a MessagePack file.
+/// Returns null on error.
+///
+/// # Safety
+/// `path` must be null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_story_load(path: *const c_char) -> *mut KataruStory {
+    guard(ptr::null_mut(), || {
+        into_raw(
+            check(read_str(path).and_then(Story::load)).map(|story| KataruStory(Arc::new(story))),
+        )
+    })
+}
+
+/// Frees a story. Runners created from it keep their own reference to it.
+///
+/// # Safety
+/// `story` must be null or returned by `kataru_story_load`, and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_story_free(story: *mut KataruStory) {
+    guard((), || {
+        if !story.is_null() {
+            drop(Box::from_raw(story));
+        }
+    })
+}
+
+/// Creates a bookmark that starts at the global `Start` passage.
+#[no_mangle]
+pub extern "C" fn kataru_bookmark_new() -> *mut KataruBookmark {
+    guard(ptr::null_mut(), || {
+        let mut bookmark = Bookmark::default();
+        bookmark.set_passage("Start".to_string());
+        into_raw(Some(KataruBookmark(bookmark)))
+    })
+}
+
+/// Loads a bookmark from a YAML or MessagePack file. Returns null on error.
+///
+/// # Safety
+/// `path` must be null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_bookmark_load(path: *const c_char) -> *mut KataruBookmark {
+    guard(ptr::null_mut(), || {
+        into_raw(check(read_str(path).and_then(Bookmark::load)).map(KataruBookmark))
+    })
+}
+
+/// Saves a bookmark to a YAML or MessagePack file, chosen by its extension.
+///
+/// # Safety
+/// `bookmark` must be a live bookmark and `path` must be null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_bookmark_save(
+    bookmark: *const KataruBookmark,
+    path: *const c_char,
+) -> bool {
+    guard(false, || {
+        let bookmark = &(&*bookmark).0;
+        check(read_str(path).and_then(|path| bookmark.save(path))).is_some()
+    })
+}
+
+/// Frees a bookmark.
+///
+/// # Safety
+/// `bookmark` must be null or returned by Kataru, and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_bookmark_free(bookmark: *mut KataruBookmark) {
+    guard((), || {
+        if !bookmark.is_null() {
+            drop(Box::from_raw(bookmark));
+        }
+    })
+}
+
+/// Creates a runner for `story` starting from a copy of `bookmark`.
+/// Variables missing from the bookmark are set to their defaults from the story.
+/// Returns null on error.
+///
+/// # Safety
+/// `story` and `bookmark` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_runner_new(
+    story: *const KataruStory,
+    bookmark: *const KataruBookmark,
+) -> *mut KataruRunner {
+    guard(ptr::null_mut(), || {
+        let story = &(&*story).0;
+        let mut bookmark = (&*bookmark).0.clone();
+        bookmark.init_state(story);
+        into_raw(
+            check(OwnedRunner::new(bookmark, story.clone())).map(|runner| KataruRunner {
+                runner,
+                line: Line::End,
+                strings: LineStrings::default(),
+            }),
+        )
+    })
+}
+
+/// Frees a runner, along with every string returned for its current line.
+///
+/// # Safety
+/// `runner` must be null or returned by `kataru_runner_new`, and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_runner_free(runner: *mut KataruRunner) {
+    guard((), || {
+        if !runner.is_null() {
+            drop(Box::from_raw(runner));
+        }
+    })
+}
+
+/// Advances to the next line, passing `input` as the picked choice or entered text.
+/// Returns false on error.
+///
+/// # Safety
+/// `runner` must be live and `input` must be null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_runner_next(
+    runner: *mut KataruRunner,
+    input: *const c_char,
+) -> bool {
+    guard(false, || {
+        let runner = &mut *runner;
+        match check(read_str(input).and_then(|input| runner.runner.next(input))) {
+            Some(line) => {
+                runner.strings = LineStrings::new(&line);
+                runner.line = line;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Sets the unit that attribute offsets of the following lines are measured in,
+/// given as one of the `OffsetUnit` values. Offsets are in bytes of UTF-8 unless this is called.
+/// Returns false if `unit` isn't an `OffsetUnit`, leaving the unit as it was.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_runner_set_offset_unit(
+    runner: *mut KataruRunner,
+    unit: u32,
+) -> bool {
+    guard(false, || {
+        let unit = match unit {
+            0 => OffsetUnit::Byte,
+            1 => OffsetUnit::Char,
+            2 => OffsetUnit::Utf16,
+            _ => {
+                set_last_error(&format!("Invalid offset unit {}", unit));
+                return false;
+            }
+        };
+        (&mut *runner).runner.set_offset_unit(unit);
+        true
+    })
+}
+
+/// Returns the kind of line the runner is on.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_runner_tag(runner: *const KataruRunner) -> LineTag {
+    guard(LineTag::End, || LineTag::tag(&(&*runner).line))
+}
+
+/// Returns a copy of the runner's bookmark, to be freed with `kataru_bookmark_free`.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_runner_bookmark(
+    runner: *const KataruRunner,
+) -> *mut KataruBookmark {
+    guard(ptr::null_mut(), || {
+        into_raw(Some(KataruBookmark((&*runner).runner.bookmark().clone())))
+    })
+}
+
+/// Returns the speaker of the current dialogue line, or an empty string for other lines.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_dialogue_name(runner: *const KataruRunner) -> *const c_char {
+    guard(ptr::null(), || (&*runner).strings.speaker.as_ptr())
+}
+
+/// Returns the text of the current dialogue line, or an empty string for other lines.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_dialogue_text(runner: *const KataruRunner) -> *const c_char {
+    guard(ptr::null(), || (&*runner).strings.text.as_ptr())
+}
+
+/// Returns the number of attributes in the current dialogue line.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_dialogue_attributes_len(runner: *const KataruRunner) -> usize {
+    guard(0, || (&*runner).strings.attributes.len())
+}
+
+/// Returns the attribute at `index` in the current dialogue line.
+/// Its name is null if `index` is out of range.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_dialogue_attribute(
+    runner: *const KataruRunner,
+    index: usize,
+) -> KataruAttribute {
+    guard(empty_attribute(), || {
+        let runner = &*runner;
+        match (&runner.line, runner.strings.attributes.get(index)) {
+            (Line::Dialogue(dialogue), Some((name, params))) => {
+                let attribute = &dialogue.attributes[index];
+                KataruAttribute {
+                    name: name.as_ptr(),
+                    start: attribute.start,
+                    end: attribute.end,
+                    params_len: params.len(),
+                }
+            }
+            _ => empty_attribute(),
+        }
+    })
+}
+
+/// Returns the parameter at `param` of the attribute at `index` in the current dialogue line.
+/// Its name is null if either index is out of range.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_dialogue_attribute_param(
+    runner: *const KataruRunner,
+    index: usize,
+    param: usize,
+) -> KataruParam {
+    guard(empty_param(), || {
+        let runner = &*runner;
+        match (&runner.line, runner.strings.attributes.get(index)) {
+            (Line::Dialogue(dialogue), Some((_, params))) => {
+                match (
+                    params.get(param),
+                    dialogue.attributes[index].params.values().nth(param),
+                ) {
+                    (Some(strings), Some(value)) => c_param(strings, value),
+                    _ => empty_param(),
+                }
+            }
+            _ => empty_param(),
+        }
+    })
+}
+
+/// Returns the number of choices on the current line, or 0 if it isn't a choices line.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_choices_len(runner: *const KataruRunner) -> usize {
+    guard(0, || (&*runner).strings.choices.len())
+}
+
+/// Returns the text of the choice at `index`, or null if it's out of range.
+/// Pass this text to `kataru_runner_next` to pick the choice.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_choice(runner: *const KataruRunner, index: usize) -> *const c_char {
+    guard(ptr::null(), || {
+        match (&*runner).strings.choices.get(index) {
+            Some(choice) => choice.as_ptr(),
+            None => ptr::null(),
+        }
+    })
+}
+
+/// Returns the number of seconds before the current choices time out, or 0 if they don't.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_choices_timeout(runner: *const KataruRunner) -> f64 {
+    guard(0.0, || match &(&*runner).line {
+        Line::Choices(choices) => choices.timeout,
+        _ => 0.0,
+    })
+}
+
+/// Returns the name of the current command, or an empty string for other lines.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_command_name(runner: *const KataruRunner) -> *const c_char {
+    guard(ptr::null(), || (&*runner).strings.command.as_ptr())
+}
+
+/// Returns the number of parameters of the current command.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_command_params_len(runner: *const KataruRunner) -> usize {
+    guard(0, || (&*runner).strings.params.len())
+}
+
+/// Returns the parameter at `index` of the current command.
+/// Its name is null if `index` is out of range.
+///
+/// # Safety
+/// `runner` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn kataru_command_param(
+    runner: *const KataruRunner,
+    index: usize,
+) -> KataruParam {
+    guard(empty_param(), || {
+        let runner = &*runner;
+        match &runner.line {
+            Line::Command(command) => match (
+                runner.strings.params.get(index),
+                command.params.values().nth(index),
+            ) {
+                (Some(strings), Some(value)) => c_param(strings, value),
+                _ => empty_param(),
+            },
+            _ => empty_param(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(guard(0, || panic!("out of lines")), 0);
+        let message = unsafe { CStr::from_ptr(kataru_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "Kataru panicked: out of lines");
+        assert_eq!(guard(0, || 1), 1);
+    }
+}
@@ -0,0 +1,513 @@
+use crate::{
+    structs::{ChoiceTarget, QualifiedName, RawChoice, RawChoices, RawLine, Story, StoryGetters},
+    traits::IntoStr,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A passage identified by the namespace it was resolved in and its name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PassageId {
+    pub namespace: String,
+    pub passage: String,
+}
+
+impl PassageId {
+    pub fn new(namespace: &str, passage: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            passage: passage.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PassageId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.passage)
+    }
+}
+
+/// How control moves along an edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// A `call:` line.
+    Call,
+    /// A choice whose target is a passage name.
+    Choice,
+    /// The `default` target of a choices line.
+    Default,
+}
+
+/// A transfer of control from one passage to another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub to: PassageId,
+    pub kind: EdgeKind,
+    /// Index of the line in the flattened passage, matching `Position::line`.
+    pub line: usize,
+    /// Text of the choice that leads to this edge, if any.
+    pub label: Option<String>,
+    /// Enclosing `if`/`elif`/`else` expressions and choice conditions, outermost first.
+    pub conditions: Vec<String>,
+    /// True if this is a top-level `call:` that always runs when the passage does.
+    pub unconditional: bool,
+}
+
+impl Edge {
+    /// Describes when this edge is taken, combining its conditions and choice text.
+    pub fn description(&self) -> Option<String> {
+        let mut parts = self.conditions.clone();
+        match (&self.label, self.kind) {
+            (Some(label), _) => parts.push(label.to_string()),
+            (None, EdgeKind::Default) => parts.push("default".to_string()),
+            _ => (),
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(": "))
+        }
+    }
+}
+
+/// A passage and everything it can transfer control to.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PassageNode {
+    /// Outgoing edges in flattened line order.
+    pub edges: Vec<Edge>,
+    /// Flattened line indices of each choices line.
+    pub choice_lines: Vec<usize>,
+}
+
+/// Call graph of every passage in a story.
+/// Targets are resolved from the calling passage's namespace the same way the `Runner` does,
+/// and targets that can't be resolved are left out.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StoryGraph {
+    pub passages: BTreeMap<PassageId, PassageNode>,
+}
+
+/// Walks passage lines in the same order as `Runner::load_lines` flattens them.
+struct GraphBuilder<'a> {
+    story: &'a Story,
+    namespace: &'a str,
+    node: PassageNode,
+    label: Option<String>,
+    conditions: Vec<String>,
+    depth: usize,
+}
+
+impl<'a> GraphBuilder<'a> {
+    fn add_edge(&mut self, kind: EdgeKind, line: usize, passage_name: &str) {
+        let qname = QualifiedName::from(self.namespace, passage_name);
+        if let Ok((namespace, _section, _passage)) = self.story.passage(&qname) {
+            self.node.edges.push(Edge {
+                to: PassageId::new(namespace, qname.name),
+                kind,
+                line,
+                label: self.label.clone(),
+                conditions: self.conditions.clone(),
+                unconditional: kind == EdgeKind::Call && self.depth == 0,
+            });
+        }
+    }
+
+    /// Walks nested lines with an extra `condition` and choice `label` in scope.
+    fn add_nested(
+        &mut self,
+        lines: &[RawLine],
+        line_num: &mut usize,
+        condition: Option<&str>,
+        label: Option<&str>,
+    ) {
+        let outer_label = self.label.clone();
+        if let Some(label) = label {
+            self.label = Some(label.to_string());
+        }
+        if let Some(condition) = condition {
+            self.conditions.push(condition.to_string());
+        }
+        self.depth += 1;
+        self.add_lines(lines, line_num);
+        self.depth -= 1;
+        if condition.is_some() {
+            self.conditions.pop();
+        }
+        self.label = outer_label;
+    }
+
+    fn add_target(
+        &mut self,
+        target: &ChoiceTarget,
+        choice_line: usize,
+        line_num: &mut usize,
+        condition: Option<&str>,
+        label: &str,
+        is_first: &mut bool,
+    ) {
+        match target {
+            ChoiceTarget::PassageName(passage_name) => {
+                let outer_label = self.label.replace(label.to_string());
+                if let Some(condition) = condition {
+                    self.conditions.push(condition.to_string());
+                }
+                self.add_edge(EdgeKind::Choice, choice_line, passage_name);
+                if condition.is_some() {
+                    self.conditions.pop();
+                }
+                self.label = outer_label;
+            }
+            ChoiceTarget::Lines(lines) => {
+                // Breaks are inserted before each embedded passage except the first.
+                if !*is_first {
+                    *line_num += 1;
+                }
+                self.add_nested(lines, line_num, condition, Some(label));
+                *is_first = false;
+            }
+            ChoiceTarget::None => (),
+        }
+    }
+
+    fn add_choices(&mut self, choices: &RawChoices, choice_line: usize, line_num: &mut usize) {
+        self.node.choice_lines.push(choice_line);
+        let mut is_first = true;
+        for (key, choice) in choices {
+            match choice {
+                RawChoice::Target(target) => {
+                    self.add_target(target, choice_line, line_num, None, key, &mut is_first)
+                }
+                RawChoice::Conditional(conditional) => {
+                    for (inner_key, target) in conditional {
+                        self.add_target(
+                            target,
+                            choice_line,
+                            line_num,
+                            Some(key),
+                            inner_key,
+                            &mut is_first,
+                        )
+                    }
+                }
+            }
+        }
+        match &choices.default {
+            ChoiceTarget::PassageName(passage_name) => {
+                self.add_edge(EdgeKind::Default, choice_line, passage_name)
+            }
+            ChoiceTarget::Lines(lines) => {
+                if !is_first {
+                    *line_num += 1;
+                }
+                self.add_nested(lines, line_num, None, None)
+            }
+            ChoiceTarget::None => (),
+        }
+    }
+
+    fn add_lines(&mut self, lines: &[RawLine], line_num: &mut usize) {
+        for line in lines {
+            let current_line = *line_num;
+            *line_num += 1;
+            match line {
+                RawLine::Call(call) => self.add_edge(EdgeKind::Call, current_line, &call.passage),
+                RawLine::Branches(branches) => {
+                    let mut is_first = true;
+                    for (expression, branch_lines) in &branches.exprs {
+                        if !is_first {
+                            *line_num += 1;
+                        }
+                        self.add_nested(branch_lines, line_num, Some(expression), None);
+                        is_first = false;
+                    }
+                }
+                RawLine::Variations(variations) => {
+                    let mut is_first = true;
+                    for alternative in &variations.alternatives {
+                        if !is_first {
+                            *line_num += 1;
+                        }
+                        self.add_nested(
+                            alternative,
+                            line_num,
+                            Some(variations.kind.into_str()),
+                            None,
+                        );
+                        is_first = false;
+                    }
+                }
+                RawLine::Choices(choices) => self.add_choices(choices, current_line, line_num),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl StoryGraph {
+    /// Builds the call graph from every `call:`, choice target and choices default in `story`.
+    pub fn new(story: &Story) -> Self {
+        let mut graph = Self::default();
+        for (namespace, section) in story {
+            for (passage_name, passage) in &section.passages {
+                let mut builder = GraphBuilder {
+                    story,
+                    namespace,
+                    node: PassageNode::default(),
+                    label: None,
+                    conditions: Vec::new(),
+                    depth: 0,
+                };
+                builder.add_lines(passage, &mut 0);
+                graph
+                    .passages
+                    .insert(PassageId::new(namespace, passage_name), builder.node);
+            }
+        }
+        graph
+    }
+
+    /// Resolves `passage_name` from `namespace` to the id of a passage in this graph.
+    pub fn resolve(&self, namespace: &str, passage_name: &str) -> Option<PassageId> {
+        let qname = QualifiedName::from(namespace, passage_name);
+        qname
+            .resolve()
+            .map(|namespace| PassageId::new(namespace, qname.name))
+            .find(|id| self.passages.contains_key(id))
+    }
+
+    /// Gets the edges leaving `id`.
+    pub fn edges(&self, id: &PassageId) -> &[Edge] {
+        match self.passages.get(id) {
+            Some(node) => &node.edges,
+            None => &[],
+        }
+    }
+
+    /// Groups passage ids by namespace, in sorted order.
+    fn namespaces(&self) -> BTreeMap<&str, Vec<&PassageId>> {
+        let mut namespaces: BTreeMap<&str, Vec<&PassageId>> = BTreeMap::new();
+        for id in self.passages.keys() {
+            namespaces.entry(&id.namespace).or_default().push(id);
+        }
+        namespaces
+    }
+
+    /// Renders this graph as a Graphviz DOT digraph with one cluster per namespace.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph story {\n");
+        for (namespace, ids) in self.namespaces() {
+            dot.push_str(&format!(
+                "    subgraph \"cluster_{}\" {{\n        label = \"{}\";\n",
+                escape_dot(namespace),
+                escape_dot(namespace)
+            ));
+            for id in ids {
+                dot.push_str(&format!(
+                    "        \"{}\" [label = \"{}\"];\n",
+                    escape_dot(&id.to_string()),
+                    escape_dot(&id.passage)
+                ));
+            }
+            dot.push_str("    }\n");
+        }
+        for (id, node) in &self.passages {
+            for edge in &node.edges {
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\"",
+                    escape_dot(&id.to_string()),
+                    escape_dot(&edge.to.to_string())
+                ));
+                if let Some(description) = edge.description() {
+                    dot.push_str(&format!(" [label = \"{}\"]", escape_dot(&description)));
+                }
+                dot.push_str(";\n");
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders this graph as a Mermaid flowchart with one subgraph per namespace.
+    pub fn to_mermaid(&self) -> String {
+        // Mermaid ids can't contain ':', so number the passages instead.
+        let node_ids: BTreeMap<&PassageId, String> = self
+            .passages
+            .keys()
+            .enumerate()
+            .map(|(i, id)| (id, format!("p{}", i)))
+            .collect();
+
+        let mut mermaid = String::from("flowchart TD\n");
+        for (i, (namespace, ids)) in self.namespaces().into_iter().enumerate() {
+            mermaid.push_str(&format!(
+                "    subgraph n{} [\"{}\"]\n",
+                i,
+                escape_mermaid(namespace)
+            ));
+            for id in ids {
+                mermaid.push_str(&format!(
+                    "        {}[\"{}\"]\n",
+                    node_ids[id],
+                    escape_mermaid(&id.passage)
+                ));
+            }
+            mermaid.push_str("    end\n");
+        }
+        for (id, node) in &self.passages {
+            for edge in &node.edges {
+                match edge.description() {
+                    Some(description) => mermaid.push_str(&format!(
+                        "    {} -->|\"{}\"| {}\n",
+                        node_ids[id],
+                        escape_mermaid(&description),
+                        node_ids[&edge.to]
+                    )),
+                    None => mermaid.push_str(&format!(
+                        "    {} --> {}\n",
+                        node_ids[id], node_ids[&edge.to]
+                    )),
+                }
+            }
+        }
+        mermaid
+    }
+}
+
+/// Escapes text for use inside a quoted DOT string.
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes text for use inside a quoted Mermaid label.
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FromYaml;
+
+    #[test]
+    fn test_story_graph() {
+        let story = Story::from_yml(
+            r#"
+global:
+  config:
+    namespace: global
+  passages:
+    Start:
+      - if $x:
+          - call: A
+        else:
+          - choices:
+              Go: B
+              Stay:
+                - call: A
+            default: B
+      - call: B
+    A:
+      - Hello
+    B:
+      - Bye
+"#,
+        )
+        .unwrap();
+        let graph = StoryGraph::new(&story);
+        let start = PassageId::new("global", "Start");
+        let edges: Vec<(&str, EdgeKind, usize, Option<&str>, bool)> = graph
+            .edges(&start)
+            .iter()
+            .map(|edge| {
+                (
+                    edge.to.passage.as_str(),
+                    edge.kind,
+                    edge.line,
+                    edge.label.as_deref(),
+                    edge.unconditional,
+                )
+            })
+            .collect();
+        // if (0), call A (1), break (2), choices (3), call A (4), call B (5).
+        assert_eq!(
+            edges,
+            vec![
+                ("A", EdgeKind::Call, 1, None, false),
+                ("B", EdgeKind::Choice, 3, Some("Go"), false),
+                ("A", EdgeKind::Call, 4, Some("Stay"), false),
+                ("B", EdgeKind::Default, 3, None, false),
+                ("B", EdgeKind::Call, 5, None, true),
+            ]
+        );
+        assert_eq!(graph.edges(&start)[0].conditions, vec!["if $x"]);
+        assert_eq!(graph.passages[&start].choice_lines, vec![3]);
+        assert_eq!(
+            graph.resolve("global", "A"),
+            Some(PassageId::new("global", "A"))
+        );
+    }
+
+    fn export_story() -> Story {
+        Story::from_yml(
+            r#"
+global:
+  config:
+    namespace: global
+  passages:
+    Start:
+      - choices:
+          Say "hi": ns:Greet
+      - call: ns:Greet
+ns:
+  config:
+    namespace: ns
+  passages:
+    Greet:
+      - if $x:
+          - call: Start
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = StoryGraph::new(&export_story()).to_dot();
+        assert_eq!(
+            dot,
+            r#"digraph story {
+    subgraph "cluster_global" {
+        label = "global";
+        "global:Start" [label = "Start"];
+    }
+    subgraph "cluster_ns" {
+        label = "ns";
+        "ns:Greet" [label = "Greet"];
+    }
+    "global:Start" -> "ns:Greet" [label = "Say \"hi\""];
+    "global:Start" -> "ns:Greet";
+    "ns:Greet" -> "global:Start" [label = "if $x"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let mermaid = StoryGraph::new(&export_story()).to_mermaid();
+        assert_eq!(
+            mermaid,
+            r#"flowchart TD
+    subgraph n0 ["global"]
+        p0["Start"]
+    end
+    subgraph n1 ["ns"]
+        p1["Greet"]
+    end
+    p0 -->|"Say #quot;hi#quot;"| p1
+    p0 --> p1
+    p1 -->|"if $x"| p0
+"#
+        );
+    }
+}
@@ -7,29 +7,44 @@ extern crate maplit;
 #[macro_use]
 mod error;
 
+mod analyzer;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 #[macro_use]
 mod runner;
+mod graph;
+mod locale;
+mod message;
 mod packer;
+mod stats;
 mod structs;
 mod tagger;
 mod traits;
 mod validator;
 mod value;
 mod vars;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+pub use analyzer::Analyzer;
 pub use error::{Error, Result};
+pub use graph::{Edge, EdgeKind, PassageId, PassageNode, StoryGraph};
+pub use locale::{line_id, split_id, Locale, SourceString, StringTable};
+pub use message::{format_message, ordinal_category, plural_category};
 pub use packer::pack;
-pub use runner::Runner;
+pub use runner::{OwnedRunner, Runner, StoryRunner};
+pub use stats::StoryStats;
 pub use structs::{
-    Bookmark, CharacterData, Choices, Command, Config, Dialogue, Input, Line, Map, Operator,
-    Params, Passage, Passages, Position, Section, SetCommand, State, StateMod, Story, StoryGetters,
-    GLOBAL,
+    story_from_sources, Attribute, Bookmark, CharacterData, Choices, Command, Config, Dialogue,
+    History, Input, Line, Map, OffsetUnit, Operator, Params, Passage, Passages, Position,
+    ReadLines, Rng, Section, SetCommand, Span, State, StateMod, Step, Story, StoryGetters, Thread,
+    Transcript, TranscriptEntry, VariationKind, Variations, GLOBAL, MAIN_THREAD,
 };
 pub use tagger::LineTag;
 pub use traits::{
     FromMessagePack, FromYaml, Load, LoadMessagePack, LoadYaml, Merge, Save, SaveMessagePack,
     SaveYaml,
 };
-pub use validator::Validator;
-pub use value::Value;
+pub use validator::{Diagnostic, Severity, Validator};
+pub use value::{Context, Expr, Exprs, Function, Functions, Value};
 pub use vars::{contains_var, extract_var};
@@ -0,0 +1,517 @@
+use crate::{
+    error::{Error, Result},
+    structs::{fnv1a, RawChoice, RawLine, Story},
+    traits::{
+        FromMessagePack, FromYaml, Load, LoadMessagePack, LoadYaml, Save, SaveMessagePack, SaveYaml,
+    },
+    Map,
+};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::BTreeSet, fmt::Write, mem};
+
+/// Marker that starts an explicit line ID.
+const ID_MARKER: &str = "#line:";
+
+/// Splits an explicit ID written at the end of `text` as ` #line:id`,
+/// returning the ID and the text without it. Text without the marker is returned as is.
+pub fn split_id(text: &str) -> (Option<&str>, &str) {
+    let trimmed = text.trim_end();
+    if let Some(start) = trimmed.rfind(ID_MARKER) {
+        let id = &trimmed[start + ID_MARKER.len()..];
+        let before = &trimmed[..start];
+        if !id.is_empty()
+            && (before.is_empty() || before.ends_with(char::is_whitespace))
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return (Some(id), before.trim_end());
+        }
+    }
+    (None, text)
+}
+
+/// Returns the ID of a line of dialogue or choice written as `text` in `passage`,
+/// along with the text without its explicit ID.
+/// Lines without an explicit ID get a hash of their namespace, passage and text,
+/// which stays the same as long as they aren't edited or moved.
+pub fn line_id<'t>(namespace: &str, passage: &str, text: &'t str) -> (String, &'t str) {
+    match split_id(text) {
+        (Some(id), text) => (id.to_string(), text),
+        (None, text) => {
+            let hash = fnv1a(format!("{}:{}:{}", namespace, passage, text).as_bytes());
+            (format!("{:016x}", hash), text)
+        }
+    }
+}
+
+/// A line of dialogue or choice that can be translated.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceString {
+    pub id: String,
+    pub namespace: String,
+    pub passage: String,
+    /// Name of the speaker, if the line is dialogue with a named speaker.
+    pub speaker: String,
+    /// Text as written in the story, without its explicit ID.
+    pub text: String,
+    /// True if this is the text of a choice.
+    pub choice: bool,
+}
+
+/// Every translatable string in a story, ordered by namespace, passage and line.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StringTable {
+    pub strings: Vec<SourceString>,
+}
+
+impl StringTable {
+    pub fn new(story: &Story) -> Self {
+        let mut table = Self::default();
+        let mut ids = BTreeSet::new();
+        let mut namespaces: Vec<&String> = story.keys().collect();
+        namespaces.sort();
+        for namespace in namespaces {
+            let section = &story[namespace];
+            let mut passages: Vec<&String> = section.passages.keys().collect();
+            passages.sort();
+            for passage in passages {
+                let mut adder = StringAdder {
+                    table: &mut table,
+                    ids: &mut ids,
+                    namespace,
+                    passage,
+                };
+                adder.add_lines(&section.passages[passage]);
+            }
+        }
+        table
+    }
+
+    /// Exports the strings as CSV with an empty `translation` column to fill in.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("id,namespace,passage,speaker,text,translation\n");
+        for string in &self.strings {
+            let fields = [
+                &string.id,
+                &string.namespace,
+                &string.passage,
+                &string.speaker,
+                &string.text,
+            ];
+            for field in &fields {
+                csv.push_str(&escape_csv(field));
+                csv.push(',');
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Exports the strings as a gettext PO template, using IDs as message contexts.
+    pub fn to_po(&self) -> String {
+        let mut po = String::from(
+            "msgid \"\"\nmsgstr \"\"\n\"Language: \\n\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n",
+        );
+        for string in &self.strings {
+            po.push('\n');
+            let _ = write!(po, "#. {}:{}", string.namespace, string.passage);
+            if !string.speaker.is_empty() {
+                let _ = write!(po, " {}", string.speaker);
+            }
+            po.push('\n');
+            let _ = writeln!(po, "msgctxt \"{}\"", escape_po(&string.id));
+            let _ = writeln!(po, "msgid \"{}\"", escape_po(&string.text));
+            po.push_str("msgstr \"\"\n");
+        }
+        po
+    }
+
+    /// Exports the strings as an XLIFF 1.2 document in `source_language`.
+    pub fn to_xliff(&self, source_language: &str) -> String {
+        let mut xliff = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xliff.push_str("<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n");
+        let _ = writeln!(
+            xliff,
+            "  <file original=\"story\" source-language=\"{}\" datatype=\"plaintext\">",
+            escape_xml(source_language)
+        );
+        xliff.push_str("    <body>\n");
+        for string in &self.strings {
+            let _ = writeln!(
+                xliff,
+                "      <trans-unit id=\"{}\">",
+                escape_xml(&string.id)
+            );
+            let _ = writeln!(
+                xliff,
+                "        <source>{}</source>",
+                escape_xml(&string.text)
+            );
+            let mut note = format!("{}:{}", string.namespace, string.passage);
+            if !string.speaker.is_empty() {
+                let _ = write!(note, " {}", string.speaker);
+            }
+            let _ = writeln!(xliff, "        <note>{}</note>", escape_xml(&note));
+            xliff.push_str("      </trans-unit>\n");
+        }
+        xliff.push_str("    </body>\n  </file>\n</xliff>\n");
+        xliff
+    }
+}
+
+/// Walks the lines of a passage to add their strings to a table.
+struct StringAdder<'a> {
+    table: &'a mut StringTable,
+    ids: &'a mut BTreeSet<String>,
+    namespace: &'a str,
+    passage: &'a str,
+}
+
+impl<'a> StringAdder<'a> {
+    fn add(&mut self, speaker: &str, text: &str, choice: bool) {
+        let (id, text) = line_id(self.namespace, self.passage, text);
+        if self.ids.insert(id.clone()) {
+            self.table.strings.push(SourceString {
+                id,
+                namespace: self.namespace.to_string(),
+                passage: self.passage.to_string(),
+                speaker: speaker.to_string(),
+                text: text.to_string(),
+                choice,
+            });
+        }
+    }
+
+    fn add_lines(&mut self, lines: &[RawLine]) {
+        for line in lines {
+            match line {
+                RawLine::Dialogue(dialogue) => {
+                    for (speaker, text) in dialogue {
+                        self.add(speaker, text, false);
+                    }
+                }
+                RawLine::Text(text) => self.add("", text, false),
+                RawLine::Branches(branches) => {
+                    for lines in branches.exprs.values() {
+                        self.add_lines(lines);
+                    }
+                }
+                RawLine::Variations(variations) => {
+                    for lines in &variations.alternatives {
+                        self.add_lines(lines);
+                    }
+                }
+                RawLine::Choices(choices) => {
+                    for (key, choice) in choices.as_ref() {
+                        match choice {
+                            RawChoice::Target(_) => self.add("", key, true),
+                            RawChoice::Conditional(conditional) => {
+                                for inner_key in conditional.keys() {
+                                    self.add("", inner_key, true);
+                                }
+                            }
+                        }
+                    }
+                    for lines in choices.embedded() {
+                        self.add_lines(lines);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Translated strings keyed by line ID.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Locale {
+    /// Language tag such as `fr` or `pt-BR`, which picks the rules for plural messages.
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub strings: Map<String, String>,
+}
+
+impl Locale {
+    /// Returns the translation of `text` written in `passage`,
+    /// falling back to `text` without its explicit ID.
+    pub fn translate<'a>(&'a self, namespace: &str, passage: &str, text: &'a str) -> Cow<'a, str> {
+        let (id, text) = line_id(namespace, passage, text);
+        match self.strings.get(&id) {
+            Some(translation) => Cow::Borrowed(translation),
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    /// Loads the `translation` column of a CSV file exported by `StringTable::to_csv`.
+    /// Rows without a translation are left out.
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut rows = parse_csv(text)?.into_iter();
+        let header = rows.next().unwrap_or_default();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|field| field == name)
+                .ok_or_else(|| error!("CSV is missing a '{}' column", name))
+        };
+        let (id_column, translation_column) = (column("id")?, column("translation")?);
+
+        let mut locale = Self::default();
+        for row in rows {
+            if let (Some(id), Some(translation)) = (row.get(id_column), row.get(translation_column))
+            {
+                if !translation.is_empty() {
+                    locale.strings.insert(id.clone(), translation.clone());
+                }
+            }
+        }
+        Ok(locale)
+    }
+
+    /// Loads the translated messages of a PO file, keyed by their message contexts.
+    /// Messages without a context or translation are left out.
+    pub fn from_po(text: &str) -> Result<Self> {
+        let mut locale = Self::default();
+        let mut entry = PoEntry::default();
+        let mut field = PoField::None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, string) = if line.starts_with('"') {
+                ("", line)
+            } else {
+                line.split_once(' ').unwrap_or((line, ""))
+            };
+            let string = unescape_po(string.trim())
+                .ok_or_else(|| error!("Invalid PO string on line {}", i + 1))?;
+            match keyword {
+                "msgctxt" => {
+                    locale.add_po_entry(mem::take(&mut entry));
+                    entry.context = Some(string);
+                    field = PoField::Context;
+                }
+                "msgid" => {
+                    if entry.translated {
+                        locale.add_po_entry(mem::take(&mut entry));
+                    }
+                    field = PoField::Id;
+                }
+                "msgstr" => {
+                    entry.translation = string;
+                    entry.translated = true;
+                    field = PoField::Translation;
+                }
+                "" => match field {
+                    PoField::Context => entry
+                        .context
+                        .get_or_insert_with(String::new)
+                        .push_str(&string),
+                    PoField::Translation => entry.translation.push_str(&string),
+                    PoField::Id | PoField::None => (),
+                },
+                _ => return Err(error!("Unknown PO keyword '{}' on line {}", keyword, i + 1)),
+            }
+        }
+        locale.add_po_entry(entry);
+        Ok(locale)
+    }
+
+    fn add_po_entry(&mut self, entry: PoEntry) {
+        match entry.context {
+            Some(id) if !entry.translation.is_empty() => {
+                self.strings.insert(id, entry.translation);
+            }
+            Some(_) => (),
+            // The header has no context, and may say which language this is.
+            None => {
+                for line in entry.translation.lines() {
+                    if let Some(language) = line.strip_prefix("Language:") {
+                        self.language = language.trim().to_string();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A message being read from a PO file.
+#[derive(Default)]
+struct PoEntry {
+    context: Option<String>,
+    translation: String,
+    translated: bool,
+}
+
+/// The field of a PO message that continuation lines are appended to.
+enum PoField {
+    None,
+    Context,
+    Id,
+    Translation,
+}
+
+fn escape_csv(text: &str) -> Cow<'_, str> {
+    if text.contains(&[',', '"', '\n', '\r'][..]) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Parses CSV rows, allowing quoted fields with commas, quotes and newlines.
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(mem::take(&mut field)),
+            '\r' if !quoted => (),
+            '\n' if !quoted => {
+                row.push(mem::take(&mut field));
+                rows.push(mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(error!("CSV has an unterminated quoted field"));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn escape_po(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+/// Unescapes a quoted PO string, or returns None if it isn't quoted.
+fn unescape_po(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut string = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                c => string.push(c),
+            }
+        } else {
+            string.push(c);
+        }
+    }
+    Some(string)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl FromYaml for Locale {}
+impl FromMessagePack for Locale {}
+impl SaveYaml for Locale {}
+impl SaveMessagePack for Locale {}
+impl Save for Locale {}
+impl LoadYaml for Locale {}
+impl LoadMessagePack for Locale {}
+impl Load for Locale {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_id() {
+        assert_eq!(
+            split_id("Hello there. #line:greeting"),
+            (Some("greeting"), "Hello there.")
+        );
+        assert_eq!(split_id("#line:only.id"), (Some("only.id"), ""));
+        assert_eq!(split_id("Number #1 fan!"), (None, "Number #1 fan!"));
+        assert_eq!(split_id("C# is#fun"), (None, "C# is#fun"));
+
+        // Hashtags are text, and so is a marker that isn't its own word.
+        assert_eq!(split_id("I'm #1"), (None, "I'm #1"));
+        assert_eq!(split_id("#hashtag"), (None, "#hashtag"));
+        assert_eq!(split_id("Not#line:id"), (None, "Not#line:id"));
+
+        let (id, text) = line_id("global", "Start", "Hello there.");
+        assert_eq!(text, "Hello there.");
+        assert_eq!(id.len(), 16);
+        assert_ne!(line_id("global", "End", "Hello there.").0, id);
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = "id,namespace,passage,speaker,text,translation\n\
+                   a,global,Start,,\"Hi, you.\",\"Salut, \"\"toi\"\".\"\n\
+                   b,global,Start,,Untranslated,\n\
+                   c,global,Start,,Two lines,\"Une\nligne\"\n";
+        let locale = Locale::from_csv(csv).unwrap();
+        assert_eq!(
+            locale.strings,
+            hashmap! {
+                "a".to_string() => "Salut, \"toi\".".to_string(),
+                "c".to_string() => "Une\nligne".to_string(),
+            }
+        );
+        assert!(Locale::from_csv("id,text\n").is_err());
+        assert_eq!(escape_csv("plain"), "plain");
+        assert_eq!(escape_csv("a \"b\", c"), "\"a \"\"b\"\", c\"");
+    }
+
+    #[test]
+    fn test_po() {
+        let po = r#"
+msgid ""
+msgstr ""
+"Language: fr\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#. global:Start Alice
+msgctxt "a"
+msgid "Say \"hi\"."
+msgstr "Dis "
+"\"salut\"."
+
+msgctxt "b"
+msgid "Untranslated"
+msgstr ""
+"#;
+        let locale = Locale::from_po(po).unwrap();
+        assert_eq!(
+            locale.strings,
+            hashmap! { "a".to_string() => "Dis \"salut\".".to_string() }
+        );
+        assert_eq!(locale.language, "fr");
+        assert!(Locale::from_po("msgid unquoted").is_err());
+        assert_eq!(
+            unescape_po(&format!("\"{}\"", escape_po("a\\b \"c\"\n"))),
+            Some("a\\b \"c\"\n".to_string())
+        );
+    }
+}
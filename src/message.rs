@@ -0,0 +1,509 @@
+use crate::{structs::escape_tags, Context, Value};
+
+/// A piece of dialogue text, which may interpolate expressions or pick between messages.
+#[derive(Debug, Clone, PartialEq)]
+enum Part<'a> {
+    Text(&'a str),
+    /// `{expr}`
+    Expr(&'a str),
+    /// `#` inside a plural message, replaced by the number being pluralized.
+    Hash,
+    /// `{expr, plural, one {...} other {...}}`, `{expr, selectordinal, ...}` or `{expr, select, ...}`.
+    Selector(Selector<'a>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SelectorKind {
+    Plural,
+    Ordinal,
+    Select,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Selector<'a> {
+    /// The whole `{...}`, shown as is if the selector can't be formatted.
+    source: &'a str,
+    arg: &'a str,
+    kind: SelectorKind,
+    /// Subtracted from the number before picking a plural category and replacing `#`.
+    offset: i64,
+    options: Vec<(&'a str, Vec<Part<'a>>)>,
+}
+
+/// Returns the index of the brace closing the one at `start`.
+fn closing_brace(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Splits `text` on commas that aren't nested in brackets, into at most `n` pieces.
+fn split_args(text: &str, n: usize) -> Vec<&str> {
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 && args.len() + 1 < n => {
+                args.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    args.push(&text[start..]);
+    args
+}
+
+/// Parses dialogue text. `#` is only special inside plural messages, where `hash` is set.
+fn parse(text: &str, hash: bool) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let (mut i, mut text_start) = (0, 0);
+    while let Some(c) = text[i..].chars().next() {
+        let part = match c {
+            '{' | '}' if text[i + 1..].starts_with(c) => Some((Part::Text(&text[i..i + 1]), i + 2)),
+            '{' => {
+                closing_brace(text, i).map(|end| (parse_placeholder(&text[i..=end], hash), end + 1))
+            }
+            '#' if hash => Some((Part::Hash, i + 1)),
+            _ => None,
+        };
+        match part {
+            Some((part, end)) => {
+                if text_start < i {
+                    parts.push(Part::Text(&text[text_start..i]));
+                }
+                parts.push(part);
+                i = end;
+                text_start = end;
+            }
+            None => i += c.len_utf8(),
+        }
+    }
+    if text_start < text.len() {
+        parts.push(Part::Text(&text[text_start..]));
+    }
+    parts
+}
+
+/// Parses the `{...}` in `source` as a selector, or as an expression if it isn't one.
+fn parse_placeholder(source: &str, hash: bool) -> Part<'_> {
+    let inner = &source[1..source.len() - 1];
+    if let [arg, kind, options] = split_args(inner, 3)[..] {
+        let kind = match kind.trim() {
+            "plural" => Some(SelectorKind::Plural),
+            "selectordinal" => Some(SelectorKind::Ordinal),
+            "select" => Some(SelectorKind::Select),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            if let Some(selector) = parse_selector(source, arg, kind, options, hash) {
+                return Part::Selector(selector);
+            }
+        }
+    }
+    Part::Expr(inner)
+}
+
+fn parse_selector<'a>(
+    source: &'a str,
+    arg: &'a str,
+    kind: SelectorKind,
+    mut text: &'a str,
+    hash: bool,
+) -> Option<Selector<'a>> {
+    let hash = hash || kind != SelectorKind::Select;
+    let mut selector = Selector {
+        source,
+        arg,
+        kind,
+        offset: 0,
+        options: Vec::new(),
+    };
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            break;
+        }
+        let key_end = text.find(|c: char| c == '{' || c.is_whitespace())?;
+        let key = &text[..key_end];
+        if let Some(offset) = key.strip_prefix("offset:") {
+            selector.offset = offset.parse().ok()?;
+            text = &text[key_end..];
+            continue;
+        }
+        let start = key_end + text[key_end..].find(|c: char| !c.is_whitespace())?;
+        if !text[start..].starts_with('{') {
+            return None;
+        }
+        let end = closing_brace(text, start)?;
+        selector
+            .options
+            .push((key, parse(&text[start + 1..end], hash)));
+        text = &text[end + 1..];
+    }
+    if selector.options.is_empty() {
+        None
+    } else {
+        Some(selector)
+    }
+}
+
+/// Formats `parts`, replacing `#` with `hash`.
+/// With `markup`, `<` in interpolated values is escaped so that it isn't parsed as a tag.
+fn format(
+    parts: &[Part],
+    context: &Context,
+    hash: Option<&Value>,
+    markup: bool,
+    result: &mut String,
+) {
+    for part in parts {
+        match part {
+            Part::Text(text) => result.push_str(text),
+            Part::Expr(expr) => match Value::from_expr(expr, context) {
+                Ok(value) => push_value(&value, markup, result),
+                Err(_) => {
+                    result.push('{');
+                    result.push_str(expr);
+                    result.push('}');
+                }
+            },
+            Part::Hash => match hash {
+                Some(value) => push_value(value, markup, result),
+                None => result.push('#'),
+            },
+            Part::Selector(selector) => {
+                if !format_selector(selector, context, hash, markup, result) {
+                    result.push_str(selector.source);
+                }
+            }
+        }
+    }
+}
+
+/// Formats the option picked by `selector`. Returns false if no option could be picked.
+fn format_selector(
+    selector: &Selector,
+    context: &Context,
+    hash: Option<&Value>,
+    markup: bool,
+    result: &mut String,
+) -> bool {
+    let value = match Value::from_expr(selector.arg, context) {
+        Ok(value) => value,
+        Err(_) => return false,
+    };
+    let option = |key: &str| {
+        selector
+            .options
+            .iter()
+            .find(|(option_key, _)| *option_key == key)
+            .map(|(_, parts)| parts)
+    };
+    let (parts, hash) = match selector.kind {
+        SelectorKind::Select => {
+            let key = match &value {
+                Value::String(text) => text.clone(),
+                value => value.to_string(),
+            };
+            (option(&key).or_else(|| option("other")), hash.cloned())
+        }
+        SelectorKind::Plural | SelectorKind::Ordinal => {
+            let n = match value.as_f64() {
+                Some(n) => n,
+                None => return false,
+            };
+            let offset_value = match value {
+                Value::Int(i) => Value::Int(i - selector.offset),
+                _ => Value::Number(n - selector.offset as f64),
+            };
+            let language = &context.bookmark.locale.language;
+            let category = match selector.kind {
+                SelectorKind::Plural => plural_category(language, n - selector.offset as f64),
+                _ => ordinal_category(language, n - selector.offset as f64),
+            };
+            let exact = format!("={}", value);
+            (
+                option(&exact)
+                    .or_else(|| option(category))
+                    .or_else(|| option("other")),
+                Some(offset_value),
+            )
+        }
+    };
+    match parts {
+        Some(parts) => {
+            format(parts, context, hash.as_ref(), markup, result);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Formats `text`, interpolating `{expr}` and picking messages with
+/// `{expr, plural, ...}`, `{expr, selectordinal, ...}` and `{expr, select, ...}`.
+/// Plural categories follow the rules of the bookmark's locale language.
+pub fn format_message(text: &str, context: &Context) -> String {
+    let mut result = String::with_capacity(text.len());
+    format(&parse(text, false), context, None, false, &mut result);
+    result
+}
+
+/// Like `format_message`, but escapes `<` in interpolated values for `extract_attr`,
+/// so that values such as player input can't add or close tags.
+pub(crate) fn format_markup(text: &str, context: &Context) -> String {
+    let mut result = String::with_capacity(text.len());
+    format(&parse(text, false), context, None, true, &mut result);
+    result
+}
+
+/// Appends `value` to `result`, escaping tags in it when formatting `markup`.
+fn push_value(value: &Value, markup: bool, result: &mut String) {
+    if markup {
+        result.push_str(&escape_tags(value.to_string()));
+    } else {
+        result.push_str(&value.to_string());
+    }
+}
+
+/// Returns the expressions interpolated into `text`, including selector arguments.
+pub fn message_exprs(text: &str) -> Vec<&str> {
+    fn add<'a>(parts: Vec<Part<'a>>, exprs: &mut Vec<&'a str>) {
+        for part in parts {
+            match part {
+                Part::Expr(expr) => exprs.push(expr),
+                Part::Selector(selector) => {
+                    exprs.push(selector.arg);
+                    for (_key, parts) in selector.options {
+                        add(parts, exprs);
+                    }
+                }
+                Part::Text(_) | Part::Hash => (),
+            }
+        }
+    }
+    let mut exprs = Vec::new();
+    add(parse(text, false), &mut exprs);
+    exprs
+}
+
+/// Splits `n` into its integer part and whether it has a fractional part.
+fn operands(n: f64) -> (u64, bool) {
+    let n = n.abs();
+    (n.trunc() as u64, n.fract() != 0.)
+}
+
+/// Returns the CLDR plural category of `n` for the primary subtag of `language`.
+/// Languages without rules here use English rules.
+pub fn plural_category(language: &str, n: f64) -> &'static str {
+    let (i, fraction) = operands(n);
+    let integer = !fraction;
+    let (i10, i100) = (i % 10, i % 100);
+    match primary_language(language).as_str() {
+        "ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" | "lo" | "my" => "other",
+        "fr" | "pt" => {
+            if i <= 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+        "ru" | "uk" | "be" => {
+            if !integer {
+                "other"
+            } else if i10 == 1 && i100 != 11 {
+                "one"
+            } else if (2..=4).contains(&i10) && !(12..=14).contains(&i100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "pl" => {
+            if !integer {
+                "other"
+            } else if i == 1 {
+                "one"
+            } else if (2..=4).contains(&i10) && !(12..=14).contains(&i100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "cs" | "sk" => match (integer, i) {
+            (false, _) => "many",
+            (true, 1) => "one",
+            (true, 2..=4) => "few",
+            _ => "other",
+        },
+        "he" => match (integer, i) {
+            (true, 1) => "one",
+            (true, 2) => "two",
+            _ => "other",
+        },
+        "ar" => {
+            if !integer {
+                "other"
+            } else if i == 0 {
+                "zero"
+            } else if i == 1 {
+                "one"
+            } else if i == 2 {
+                "two"
+            } else if (3..=10).contains(&i100) {
+                "few"
+            } else if (11..=99).contains(&i100) {
+                "many"
+            } else {
+                "other"
+            }
+        }
+        _ => {
+            if i == 1 && integer {
+                "one"
+            } else {
+                "other"
+            }
+        }
+    }
+}
+
+/// Returns the CLDR ordinal category of `n` for the primary subtag of `language`.
+/// Only English and French have ordinal categories here; other languages always use `other`.
+pub fn ordinal_category(language: &str, n: f64) -> &'static str {
+    let (i, _fraction) = operands(n);
+    let (i10, i100) = (i % 10, i % 100);
+    match primary_language(language).as_str() {
+        "" | "en" => match (i10, i100) {
+            (1, _) if i100 != 11 => "one",
+            (2, _) if i100 != 12 => "two",
+            (3, _) if i100 != 13 => "few",
+            _ => "other",
+        },
+        "fr" if i == 1 => "one",
+        _ => "other",
+    }
+}
+
+fn primary_language(language: &str) -> String {
+    language
+        .split(&['-', '_'][..])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bookmark, Context, Locale, GLOBAL};
+    use std::sync::Arc;
+
+    fn bookmark(language: &str) -> Bookmark {
+        let mut bookmark = Bookmark::new(hashmap! {
+            GLOBAL.to_string() => hashmap! {
+                "coins".to_string() => Value::Int(1),
+                "weight".to_string() => Value::Number(1.5),
+                "guests".to_string() => Value::Int(3),
+                "place".to_string() => Value::Int(22),
+                "pronoun".to_string() => Value::String("she".to_string()),
+            }
+        });
+        bookmark.locale = Arc::new(Locale {
+            language: language.to_string(),
+            ..Locale::default()
+        });
+        bookmark
+    }
+
+    #[test]
+    fn test_plural() {
+        let mut en = bookmark("en");
+        let coins = "{$coins, plural, =0 {no coins} one {# coin} other {# coins}}";
+        assert_eq!(format_message(coins, &Context::new(&en)), "1 coin");
+        en.state
+            .get_mut(GLOBAL)
+            .unwrap()
+            .insert("coins".to_string(), Value::Int(0));
+        assert_eq!(format_message(coins, &Context::new(&en)), "no coins");
+        assert_eq!(
+            format_message(
+                "{$weight, plural, one {# kilo} other {# kilos}}",
+                &Context::new(&en)
+            ),
+            "1.5 kilos"
+        );
+        assert_eq!(
+            format_message(
+                "{$guests, plural, offset:1 =1 {Just you.} one {You and # friend.} other {You and # friends.}}",
+                &Context::new(&en)
+            ),
+            "You and 2 friends."
+        );
+        assert_eq!(
+            format_message(
+                "{$place, selectordinal, one {#st} two {#nd} few {#rd} other {#th}}",
+                &Context::new(&en)
+            ),
+            "22nd"
+        );
+
+        // Russian has more than two forms.
+        let ru = bookmark("ru-RU");
+        let apples =
+            "{$guests, plural, one {# яблоко} few {# яблока} many {# яблок} other {# яблока}}";
+        assert_eq!(format_message(apples, &Context::new(&ru)), "3 яблока");
+        assert_eq!(plural_category("ru", 5.), "many");
+        assert_eq!(plural_category("ru", 21.), "one");
+        assert_eq!(plural_category("fr", 0.), "one");
+        assert_eq!(plural_category("ja", 1.), "other");
+        assert_eq!(plural_category("ar", 105.), "few");
+    }
+
+    #[test]
+    fn test_select() {
+        let bookmark = bookmark("en");
+        assert_eq!(
+            format_message(
+                "{$pronoun, select, he {his} she {her} other {their}} {$coins, plural, one {coin, with {$pronoun, select, she {# for her} other {#}}} other {coins}}.",
+                &Context::new(&bookmark)
+            ),
+            "her coin, with 1 for her."
+        );
+        assert_eq!(
+            format_message(
+                "{$missing, select, other {x}} {$pronoun, select, he {his}}",
+                &Context::new(&bookmark)
+            ),
+            "{$missing, select, other {x}} {$pronoun, select, he {his}}"
+        );
+    }
+
+    #[test]
+    fn test_message_exprs() {
+        assert_eq!(
+            message_exprs("{$a + 1} {{not}} {$b, plural, one {{$c}} other {{len([1, 2])}}} #"),
+            vec!["$a + 1", "$b", "$c", "len([1, 2])"]
+        );
+        assert_eq!(
+            format_message(
+                "Sum {random(1, 1) + 1}, map {{\"a\": 1}[\"a\"]}, {unclosed",
+                &Context::new(&bookmark("en"))
+            ),
+            "Sum 2, map {\"a\": 1}[\"a\"]}, {unclosed"
+        );
+    }
+}
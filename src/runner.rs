@@ -1,51 +1,62 @@
 use crate::{
     error::{Error, Result},
+    line_id,
     structs::{
-        Bookmark, ChoiceTarget, Choices, CommandGetters, Dialogue, Passage, QualifiedName,
-        RawChoice, RawChoices, RawLine, Return, State, Story, StoryGetters,
+        Bookmark, ChoiceTarget, Choices, CommandGetters, Dialogue, FlatPassage, Passage,
+        QualifiedName, RawChoices, RawLine, ReadLines, State, Story, StoryGetters,
     },
-    Line, Map, Section, Value,
+    Context, Exprs, Functions, Line, Locale, Map, OffsetUnit, Section, Value,
+};
+use std::{
+    borrow::{Borrow, BorrowMut},
+    sync::Arc,
 };
 
-static RETURN: RawLine = RawLine::Return(Return { r#return: () });
-static EMPTY_PASSAGE: Passage = Vec::new();
-lazy_static! {
-    static ref EMPTY_SECTION: Section = Section::default();
-}
-
-pub struct Runner<'r> {
-    /// Reference to bookmark to mutate as we progress through the story.
-    pub bookmark: &'r mut Bookmark,
-    /// Const reference to story to read.
-    pub story: &'r Story,
+/// Runs through a story, holding the story as `S` and the bookmark to progress as `B`.
+/// Use `Runner` to borrow both, or `OwnedRunner` to own the bookmark and share the story.
+pub struct StoryRunner<S, B> {
+    /// Bookmark to mutate as we progress through the story.
+    pub bookmark: B,
+    /// Story to read.
+    pub story: S,
     //// Current line number.
     pub line_num: usize,
-    /// Current passage (list of lines).
-    pub passage: &'r Passage,
-    /// Current section (list of passages enclosed in a namespace.
-    pub section: &'r Section,
-    /// Flattened array of line references (use `line_num` to index).
-    lines: Vec<&'r RawLine>,
+    /// Lines shown in this and earlier playthroughs, used to mark dialogue as seen.
+    pub read_lines: ReadLines,
+    /// Flattened lines of the current passage (use `line_num` to index).
+    lines: Arc<FlatPassage>,
+    /// Compiled expressions of the current section.
+    exprs: Exprs,
+    /// Functions registered by the host for expressions to call.
+    functions: Functions,
     /// Loaded choice-to-passage mapping from last choices seen.
-    choice_to_passage: Map<&'r str, &'r str>,
+    choice_to_passage: Map<String, String>,
     /// Loaded choice-to-line-num mapping from last choices seen.
-    choice_to_line_num: Map<&'r str, usize>,
+    choice_to_line_num: Map<String, usize>,
     /// Stack of break points.
     breaks: Vec<usize>,
     /// Last known speaker.
     speaker: String,
 }
 
-impl<'r> Runner<'r> {
-    pub fn new(bookmark: &'r mut Bookmark, story: &'r Story) -> Result<Self> {
+/// Runner that borrows its story and bookmark.
+pub type Runner<'r> = StoryRunner<&'r Story, &'r mut Bookmark>;
+
+/// Runner that owns its bookmark and shares its story, so that it can be stored
+/// without lifetimes and sent to other threads.
+pub type OwnedRunner = StoryRunner<Arc<Story>, Bookmark>;
+
+impl<S: Borrow<Story>, B: BorrowMut<Bookmark>> StoryRunner<S, B> {
+    pub fn new(bookmark: B, story: S) -> Result<Self> {
         // Flatten dialogue lines
         let mut runner = Self {
             bookmark,
             story,
             line_num: 0,
-            lines: Vec::new(),
-            passage: &EMPTY_PASSAGE,
-            section: &EMPTY_SECTION,
+            read_lines: ReadLines::default(),
+            lines: Arc::default(),
+            exprs: Exprs::default(),
+            functions: Functions::default(),
             choice_to_passage: Map::new(),
             choice_to_line_num: Map::new(),
             breaks: Vec::new(),
@@ -55,23 +66,106 @@ impl<'r> Runner<'r> {
         Ok(runner)
     }
 
-    fn readline(&self) -> Result<&'r RawLine> {
-        if self.bookmark.line() >= self.lines.len() {
-            return Err(error!(
-                "Invalid line number {} in passage '{}'",
-                self.bookmark.line(),
-                self.bookmark.passage()
-            ));
+    /// Creates a runner that resumes the bookmark's thread named `thread` where it left off.
+    /// Unlike `new`, this doesn't run `onEnter` again, so runners for different threads
+    /// can take turns with the same bookmark.
+    pub fn attach(mut bookmark: B, story: S, thread: &str) -> Result<Self> {
+        bookmark.borrow_mut().switch_thread(thread)?;
+        let mut runner = Self {
+            bookmark,
+            story,
+            line_num: 0,
+            read_lines: ReadLines::default(),
+            lines: Arc::default(),
+            exprs: Exprs::default(),
+            functions: Functions::default(),
+            choice_to_passage: Map::new(),
+            choice_to_line_num: Map::new(),
+            breaks: Vec::new(),
+            speaker: "".to_string(),
         };
-        Ok(self.lines[self.bookmark.line()])
+        runner.resume()?;
+        Ok(runner)
+    }
+
+    /// Creates a runner for a new thread named `thread` that starts at `passage`,
+    /// sharing state with the bookmark's other threads.
+    pub fn spawn(mut bookmark: B, story: S, thread: &str, passage: &str) -> Result<Self> {
+        bookmark.borrow_mut().add_thread(thread, passage);
+        bookmark.borrow_mut().switch_thread(thread)?;
+        Self::new(bookmark, story)
+    }
+
+    #[inline]
+    pub fn bookmark(&self) -> &Bookmark {
+        self.bookmark.borrow()
+    }
+
+    #[inline]
+    pub fn bookmark_mut(&mut self) -> &mut Bookmark {
+        self.bookmark.borrow_mut()
+    }
+
+    #[inline]
+    pub fn story(&self) -> &Story {
+        self.story.borrow()
+    }
+
+    /// Returns the section containing the current passage.
+    pub fn section(&self) -> Result<&Section> {
+        let bookmark = self.bookmark();
+        let qname = QualifiedName::from(bookmark.namespace(), bookmark.passage());
+        Ok(self.story().passage(&qname)?.1)
+    }
+
+    /// Returns the current passage.
+    pub fn passage(&self) -> Result<&Passage> {
+        let bookmark = self.bookmark();
+        let qname = QualifiedName::from(bookmark.namespace(), bookmark.passage());
+        Ok(self.story().passage(&qname)?.2)
+    }
+
+    /// Context for evaluating expressions in the current section.
+    fn context(&self) -> Context<'_> {
+        Context::new(self.bookmark())
+            .with_exprs(&self.exprs)
+            .with_functions(&self.functions)
+    }
+
+    /// Key of a line of dialogue in `read_lines`, made from its ID so that it stays the same
+    /// when other lines are added or moved. Lines with a `speaker` are prefixed with their name,
+    /// since different characters can say the same text.
+    fn read_key(&self, speaker: &str, text: &str) -> String {
+        let bookmark = self.bookmark();
+        let (id, _text) = line_id(bookmark.namespace(), bookmark.passage(), text);
+        if speaker.is_empty() {
+            id
+        } else {
+            format!("{}:{}", speaker, id)
+        }
+    }
+
+    /// Gets the bookmarked line of `lines`.
+    fn readline<'l>(&self, lines: &'l FlatPassage) -> Result<&'l RawLine> {
+        let bookmark = self.bookmark();
+        lines.lines.get(bookmark.line()).ok_or_else(|| {
+            error!(
+                "Invalid line number {} in passage '{}'",
+                bookmark.line(),
+                bookmark.passage()
+            )
+        })
     }
 
     /// Gets the next dialogue line from the story based on the user's input.
     /// Internally, a single call to `next()` may result in multiple lines being processed,
     /// i.e. when a choice is being made.
     pub fn next(&mut self, mut input: &str) -> Result<Line> {
+        self.bookmark_mut().begin_step();
         loop {
-            let raw_line = self.readline()?;
+            // Hold onto the current passage, since the runner may move to another one.
+            let lines = Arc::clone(&self.lines);
+            let raw_line = self.readline(&lines)?;
             // println!("{:#?}", raw_line);
             match raw_line {
                 // When a choice is encountered, it should first be returned for display.
@@ -82,15 +176,19 @@ impl<'r> Runner<'r> {
                         let choices = self.load_choices(raw_choices)?;
                         // If no choices, call the default.
                         if choices.is_empty() {
-                            self.call_default(&raw_choices)?
+                            self.call_default(raw_choices)?
                         } else {
                             return Ok(Line::Choices(choices));
                         }
                     } else {
                         if let Some(passage_name) = self.choice_to_passage.remove(input) {
-                            self.call(passage_name.to_string())?;
+                            self.bookmark_mut().log_choice(input);
+                            // Return to the line after the choices block.
+                            self.bookmark_mut().skip_lines(raw_choices.line_len() - 1);
+                            self.call(passage_name)?;
                         } else if let Some(skip_lines) = self.choice_to_line_num.remove(input) {
-                            let next_line = raw_choices.take(self.bookmark, skip_lines);
+                            self.bookmark_mut().log_choice(input);
+                            let next_line = raw_choices.take(self.bookmark_mut(), skip_lines);
                             self.breaks.push(next_line);
                         } else {
                             return Ok(Line::InvalidChoice);
@@ -103,16 +201,21 @@ impl<'r> Runner<'r> {
                     if input.is_empty() {
                         return Ok(Line::Input(input_cmd.clone()));
                     } else {
-                        for (var, _prompt) in &input_cmd.input {
+                        for var in input_cmd.input.keys() {
                             let mut state = State::new();
                             state.insert(var.clone(), Value::String(input.to_string()));
-                            self.bookmark.set_state(&state)?
+                            self.set_state(&state)?
                         }
-                        self.bookmark.next_line();
+                        self.bookmark_mut().next_line();
                     }
                 }
                 RawLine::Branches(branches) => {
-                    let next_line = branches.take(&mut self.bookmark)?;
+                    let skip_lines = branches.pick(&self.context())?;
+                    let next_line = branches.take(self.bookmark_mut(), skip_lines);
+                    self.breaks.push(next_line);
+                }
+                RawLine::Variations(variations) => {
+                    let next_line = variations.take(self.bookmark_mut());
                     self.breaks.push(next_line);
                 }
                 RawLine::Call(call) => {
@@ -120,50 +223,62 @@ impl<'r> Runner<'r> {
                 }
                 RawLine::Return(_) => {
                     self.run_on_exit()?;
-                    match self.bookmark.stack.pop() {
+                    match self.bookmark_mut().stack.pop() {
                         Some(position) => {
-                            self.bookmark.set_position(position);
+                            self.bookmark_mut().set_position(position);
                             self.load_bookmark_position()?;
                         }
                         None => return Ok(Line::End),
                     }
                 }
                 RawLine::Break => {
-                    let last_break = self.breaks.pop();
-                    self.bookmark.set_line(match last_break {
-                        Some(line_num) => line_num,
-                        None => 0,
-                    });
+                    // Blocks left through their last alternative never reach a break,
+                    // so drop any break points that are already behind us.
+                    let line = self.bookmark().line();
+                    let mut next_line = line + 1;
+                    while let Some(last_break) = self.breaks.pop() {
+                        if last_break > line {
+                            next_line = last_break;
+                            break;
+                        }
+                    }
+                    self.bookmark_mut().set_line(next_line);
                 }
                 RawLine::Command(raw_command) => {
-                    self.bookmark.next_line();
-                    let command = raw_command.get_full_command(&self.story, &self.bookmark)?;
+                    self.bookmark_mut().next_line();
+                    let command = raw_command.get_full_command(self.story(), &self.context())?;
                     return Ok(Line::Command(command));
                 }
                 RawLine::PositionalCommand(positional_command) => {
-                    self.bookmark.next_line();
+                    self.bookmark_mut().next_line();
                     let command =
-                        positional_command.get_full_command(&self.story, &self.bookmark)?;
+                        positional_command.get_full_command(self.story(), &self.context())?;
                     return Ok(Line::Command(command));
                 }
                 RawLine::SetCommand(set) => {
-                    self.bookmark.next_line();
-                    self.bookmark.set_state(&set.set)?;
+                    self.bookmark_mut().next_line();
+                    self.set_state(&set.set)?;
                 }
                 RawLine::Dialogue(map) => {
-                    self.bookmark.next_line();
-                    let dialogue = Dialogue::from_map(map, &self.story, &self.bookmark)?;
+                    let key = match map.iter().next() {
+                        Some((speaker, text)) => self.read_key(speaker, text),
+                        None => String::new(),
+                    };
+                    self.bookmark_mut().next_line();
+                    let mut dialogue = Dialogue::from_map(map, self.story(), &self.context())?;
+                    dialogue.seen = self.read_lines.read(key);
                     self.speaker = dialogue.name.clone();
+                    self.bookmark_mut().log_dialogue(&dialogue);
                     return Ok(Line::Dialogue(dialogue));
                 }
                 RawLine::Text(text) => {
-                    self.bookmark.next_line();
-                    return Ok(Line::Dialogue(Dialogue::from(
-                        &self.speaker,
-                        text,
-                        self.story,
-                        self.bookmark,
-                    )?));
+                    let key = self.read_key("", text);
+                    self.bookmark_mut().next_line();
+                    let mut dialogue =
+                        Dialogue::from(&self.speaker, text, self.story(), &self.context())?;
+                    dialogue.seen = self.read_lines.read(key);
+                    self.bookmark_mut().log_dialogue(&dialogue);
+                    return Ok(Line::Dialogue(dialogue));
                 }
                 _ => return Err(error!("Unknown error.")),
             };
@@ -175,10 +290,13 @@ impl<'r> Runner<'r> {
     /// This requires that the current line is a return statement, and
     /// that this section has no `on_exit` callback.
     fn can_optimize_tail_call(&self) -> bool {
-        if let RawLine::Return(_) = self.lines[self.bookmark.line()] {
-            return self.section.on_exit().is_none();
+        match self.lines.lines.get(self.bookmark().line()) {
+            Some(RawLine::Return(_)) => match self.story().get(self.bookmark().namespace()) {
+                Some(section) => section.on_exit().is_none(),
+                None => false,
+            },
+            _ => false,
         }
-        false
     }
 
     /// Calls the default target for this choices object.
@@ -188,12 +306,13 @@ impl<'r> Runner<'r> {
         match &raw.default {
             ChoiceTarget::None => Err(error!("No choice target available.")),
             ChoiceTarget::Lines(_lines) => {
-                self.bookmark
-                    .skip_lines(raw.line_len() - raw.default.line_len() - 1);
+                self.bookmark_mut()
+                    .skip_lines(raw.line_len() - raw.default.line_len());
                 Ok(())
             }
             ChoiceTarget::PassageName(passage_name) => {
-                self.bookmark.skip_lines(raw.line_len() - 2);
+                // Return to the line after the choices block.
+                self.bookmark_mut().skip_lines(raw.line_len() - 1);
                 self.call(passage_name.clone())
             }
         }
@@ -202,16 +321,17 @@ impl<'r> Runner<'r> {
     /// Call the configured passage by putting return position on stack.
     /// And goto the passage.
     pub fn call(&mut self, passage_name: String) -> Result<()> {
-        self.bookmark.next_line();
+        self.bookmark_mut().next_line();
 
         // Don't push this func onto the stack of the next line is just a return.
         // (Tail call optimization).
         if !self.can_optimize_tail_call() {
-            self.bookmark.stack.push(self.bookmark.position().clone());
+            let position = self.bookmark().position().clone();
+            self.bookmark_mut().stack.push(position);
         }
 
-        self.bookmark.set_passage(passage_name);
-        self.bookmark.set_line(0);
+        self.bookmark_mut().set_passage(passage_name);
+        self.bookmark_mut().set_line(0);
         self.goto()?;
         Ok(())
     }
@@ -224,146 +344,151 @@ impl<'r> Runner<'r> {
         Ok(())
     }
 
+    /// Applies the state changes of a set command or input, using the current section's expressions.
+    fn set_state(&mut self, state: &State) -> Result<()> {
+        let bookmark: &mut Bookmark = self.bookmark.borrow_mut();
+        bookmark.set_state_with(state, Some(&self.exprs), Some(&self.functions))
+    }
+
+    /// Registers a function that expressions in the story can call by `name`.
+    /// Registered functions live on the runner, so they are never saved with the bookmark.
+    pub fn register_function<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.functions.insert(name, function);
+    }
+
+    /// Replaces all registered functions with `functions`.
+    pub fn set_functions(&mut self, functions: Functions) {
+        self.functions = functions;
+    }
+
+    /// Functions registered with `register_function`, for passing to the validator.
+    #[inline]
+    pub fn functions(&self) -> &Functions {
+        &self.functions
+    }
+
+    /// Sets whether operators ignore values of the wrong type instead of returning errors.
+    /// See `Bookmark::lenient`.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.bookmark_mut().lenient = lenient;
+    }
+
+    /// Sets the translations used for dialogue and choices.
+    pub fn set_locale(&mut self, locale: Arc<Locale>) {
+        self.bookmark_mut().locale = locale;
+    }
+
+    /// Sets the unit that dialogue attribute offsets are measured in.
+    pub fn set_offset_unit(&mut self, unit: OffsetUnit) {
+        self.bookmark_mut().offsets = unit;
+    }
+
+    /// Sets how many calls to `next` are recorded so that they can be rewound.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.bookmark_mut().history.set_limit(limit);
+    }
+
+    /// Sets how many lines of dialogue and picked choices are kept in the transcript.
+    pub fn set_transcript_limit(&mut self, limit: usize) {
+        self.bookmark_mut().transcript.set_limit(limit);
+    }
+
+    /// Undoes the last `steps` calls to `next`, including any changes they made to state.
+    pub fn rewind(&mut self, steps: usize) -> Result<()> {
+        self.bookmark_mut().rewind(steps)?;
+        self.resume()
+    }
+
     pub fn save_snapshot(&mut self, name: &str) {
-        self.bookmark.save_snapshot(name)
+        self.bookmark_mut().save_snapshot(name)
     }
 
     /// Repopulates `self` with a list of all valid choices from `raw` in order.
     /// Also repopulates the `choice_to_passage` and `choice_to_line_num` maps.
-    pub fn load_choices(&mut self, raw: &'r RawChoices) -> Result<Choices> {
+    pub fn load_choices(&mut self, raw: &RawChoices) -> Result<Choices> {
+        let context = Context::new(self.bookmark.borrow())
+            .with_exprs(&self.exprs)
+            .with_functions(&self.functions);
         let choices = Choices::from_raw(
             &mut self.choice_to_passage,
             &mut self.choice_to_line_num,
             raw,
-            &self.bookmark,
+            &context,
         )?;
         Ok(choices)
     }
 
     pub fn load_snapshot(&mut self, name: &str) -> Result<()> {
-        self.bookmark.load_snapshot(name)?;
-        self.load_bookmark_position()?;
-        if let RawLine::Choices(raw_choices) = self.readline()? {
-            self.load_choices(raw_choices)?;
-        }
-
-        Ok(())
+        self.bookmark_mut().load_snapshot(name)?;
+        self.resume()
     }
 
-    /// Loads lines into a single flat array of references.
-    /// Initializes breakpoint stack.
-    fn load_passage(&mut self, lines: &'r [RawLine]) {
-        self.lines = vec![];
-        self.load_lines(lines);
-        self.lines.push(&RETURN);
-        // for (i, e) in self.lines.iter().enumerate() {
-        //     println!("{}: {:?}", i, e);
-        // }
-
-        self.breaks = vec![];
-        self.load_breaks();
-    }
-
-    /// Initialize the line break stack.
-    /// Loop through each line in the flattened array until current line
-    /// number is reached.
-    /// Each time a branch is detected, push the end of the branch on the break stack.
-    /// We must remove breaks that we pass through.
-    fn load_breaks(&mut self) {
-        for (line_num, line) in self.lines.iter().enumerate() {
-            if line_num >= self.bookmark.line() {
-                break;
-            }
+    /// Switches to the bookmark's thread named `name` and resumes it where it left off.
+    pub fn switch_thread(&mut self, name: &str) -> Result<()> {
+        self.bookmark_mut().switch_thread(name)?;
+        self.resume()
+    }
 
-            // If we pass the last break, remove it from the stack.
-            if let Some(last_break) = self.breaks.last() {
-                if line_num > *last_break {
-                    self.breaks.pop();
-                }
-            }
-            match line {
-                RawLine::Branches(branches) => {
-                    self.breaks.push(line_num + branches.line_len());
-                }
-                RawLine::Choices(choices) => {
-                    self.breaks.push(line_num + choices.line_len());
-                }
-                _ => (),
-            }
+    /// Loads the bookmark's position without running `onEnter`,
+    /// reloading choices if we stopped at them.
+    fn resume(&mut self) -> Result<()> {
+        self.load_bookmark_position()?;
+        let lines = Arc::clone(&self.lines);
+        if let RawLine::Choices(raw_choices) = self.readline(&lines)? {
+            self.load_choices(raw_choices)?;
         }
+        Ok(())
     }
 
-    /// Loads lines into a single flat array of references.
-    fn load_lines(&mut self, lines: &'r [RawLine]) {
-        for line in lines {
-            self.lines.push(&line);
-            match line {
-                RawLine::Branches(branches) => {
-                    // Add breaks before each lines except the first.
-                    let mut is_first = true;
-                    for (_expression, branch_lines) in &branches.exprs {
-                        if !is_first {
-                            self.lines.push(&RawLine::Break);
-                        }
-                        self.load_lines(branch_lines);
-                        is_first = false;
-                    }
-                }
-                RawLine::Choices(choices) => {
-                    let mut is_first = true;
-                    let mut load_target = |target: &'r ChoiceTarget| match target {
-                        ChoiceTarget::Lines(lines) => {
-                            if !is_first {
-                                self.lines.push(&RawLine::Break);
-                            }
-                            self.load_lines(lines);
-                            is_first = false;
-                        }
-                        _ => {}
-                    };
-                    for (_key, choice) in choices {
-                        match choice {
-                            RawChoice::Target(target) => load_target(target),
-                            RawChoice::Conditional(conditional) => {
-                                for (_inner_key, target) in conditional {
-                                    load_target(target)
-                                }
-                            }
-                        }
-                    }
-                    match &choices.default {
-                        ChoiceTarget::Lines(lines) => self.load_lines(lines),
-                        _ => (),
-                    }
-                }
-                _ => (),
-            }
-        }
+    /// Loads the bookmarked passage's flattened lines from the current section.
+    /// Initializes the breakpoint stack with the ends of the blocks containing the current line.
+    fn load_passage(&mut self) -> Result<()> {
+        let bookmark = self.bookmark.borrow();
+        let flat = self
+            .story
+            .borrow()
+            .get(bookmark.namespace())
+            .and_then(|section| section.flattened(bookmark.passage()))
+            .ok_or_else(|| Error::UnknownPassage {
+                passage: bookmark.passage().to_string(),
+                namespace: bookmark.namespace().to_string(),
+            })?;
+        self.breaks = flat.breaks(bookmark.line());
+        self.lines = Arc::clone(flat);
+        Ok(())
     }
 
     /// Runs the `onEnter` set command.
     fn run_on_enter(&mut self) -> Result<()> {
-        self.story
-            .apply_set_commands(|section| section.on_enter(), &mut self.bookmark)
+        self.story.borrow().apply_set_commands(
+            |section| section.on_enter(),
+            self.bookmark.borrow_mut(),
+            Some(&self.functions),
+        )
     }
 
     /// Runs the `onEnter` set command.
     fn run_on_exit(&mut self) -> Result<()> {
-        self.story
-            .apply_set_commands(|section| section.on_exit(), &mut self.bookmark)
+        self.story.borrow().apply_set_commands(
+            |section| section.on_exit(),
+            self.bookmark.borrow_mut(),
+            Some(&self.functions),
+        )
     }
 
     /// Gets the current passage based on the bookmark's position.
     /// Loads the lines into its flattened form.
     /// Automatically handles updating of namespace.
     fn load_bookmark_position(&mut self) -> Result<()> {
-        let qname = QualifiedName::from(self.bookmark.namespace(), self.bookmark.passage());
-        let (namespace, section, passage) = self.story.passage(&qname)?;
-        self.section = section;
-        self.passage = passage;
+        let bookmark: &mut Bookmark = self.bookmark.borrow_mut();
+        let qname = QualifiedName::from(bookmark.namespace(), bookmark.passage());
+        let (namespace, section, _passage) = self.story.borrow().passage(&qname)?;
         let (namespace, passage_name) = (namespace.to_string(), qname.name.to_string());
-        self.bookmark.update_position(namespace, passage_name);
-        self.load_passage(self.passage);
-        Ok(())
+        bookmark.update_position(namespace, passage_name);
+        self.exprs = section.exprs().clone();
+        self.load_passage()
     }
 }
@@ -0,0 +1,161 @@
+use crate::structs::{ChoiceTarget, RawChoice, RawLine, Story};
+use std::fmt;
+
+/// Counts of the content written in a story.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoryStats {
+    pub namespaces: usize,
+    pub passages: usize,
+    pub characters: usize,
+    /// Lines written in passages, including nested lines.
+    pub lines: usize,
+    pub dialogue_lines: usize,
+    /// Words across all dialogue lines.
+    pub words: usize,
+    /// Individual options across all choices lines.
+    pub choices: usize,
+    pub branches: usize,
+    /// Sequence, cycle, shuffle and once lines.
+    pub variations: usize,
+    pub commands: usize,
+    pub calls: usize,
+}
+
+impl StoryStats {
+    pub fn new(story: &Story) -> Self {
+        let mut stats = Self {
+            namespaces: story.len(),
+            ..Self::default()
+        };
+        for section in story.values() {
+            stats.passages += section.passages.len();
+            stats.characters += section.characters().len();
+            for passage in section.passages.values() {
+                stats.add_lines(passage);
+            }
+        }
+        stats
+    }
+
+    fn add_target(&mut self, target: &ChoiceTarget) {
+        self.choices += 1;
+        if let ChoiceTarget::Lines(lines) = target {
+            self.add_lines(lines);
+        }
+    }
+
+    fn add_lines(&mut self, lines: &[RawLine]) {
+        for line in lines {
+            self.lines += 1;
+            match line {
+                RawLine::Dialogue(dialogue) => {
+                    self.dialogue_lines += 1;
+                    for text in dialogue.values() {
+                        self.words += text.split_whitespace().count();
+                    }
+                }
+                RawLine::Text(text) => {
+                    self.dialogue_lines += 1;
+                    self.words += text.split_whitespace().count();
+                }
+                RawLine::Branches(branches) => {
+                    self.branches += 1;
+                    for branch_lines in branches.exprs.values() {
+                        self.add_lines(branch_lines);
+                    }
+                }
+                RawLine::Variations(variations) => {
+                    self.variations += 1;
+                    for alternative in &variations.alternatives {
+                        self.add_lines(alternative);
+                    }
+                }
+                RawLine::Choices(choices) => {
+                    for (_key, choice) in choices.as_ref() {
+                        match choice {
+                            RawChoice::Target(target) => self.add_target(target),
+                            RawChoice::Conditional(conditional) => {
+                                for target in conditional.values() {
+                                    self.add_target(target);
+                                }
+                            }
+                        }
+                    }
+                    if let ChoiceTarget::Lines(lines) = &choices.default {
+                        self.add_lines(lines);
+                    }
+                }
+                RawLine::Command(_) | RawLine::PositionalCommand(_) => self.commands += 1,
+                RawLine::Call(_) => self.calls += 1,
+                _ => (),
+            }
+        }
+    }
+}
+
+impl fmt::Display for StoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Namespaces:     {}", self.namespaces)?;
+        writeln!(f, "Passages:       {}", self.passages)?;
+        writeln!(f, "Characters:     {}", self.characters)?;
+        writeln!(f, "Lines:          {}", self.lines)?;
+        writeln!(f, "Dialogue lines: {}", self.dialogue_lines)?;
+        writeln!(f, "Words:          {}", self.words)?;
+        writeln!(f, "Choices:        {}", self.choices)?;
+        writeln!(f, "Branches:       {}", self.branches)?;
+        writeln!(f, "Variations:     {}", self.variations)?;
+        writeln!(f, "Commands:       {}", self.commands)?;
+        write!(f, "Calls:          {}", self.calls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FromYaml;
+
+    #[test]
+    fn test_story_stats() {
+        let story = Story::from_yml(
+            r#"
+global:
+  config:
+    namespace: global
+    characters:
+      Alice:
+  passages:
+    Start:
+      - Alice: Hello there!
+      - if $x:
+          - Just text.
+      - choices:
+          Yes:
+            - Alice.Wave: {}
+          No: End
+      - call: End
+    End:
+      - cycle:
+          - The end.
+          - - The end again.
+            - call: Start
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            StoryStats::new(&story),
+            StoryStats {
+                namespaces: 1,
+                passages: 2,
+                characters: 1,
+                lines: 10,
+                dialogue_lines: 4,
+                words: 9,
+                choices: 2,
+                branches: 1,
+                variations: 1,
+                commands: 1,
+                calls: 2,
+            }
+        );
+    }
+}
@@ -1,101 +1,314 @@
-use super::Map;
+use super::{Map, Params};
 use crate::error::{Error, Result};
+use crate::Value;
+use serde::{Deserialize, Deserializer, Serialize};
 
-pub type Attributes = Map<String, Vec<usize>>;
+/// A span of text marked up with an attribute, such as `<shake strength=2>text</shake>`.
+/// `start` and `end` are offsets into the text with its tags removed,
+/// in bytes unless converted with `OffsetUnit::convert`.
+/// Self-closing tags such as `<pause=0.5/>` mark an empty span where `start == end`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attribute {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    /// Parameters given in the tag, merged with their defaults from the config.
+    #[serde(default)]
+    pub params: Params,
+}
 
-/// Extracts attributes from a string.
-pub fn extract_attr(
-    text: &str,
-    attrs: &Map<String, Option<String>>,
-) -> Result<(Attributes, String)> {
-    let mut attributes = Attributes::new();
-    let mut result = String::new();
-
-    // While iterating, keep track of what context we are in and where this context begins.
-    enum Context {
-        Text,
-        Open,
-        Close,
-    }
-    let mut context = Context::Text;
-    let mut start: usize = 0;
-
-    /// Utility method to finish parsing a tag and run error checking.
-    fn finish_tag(
-        result: &mut String,
-        attributes: &mut Attributes,
-        closed: bool,
-        attr: &str,
-        attrs: &Map<String, Option<String>>,
-    ) -> Result<()> {
-        // If this is an invalid tag, ignore and push to results.
-        if !attrs.contains_key(attr) {
-            result.push_str(if closed { "</" } else { "<" });
-            result.push_str(attr);
-            result.push_str(">");
-            return Ok(());
-        }
+/// Attribute spans, ordered by where their opening tags appear.
+pub type Attributes = Vec<Attribute>;
 
-        // Save the position in the result string that this tag starts on.
-        let positions = attributes.entry(attr.to_string()).or_insert(vec![]);
+/// Mapping of attribute name to its parameters and their default values.
+pub type AttributeConfig = Map<String, Option<Params>>;
 
-        if closed && positions.len() % 2 != 1 {
-            return Err(error!("Invalid closing tag </{}>", attr));
+/// An attribute's configuration as written, which older stories give as a description.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AttributeShadow {
+    Params(Params),
+    Description(#[allow(dead_code)] String),
+}
+
+/// Deserializes an attribute config, reading descriptions as attributes without parameters.
+pub fn deserialize_attributes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<AttributeConfig, D::Error> {
+    let shadow: Map<String, Option<AttributeShadow>> = Map::deserialize(deserializer)?;
+    Ok(shadow
+        .into_iter()
+        .map(|(name, config)| match config {
+            Some(AttributeShadow::Params(params)) => (name, Some(params)),
+            Some(AttributeShadow::Description(_)) | None => (name, None),
+        })
+        .collect())
+}
+
+/// Unit that attribute offsets are measured in.
+/// Engines that index strings in UTF-16 code units, such as Unity and JavaScript,
+/// need `Utf16` to place attributes in text outside of ASCII.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetUnit {
+    /// Bytes of UTF-8.
+    #[default]
+    Byte,
+    /// Unicode scalar values, as counted by `str::chars`.
+    Char,
+    /// UTF-16 code units.
+    Utf16,
+}
+
+impl OffsetUnit {
+    /// Converts the byte offset `byte` into `text` to this unit.
+    pub fn offset(self, text: &str, byte: usize) -> usize {
+        match self {
+            Self::Byte => byte,
+            Self::Char => text[..byte].chars().count(),
+            Self::Utf16 => text[..byte].encode_utf16().count(),
         }
-        positions.push(result.len());
-        Ok(())
     }
 
+    /// Converts the byte offsets of `attributes` into `text` to this unit.
+    pub fn convert(self, text: &str, attributes: &mut Attributes) {
+        if self == Self::Byte {
+            return;
+        }
+        for attribute in attributes {
+            attribute.start = self.offset(text, attribute.start);
+            attribute.end = self.offset(text, attribute.end);
+        }
+    }
+}
+
+/// A tag for a configured attribute, before it's paired with its opening or closing tag.
+enum Tag<'t> {
+    Open {
+        name: &'t str,
+        params: Params,
+        self_closing: bool,
+    },
+    Close(&'t str),
+}
+
+/// Finds the `>` that ends a tag, ignoring any inside quoted parameter values.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quoted = false;
     for (i, c) in text.char_indices() {
-        match context {
-            Context::Text => {
-                // If we reach an open tag.
-                if c == '<' {
-                    // Push text into the results.
-                    result.push_str(&text[start..i]);
-                    start = i + 1;
-                    context = Context::Open;
-                }
-            }
-            Context::Open => {
-                // If this tag is actually a close tag.
-                if c == '/' {
-                    start = i + 1;
-                    context = Context::Close;
-                }
-                // When done an open tag.
-                if c == '>' {
-                    let attr = &text[start..i];
-                    finish_tag(&mut result, &mut attributes, false, attr, attrs)?;
+        match c {
+            '"' => quoted = !quoted,
+            '>' if !quoted => return Some(i),
+            '<' if !quoted => return None,
+            _ => {}
+        }
+    }
+    None
+}
 
-                    start = i + 1;
-                    context = Context::Text;
-                }
-            }
-            Context::Close => {
-                // When done a close tag.
-                if c == '>' {
-                    let attr = &text[start..i];
-                    finish_tag(&mut result, &mut attributes, true, attr, attrs)?;
-
-                    start = i + 1;
-                    context = Context::Text;
-                }
+/// Splits a parameter value from the start of `text`, returning the value,
+/// whether it was quoted, and the remaining text.
+fn split_value(text: &str) -> Result<(&str, bool, &str)> {
+    if let Some(quoted) = text.strip_prefix('"') {
+        match quoted.find('"') {
+            Some(end) => Ok((&quoted[..end], true, &quoted[end + 1..])),
+            None => Err(error!("Unterminated quote in '{}'", text)),
+        }
+    } else {
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        Ok((&text[..end], false, &text[end..]))
+    }
+}
+
+/// Parses `raw` as the value of `param` for attribute `attr`,
+/// checking it against the type of `default`.
+fn parse_param(attr: &str, param: &str, raw: &str, quoted: bool, default: &Value) -> Result<Value> {
+    let value = if quoted {
+        Value::String(raw.to_string())
+    } else {
+        Value::from_yml(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+    };
+    match (default, value) {
+        (Value::String(_), _) => Ok(Value::String(raw.to_string())),
+        (Value::Number(_), Value::Int(n)) => Ok(Value::Number(n as f64)),
+        (default, value) if default.same_type(&value) => Ok(value),
+        (default, value) => Err(Error::TypeMismatch {
+            expected: format!(
+                "{} for parameter '{}' of attribute <{}>",
+                default.type_name(),
+                param,
+                attr
+            ),
+            found: value,
+        }),
+    }
+}
+
+/// Parses the inside of a tag.
+/// Returns None if the tag isn't for an attribute in `attrs`, so that it's kept as text.
+fn parse_tag<'t>(tag: &'t str, attrs: &AttributeConfig) -> Result<Option<Tag<'t>>> {
+    if let Some(name) = tag.strip_prefix('/') {
+        return Ok(if attrs.contains_key(name) {
+            Some(Tag::Close(name))
+        } else {
+            None
+        });
+    }
+
+    let (tag, self_closing) = match tag.strip_suffix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let name_end = tag
+        .find(|c: char| c == '=' || c.is_whitespace())
+        .unwrap_or(tag.len());
+    let name = &tag[..name_end];
+    let schema = match attrs.get(name) {
+        Some(schema) => schema.as_ref(),
+        None => return Ok(None),
+    };
+
+    let mut params = schema.cloned().unwrap_or_default();
+    let mut set_param = |param: &str, raw: &str, quoted: bool| -> Result<()> {
+        match schema.and_then(|schema| schema.get(param)) {
+            Some(default) => {
+                let value = parse_param(name, param, raw, quoted, default)?;
+                params.insert(param.to_string(), value);
+                Ok(())
             }
+            None => Err(Error::BadAttributeParameter {
+                attribute: name.to_string(),
+                param: param.to_string(),
+            }),
         }
+    };
+
+    // A value directly after the name, as in `<color=#ff0000>`, sets the first parameter.
+    let mut rest = &tag[name_end..];
+    if let Some(value) = rest.strip_prefix('=') {
+        let param = match schema.and_then(|schema| schema.keys().next()) {
+            Some(param) => param,
+            None => return Err(error!("Attribute <{}> takes no parameters", name)),
+        };
+        let (raw, quoted, remaining) = split_value(value)?;
+        set_param(param, raw, quoted)?;
+        rest = remaining;
+    }
+
+    // Every other parameter is given by name, as in `<shake strength=2 speed=3>`.
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let (param, value) = match rest.find('=') {
+            Some(eq) if !rest[..eq].contains(char::is_whitespace) => (&rest[..eq], &rest[eq + 1..]),
+            _ => {
+                return Err(error!(
+                    "Invalid parameter '{}' in attribute <{}>",
+                    rest, name
+                ))
+            }
+        };
+        let (raw, quoted, remaining) = split_value(value)?;
+        set_param(param, raw, quoted)?;
+        rest = remaining;
     }
 
-    // Push remaining text into the result.
+    Ok(Some(Tag::Open {
+        name,
+        params,
+        self_closing,
+    }))
+}
+
+/// Stands in for `<` in values substituted into a line, so that they aren't parsed as tags.
+/// This is a noncharacter, which Unicode reserves for internal use, so stories won't contain it.
+const ESCAPED_TAG_OPEN: char = '\u{FDD0}';
 
-    if let Context::Open = context {
-        start -= 1;
+/// Escapes `<` in `text` so that `extract_attr` keeps it as text rather than parsing a tag.
+pub(crate) fn escape_tags(text: String) -> String {
+    if text.contains('<') {
+        text.replace('<', &ESCAPED_TAG_OPEN.to_string())
+    } else {
+        text
     }
+}
 
-    result.push_str(&text[start..]);
-    for (attr, positions) in &attributes {
-        if positions.len() % 2 != 0 {
-            return Err(error!("Unmatched tag <{}>", attr));
+/// Appends `text` to `result`, turning tags escaped with `escape_tags` back into `<`.
+fn push_unescaped(result: &mut String, text: &str) {
+    for (i, part) in text.split(ESCAPED_TAG_OPEN).enumerate() {
+        if i > 0 {
+            result.push('<');
         }
+        result.push_str(part);
+    }
+}
+
+/// Extracts attributes from a string.
+/// Tags for attributes that aren't in `attrs` are left in the text as is.
+pub fn extract_attr(text: &str, attrs: &AttributeConfig) -> Result<(Attributes, String)> {
+    let mut attributes = Attributes::new();
+    let mut result = String::with_capacity(text.len());
+
+    // Indices in `attributes` of the spans that haven't been closed yet, innermost last.
+    let mut open: Vec<usize> = vec![];
+
+    let mut rest = text;
+    while let Some(i) = rest.find('<') {
+        push_unescaped(&mut result, &rest[..i]);
+        let after = &rest[i + 1..];
+        let tag = match tag_end(after) {
+            Some(end) => parse_tag(&after[..end], attrs)?.map(|tag| (tag, end)),
+            None => None,
+        };
+
+        match tag {
+            Some((
+                Tag::Open {
+                    name,
+                    params,
+                    self_closing,
+                },
+                end,
+            )) => {
+                if !self_closing {
+                    open.push(attributes.len());
+                }
+                attributes.push(Attribute {
+                    name: name.to_string(),
+                    start: result.len(),
+                    end: result.len(),
+                    params,
+                });
+                rest = &after[end + 1..];
+            }
+            Some((Tag::Close(name), end)) => {
+                match open.pop() {
+                    Some(index) if attributes[index].name == name => {
+                        attributes[index].end = result.len();
+                    }
+                    Some(index) => {
+                        return Err(error!(
+                            "Mismatched closing tag </{}>, expected </{}>",
+                            name, attributes[index].name
+                        ))
+                    }
+                    None => return Err(error!("Invalid closing tag </{}>", name)),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push('<');
+                rest = after;
+            }
+        }
+    }
+    push_unescaped(&mut result, rest);
+
+    if let Some(&index) = open.last() {
+        return Err(error!("Unmatched tag <{}>", attributes[index].name));
     }
     Ok((attributes, result))
 }
@@ -103,11 +316,40 @@ pub fn extract_attr(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Map;
+    use crate::{structs::Config, FromYaml};
+    use linear_map::linear_map;
+
+    fn attr(name: &str, start: usize, end: usize, params: Params) -> Attribute {
+        Attribute {
+            name: name.to_string(),
+            start,
+            end,
+            params,
+        }
+    }
+
+    #[test]
+    fn test_attribute_config() {
+        // Descriptions from older stories configure attributes without parameters.
+        let config = Config::from_yml(
+            "attributes:\n  shake: Shakes the text.\n  bold:\n  color: { value: red }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.attributes,
+            hashmap! {
+                "shake".to_string() => None,
+                "bold".to_string() => None,
+                "color".to_string() => Some(linear_map! {
+                    "value".to_string() => Value::String("red".to_string())
+                }),
+            }
+        );
+    }
 
     #[test]
     fn test_extract_attr() {
-        let attrs: Map<String, Option<String>> = hashmap! {
+        let attrs: AttributeConfig = hashmap! {
             "attr1".to_string() => None,
             "attr2".to_string() => None
         };
@@ -116,9 +358,7 @@ mod tests {
             (
                 "Test <attr1>text</attr1>.",
                 Ok((
-                    hashmap! {
-                        "attr1".to_string() => vec![5 as usize, 9]
-                    },
+                    vec![attr("attr1", 5, 9, Params::new())],
                     "Test text.".to_string(),
                 )),
             ),
@@ -138,6 +378,35 @@ mod tests {
                 "Test < text.",
                 Ok((Attributes::new(), "Test < text.".to_string())),
             ),
+            (
+                "1 < 2 <attr1>text</attr1>",
+                Ok((
+                    vec![attr("attr1", 6, 10, Params::new())],
+                    "1 < 2 text".to_string(),
+                )),
+            ),
+            (
+                "<attr1>a <attr2>b</attr2> c</attr1>",
+                Ok((
+                    vec![
+                        attr("attr1", 0, 5, Params::new()),
+                        attr("attr2", 2, 3, Params::new()),
+                    ],
+                    "a b c".to_string(),
+                )),
+            ),
+            (
+                "<attr1>a <attr2>b</attr1> c</attr2>",
+                Err(Error::Generic(
+                    "Mismatched closing tag </attr1>, expected </attr2>".to_string(),
+                )),
+            ),
+            (
+                "<attr1=1>text</attr1>",
+                Err(Error::Generic(
+                    "Attribute <attr1> takes no parameters".to_string(),
+                )),
+            ),
         ];
 
         for (text, expected) in tests {
@@ -145,4 +414,104 @@ mod tests {
             assert_eq!(result, expected);
         }
     }
+
+    #[test]
+    fn test_offset_unit() {
+        let text = "日本語😀a";
+        let ends: Vec<usize> = [OffsetUnit::Byte, OffsetUnit::Char, OffsetUnit::Utf16]
+            .iter()
+            .map(|unit| unit.offset(text, text.len()))
+            .collect();
+        assert_eq!(ends, vec![14, 5, 6]);
+        assert_eq!(OffsetUnit::Utf16.offset(text, 9), 3);
+    }
+
+    #[test]
+    fn test_extract_attr_params() {
+        let mut color = Params::new();
+        color.insert("value".to_string(), Value::String("#ffffff".to_string()));
+        let mut shake = Params::new();
+        shake.insert("strength".to_string(), Value::Number(1.0));
+        shake.insert("speed".to_string(), Value::Int(1));
+        let mut pause = Params::new();
+        pause.insert("duration".to_string(), Value::Number(0.25));
+        let attrs: AttributeConfig = hashmap! {
+            "color".to_string() => Some(color),
+            "shake".to_string() => Some(shake),
+            "pause".to_string() => Some(pause),
+        };
+
+        let (attributes, text) = extract_attr(
+            "<color=#ff0000>Red <shake strength=2>and</shake></color>,<pause=0.5/> \
+             <shake speed=3 strength=1.5>done</shake>",
+            &attrs,
+        )
+        .unwrap();
+        assert_eq!(text, "Red and, done");
+
+        let params = |values: Vec<(&str, Value)>| -> Params {
+            values
+                .into_iter()
+                .map(|(param, value)| (param.to_string(), value))
+                .collect()
+        };
+        assert_eq!(
+            attributes,
+            vec![
+                attr(
+                    "color",
+                    0,
+                    7,
+                    params(vec![("value", Value::String("#ff0000".to_string()))])
+                ),
+                attr(
+                    "shake",
+                    4,
+                    7,
+                    params(vec![
+                        ("strength", Value::Number(2.0)),
+                        ("speed", Value::Int(1))
+                    ])
+                ),
+                attr(
+                    "pause",
+                    8,
+                    8,
+                    params(vec![("duration", Value::Number(0.5))])
+                ),
+                attr(
+                    "shake",
+                    9,
+                    13,
+                    params(vec![
+                        ("strength", Value::Number(1.5)),
+                        ("speed", Value::Int(3))
+                    ])
+                ),
+            ]
+        );
+
+        // Quoted values may contain spaces and `>`, and are always strings.
+        let (attributes, text) = extract_attr("<color=\"a > b\">c</color>", &attrs).unwrap();
+        assert_eq!(text, "c");
+        assert_eq!(
+            attributes[0].params["value"],
+            Value::String("a > b".to_string())
+        );
+
+        assert_eq!(
+            extract_attr("<shake size=2>text</shake>", &attrs),
+            Err(Error::BadAttributeParameter {
+                attribute: "shake".to_string(),
+                param: "size".to_string()
+            })
+        );
+        assert_eq!(
+            extract_attr("<shake strength=\"fast\">text</shake>", &attrs),
+            Err(Error::TypeMismatch {
+                expected: "number for parameter 'strength' of attribute <shake>".to_string(),
+                found: Value::String("fast".to_string())
+            })
+        );
+    }
 }
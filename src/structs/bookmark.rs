@@ -1,11 +1,19 @@
-use super::{Map, QualifiedName, State, Story};
+use super::{
+    Dialogue, History, Map, OffsetUnit, QualifiedName, Rng, State, Step, Story, Transcript,
+    TranscriptEntry,
+};
 use crate::{
     error::{Error, Result},
     traits::FromStr,
     traits::{FromMessagePack, FromYaml, LoadYaml, SaveMessagePack},
-    Load, LoadMessagePack, Save, SaveYaml, Section, StateMod, StoryGetters, Value, GLOBAL,
+    Context, Exprs, Functions, Load, LoadMessagePack, Locale, Save, SaveYaml, Section, StateMod,
+    StoryGetters, Value, GLOBAL,
 };
 use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, mem, sync::Arc};
+
+/// Name of the thread a bookmark starts on.
+pub const MAIN_THREAD: &str = "main";
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Position {
@@ -27,8 +35,25 @@ impl Default for Position {
     }
 }
 
-/// All data necessary to find your place in the story.
+/// A place in the story along with its call stack and snapshots.
+/// A bookmark can hold several threads, such as the main dialogue and background chatter,
+/// which all share the bookmark's state.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
+pub struct Thread {
+    #[serde(default)]
+    pub position: Position,
+    #[serde(default)]
+    pub stack: Vec<Position>,
+    #[serde(default)]
+    pub snapshots: Map<String, Vec<Position>>,
+}
+
+fn main_thread() -> String {
+    MAIN_THREAD.to_string()
+}
+
+/// All data necessary to find your place in the story.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Bookmark {
     #[serde(default)]
     pub state: Map<String, State>,
@@ -38,6 +63,54 @@ pub struct Bookmark {
     pub stack: Vec<Position>,
     #[serde(default)]
     pub snapshots: Map<String, Vec<Position>>,
+    /// Name of the active thread, whose position, stack and snapshots are the ones above.
+    #[serde(default = "main_thread")]
+    thread: String,
+    /// Inactive threads, keyed by name.
+    #[serde(default)]
+    pub threads: Map<String, Thread>,
+    /// Number of times each variations line has been visited, keyed by `Variations::visit_key`.
+    #[serde(default)]
+    pub visits: Map<String, usize>,
+    /// Random number generator used by `random` and `chance` in expressions.
+    #[serde(default)]
+    pub rng: Rng,
+    /// Recent steps through the story, for rewinding. Off unless given a limit.
+    #[serde(default)]
+    pub history: History,
+    /// Recent dialogue and choices, for showing a backlog. Off unless given a limit.
+    #[serde(default)]
+    pub transcript: Transcript,
+    /// If set, operators ignore values of the wrong type and division by zero results in zero,
+    /// as in older versions, instead of returning errors. This is not saved.
+    #[serde(skip)]
+    pub lenient: bool,
+    /// Translations of dialogue and choices. This is not saved.
+    #[serde(skip)]
+    pub locale: Arc<Locale>,
+    /// Unit that dialogue attribute offsets are measured in. This is not saved.
+    #[serde(skip)]
+    pub offsets: OffsetUnit,
+}
+
+impl Default for Bookmark {
+    fn default() -> Self {
+        Self {
+            state: Map::default(),
+            position: Position::default(),
+            stack: Vec::default(),
+            snapshots: Map::default(),
+            thread: main_thread(),
+            threads: Map::default(),
+            visits: Map::default(),
+            rng: Rng::default(),
+            history: History::default(),
+            transcript: Transcript::default(),
+            lenient: false,
+            locale: Arc::default(),
+            offsets: OffsetUnit::default(),
+        }
+    }
 }
 
 impl<'a> Bookmark {
@@ -103,6 +176,70 @@ impl<'a> Bookmark {
         self.position.passage = passage;
     }
 
+    /// Name of the active thread.
+    #[inline]
+    pub fn thread(&self) -> &str {
+        &self.thread
+    }
+
+    /// Adds a thread named `name` starting at the beginning of `passage`.
+    /// If the thread already exists, it is restarted there and loses its stack and snapshots.
+    pub fn add_thread(&mut self, name: &str, passage: &str) {
+        let thread = Thread {
+            position: Position {
+                passage: passage.to_string(),
+                ..Position::default()
+            },
+            ..Thread::default()
+        };
+        if name == self.thread {
+            self.position = thread.position;
+            self.stack = thread.stack;
+            self.snapshots = thread.snapshots;
+        } else {
+            self.threads.insert(name.to_string(), thread);
+        }
+    }
+
+    /// Makes the thread named `name` active, setting aside the position, stack and snapshots
+    /// of the current thread until it is switched back to.
+    pub fn switch_thread(&mut self, name: &str) -> Result<()> {
+        if name == self.thread {
+            return Ok(());
+        }
+        let thread = self
+            .threads
+            .remove(name)
+            .ok_or_else(|| error!("No thread named '{}'", name))?;
+        let previous = Thread {
+            position: mem::replace(&mut self.position, thread.position),
+            stack: mem::replace(&mut self.stack, thread.stack),
+            snapshots: mem::replace(&mut self.snapshots, thread.snapshots),
+        };
+        let previous_name = mem::replace(&mut self.thread, name.to_string());
+        self.threads.insert(previous_name, previous);
+        Ok(())
+    }
+
+    /// Removes the inactive thread named `name`. The active thread can't be removed.
+    pub fn remove_thread(&mut self, name: &str) -> Option<Thread> {
+        self.threads.remove(name)
+    }
+
+    /// Restarts the random number generator from `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Counts a visit to the line identified by `key` and returns how many times it was visited before.
+    pub fn visit(&mut self, key: String) -> usize {
+        self.history
+            .record_visits(&key, self.visits.get(&key).copied());
+        let visits = self.visits.entry(key).or_insert(0);
+        *visits += 1;
+        *visits - 1
+    }
+
     /// Gets the value for a given variable.
     pub fn value(&'a self, var: &str) -> Result<&'a Value> {
         let qname = QualifiedName::from(&self.position.namespace, var);
@@ -112,20 +249,24 @@ impl<'a> Bookmark {
                     return Ok(val);
                 }
             } else {
-                return Err(error!("No state for namespace '{}'", namespace));
+                return Err(Error::UnknownNamespace {
+                    namespace: namespace.to_string(),
+                });
             }
         }
-        Err(error!(
-            "Var '{}' could not be found in namespace '{}' nor any of its parents.",
-            qname.name, qname.namespace
-        ))
+        Err(Error::UndefinedVariable {
+            var: qname.name.to_string(),
+            namespace: qname.namespace.to_string(),
+        })
     }
 
     /// Returns mutable state.
     pub fn state(&'a mut self) -> Result<&'a mut State> {
         match self.state.get_mut(&self.position.namespace) {
             Some(state) => Ok(state),
-            None => Err(error!("Invalid namespace {}", &self.position.namespace)),
+            None => Err(Error::UnknownNamespace {
+                namespace: self.position.namespace.clone(),
+            }),
         }
     }
 
@@ -133,17 +274,34 @@ impl<'a> Bookmark {
     pub fn global_state(&'a mut self) -> Result<&'a mut State> {
         match self.state.get_mut(GLOBAL) {
             Some(state) => Ok(state),
-            None => Err(error!("No global namespace")),
+            None => Err(Error::UnknownNamespace {
+                namespace: GLOBAL.to_string(),
+            }),
         }
     }
 
     /// Given a mapping of state changes, updates the bookmark's state.
     /// `passage` is required for $passage variables.
     pub fn set_state(&mut self, state: &State) -> Result<()> {
+        self.set_state_with(state, None, None)
+    }
+
+    /// Like `set_state`, but evaluates expressions compiled in `exprs` without parsing them again,
+    /// and lets them call the host's `functions`.
+    pub fn set_state_with(
+        &mut self,
+        state: &State,
+        exprs: Option<&Exprs>,
+        functions: Option<&Functions>,
+    ) -> Result<()> {
         for (key, value) in state {
             // If a expression, evaluate. TODO: avoid clone.
             let mut value = value.clone();
-            value.eval_as_expr(self)?;
+            value.eval_as_expr(&Context {
+                bookmark: self,
+                exprs,
+                functions,
+            })?;
 
             // If contains ${passage} expansion, text should refer to the replaced text.
             // Otherwise it should simply be the key.
@@ -155,12 +313,22 @@ impl<'a> Bookmark {
             }
 
             let statemod = StateMod::from_str(text)?;
-            let local_state = self.state()?;
-            if local_state.contains_key(statemod.var) {
-                statemod.apply(local_state, value)?;
+            let namespace = if self.state()?.contains_key(statemod.var) {
+                self.position.namespace.clone()
+            } else if self.global_state()?.contains_key(statemod.var) {
+                GLOBAL.to_string()
             } else {
-                let global_state = self.global_state()?;
-                statemod.apply(global_state, value)?;
+                return Err(Error::UndefinedVariable {
+                    var: statemod.var.to_string(),
+                    namespace: self.position.namespace.clone(),
+                });
+            };
+            if let Some(state) = self.state.get_mut(&namespace) {
+                if let Some(old_value) = state.get(statemod.var) {
+                    self.history
+                        .record_value(&namespace, statemod.var, old_value);
+                }
+                statemod.apply(&namespace, state, value, self.lenient)?;
             }
         }
         Ok(())
@@ -198,7 +366,7 @@ impl<'a> Bookmark {
             if var.starts_with("$passage") {
                 Self::default_passage_expansion(var, val, section, section_state);
             } else {
-                Self::default_val(section_state, &var, &val);
+                Self::default_val(section_state, var, val);
             }
         }
         Self::init_parent_expansions(namespace, story, section, section_state)
@@ -254,6 +422,99 @@ impl<'a> Bookmark {
         }
     }
 
+    /// Returns the translation of `text` written in the current passage,
+    /// or `text` without its explicit ID if it hasn't been translated.
+    pub fn translate<'t>(&'t self, text: &'t str) -> Cow<'t, str> {
+        self.locale
+            .translate(&self.position.namespace, &self.position.passage, text)
+    }
+
+    /// Logs `dialogue` to the transcript.
+    pub fn log_dialogue(&mut self, dialogue: &Dialogue) {
+        let entry = TranscriptEntry {
+            namespace: self.position.namespace.clone(),
+            passage: self.position.passage.clone(),
+            speaker: dialogue.name.clone(),
+            text: dialogue.text.clone(),
+            choice: false,
+        };
+        self.transcript.push(entry);
+    }
+
+    /// Logs the picked `choice` to the transcript.
+    pub fn log_choice(&mut self, choice: &str) {
+        let entry = TranscriptEntry {
+            namespace: self.position.namespace.clone(),
+            passage: self.position.passage.clone(),
+            speaker: String::new(),
+            text: choice.to_string(),
+            choice: true,
+        };
+        self.transcript.push(entry);
+    }
+
+    /// Starts recording a step through the story that can be rewound.
+    pub fn begin_step(&mut self) {
+        if self.history.limit > 0 {
+            self.history.push(Step {
+                thread: self.thread.clone(),
+                position: self.position.clone(),
+                stack: self.stack.clone(),
+                rng: self.rng.state(),
+                transcript: self.transcript.total(),
+                ..Step::default()
+            });
+        }
+    }
+
+    /// Undoes the last `steps` steps, restoring the position, stack, state,
+    /// visit counts and random number generator from before them.
+    /// Steps taken on another thread since can't be rewound past, since threads share state.
+    pub fn rewind(&mut self, steps: usize) -> Result<()> {
+        if steps > self.history.len() {
+            return Err(error!(
+                "Can't rewind {} steps when only {} are recorded",
+                steps,
+                self.history.len()
+            ));
+        }
+        if let Some(step) = self
+            .history
+            .recent()
+            .take(steps)
+            .find(|step| step.thread != self.thread)
+        {
+            return Err(error!(
+                "Can't rewind thread '{}' past a step taken on thread '{}'",
+                self.thread, step.thread
+            ));
+        }
+        for _ in 0..steps {
+            if let Some(step) = self.history.pop() {
+                self.undo(step);
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, step: Step) {
+        for (namespace, values) in step.state {
+            if let Some(state) = self.state.get_mut(&namespace) {
+                state.extend(values);
+            }
+        }
+        for (key, visits) in step.visits {
+            match visits {
+                Some(visits) => self.visits.insert(key, visits),
+                None => self.visits.remove(&key),
+            };
+        }
+        self.rng.set_state(step.rng);
+        self.transcript.truncate(step.transcript);
+        self.position = step.position;
+        self.stack = step.stack;
+    }
+
     /// Gets the qualified character name (prefixed with namespace if not global).
     pub fn qualified_character_name(&self, story: &Story, character: &str) -> Result<String> {
         let qname = QualifiedName::from(self.namespace(), character);
@@ -1,5 +1,5 @@
 use super::{line_len, Bookmark, RawLine};
-use crate::{error::Result, Value};
+use crate::{error::Result, Context, Value};
 use linear_map::LinearMap;
 use serde::{Deserialize, Serialize};
 
@@ -31,8 +31,9 @@ impl std::convert::TryFrom<BranchesShadow> for Branches {
 }
 
 impl Branches {
-    /// Evaluates the conditionals in a given branch and takes the first one that evaluates to true.
-    pub fn take(&self, bookmark: &mut Bookmark) -> Result<usize> {
+    /// Evaluates the conditionals in a given branch and picks the first one that evaluates to true.
+    /// Returns the number of lines from the if line to the start of the picked branch.
+    pub fn pick(&self, context: &Context) -> Result<usize> {
         let mut skip_lines = 1; // Skip the initial if line.
 
         let mut i = 0;
@@ -40,7 +41,7 @@ impl Branches {
             i += 1;
 
             // If we should execute this block
-            if expr == "else" || Value::from_conditional(expr, bookmark)? {
+            if expr == "else" || Value::from_conditional(expr, context)? {
                 break;
             } else {
                 // Skip all contained lines plus the break that's inserted at the end.
@@ -52,9 +53,15 @@ impl Branches {
                 }
             }
         }
-        let next_line = bookmark.line() + self.line_len() - skip_lines;
+        Ok(skip_lines)
+    }
+
+    /// Skips to the branch `skip_lines` after the if line, as picked by `pick`.
+    /// Returns the line number after the branches block.
+    pub fn take(&self, bookmark: &mut Bookmark, skip_lines: usize) -> usize {
+        let next_line = bookmark.line() + self.line_len();
         bookmark.skip_lines(skip_lines);
-        Ok(next_line)
+        next_line
     }
 
     /// A branch has one line for each expression,
@@ -74,6 +81,7 @@ mod tests {
     use linear_map::linear_map;
 
     use super::{Branches, RawLine};
+    use std::sync::Arc;
 
     #[test]
     fn test_branches_length() {
@@ -99,13 +107,13 @@ mod tests {
         let branches = Branches {
             exprs: linear_map! {
                 "if true".to_string() => vec![
-                    RawLine::Branches(Branches {
+                    RawLine::Branches(Arc::new(Branches {
                         exprs: linear_map! {
                             "if true".to_string() => vec![
                                 RawLine::Text("test".to_string())
                                 ]
                         },
-                    })
+                    }))
                 ]
             },
         };
@@ -114,14 +122,14 @@ mod tests {
         let branches = Branches {
             exprs: linear_map! {
                 "if true".to_string() => vec![
-                    RawLine::Branches(Branches {
+                    RawLine::Branches(Arc::new(Branches {
                         exprs: linear_map! {
                             "if true".to_string() => vec![
                                 RawLine::Text("test".to_string())],
                             "elif true".to_string() => vec![
                                 RawLine::Text("test".to_string())]
                         },
-                    })
+                    }))
                 ]
             },
         };
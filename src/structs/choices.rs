@@ -1,10 +1,8 @@
 use super::{line_len, Bookmark, RawLine};
-use crate::{error::Result, Map, Value};
+use crate::{error::Result, Context, Map, Value};
 use linear_map::LinearMap;
 use serde::{Deserialize, Serialize};
 
-const EMPTY_STRING: &String = &String::new();
-
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum ChoiceTarget {
@@ -12,6 +10,7 @@ pub enum ChoiceTarget {
     PassageName(String),
     None,
 }
+#[allow(clippy::derivable_impls)]
 impl Default for ChoiceTarget {
     fn default() -> Self {
         Self::None
@@ -47,31 +46,61 @@ impl RawChoices {
         self.choices.len()
     }
 
-    /// Returns equivalent number of lines for the embedded passages.
-    /// If this choices object has no embedded passages, `line_len(choices) == 1`.
-    /// Otherwise it's `1 + the line length of each embedded passage + number of embedded passages`.
+    /// Returns the embedded passages in the order they are flattened, ending with the default.
     /// This includes all conditionals.
-    pub fn line_len(&self) -> usize {
-        let mut length = 1 + self.default.line_len();
+    pub fn embedded(&self) -> Vec<&[RawLine]> {
+        let mut embedded = Vec::new();
         for (_key, choice) in &self.choices {
             match choice {
-                RawChoice::Target(ChoiceTarget::Lines(lines)) => {
-                    length += line_len(lines) + 1;
-                }
+                RawChoice::Target(ChoiceTarget::Lines(lines)) => embedded.push(lines.as_slice()),
                 RawChoice::Conditional(conditional) => {
                     for (_inner_key, target) in conditional {
                         if let ChoiceTarget::Lines(lines) = target {
-                            length += line_len(lines) + 1;
+                            embedded.push(lines.as_slice());
                         }
                     }
                 }
                 _ => (),
             }
         }
+        if let ChoiceTarget::Lines(lines) = &self.default {
+            embedded.push(lines);
+        }
+        embedded
+    }
+
+    /// Returns the offset of each embedded passage's first line from the choices line.
+    pub fn embedded_offsets(&self) -> Vec<usize> {
+        let mut offsets: Vec<usize> = Vec::new();
+        let mut offset = 1;
+        for lines in self.embedded() {
+            // Each embedded passage except the first follows a break.
+            if !offsets.is_empty() {
+                offset += 1;
+            }
+            offsets.push(offset);
+            offset += line_len(lines);
+        }
+        offsets
+    }
+
+    /// Returns equivalent number of lines for the embedded passages.
+    /// If this choices object has no embedded passages, `line_len(choices) == 1`.
+    /// Otherwise it's `1 + the line length of each embedded passage + number of embedded passages - 1`,
+    /// since a break separates consecutive embedded passages.
+    pub fn line_len(&self) -> usize {
+        let embedded = self.embedded();
+        let mut length = 1 + embedded.len().saturating_sub(1);
+        for lines in embedded {
+            length += line_len(lines);
+        }
         length
     }
+
+    /// Skips to the embedded passage `skip_lines` after the choices line.
+    /// Returns the line number after the choices block.
     pub fn take(&self, bookmark: &mut Bookmark, skip_lines: usize) -> usize {
-        let next_line = bookmark.line() + self.line_len() - skip_lines;
+        let next_line = bookmark.line() + self.line_len();
         bookmark.skip_lines(skip_lines);
         next_line
     }
@@ -114,54 +143,70 @@ impl Choices {
     }
 
     /// Repopulates the `choice_to_passage` map with all valid choices.
-    pub fn from_raw<'r>(
-        choice_to_passage: &mut Map<&'r str, &'r str>,
-        choice_to_line_num: &mut Map<&'r str, usize>,
-        raw: &'r RawChoices,
-        bookmark: &Bookmark,
+    pub fn from_raw(
+        choice_to_passage: &mut Map<String, String>,
+        choice_to_line_num: &mut Map<String, usize>,
+        raw: &RawChoices,
+        context: &Context,
     ) -> Result<Self> {
-        let mut choices = Self::default();
-        choices.timeout = raw.timeout;
+        let bookmark = context.bookmark;
+        let mut choices = Self {
+            timeout: raw.timeout,
+            ..Self::default()
+        };
         choices.reserve(raw.len());
 
         // Reset structs.
         choice_to_passage.clear();
         choice_to_passage.reserve(raw.len());
+        choice_to_line_num.clear();
+
+        // Offsets of the embedded passages, not counting the default.
+        let mut offsets = raw.embedded_offsets();
+        if let ChoiceTarget::Lines(_) = raw.default {
+            offsets.pop();
+        }
 
         //  The current passage target.
-        let mut passage: &String = &EMPTY_STRING;
-        let mut line_num = raw.line_len() - raw.default.line_len();
-        let mut add_target = |key: &'r str, target: &'r ChoiceTarget| {
+        let mut passage = String::new();
+        let mut add_target = |key: &str, target: &ChoiceTarget, visible: bool| {
+            // Choices are shown and picked by their translated text.
+            let key: &str = &bookmark.translate(key);
             match target {
                 // Populate unconditional level choices.
                 ChoiceTarget::PassageName(passage_name) => {
-                    passage = passage_name;
-                    choices.push(key);
-                    choice_to_passage.insert(key, passage);
+                    if visible {
+                        passage = passage_name.clone();
+                        choices.push(key);
+                        choice_to_passage.insert(key.to_string(), passage.clone());
+                    }
                 }
                 // Infer which passage this refers to.
                 ChoiceTarget::None => {
-                    choices.push(key);
-                    choice_to_passage.insert(key, passage);
+                    if visible {
+                        choices.push(key);
+                        choice_to_passage.insert(key.to_string(), passage.clone());
+                    }
                 }
-                ChoiceTarget::Lines(lines) => {
-                    choices.push(key);
-                    line_num -= line_len(lines) + 1;
-                    choice_to_line_num.insert(key, line_num);
+                // Hidden embedded passages still take up lines.
+                ChoiceTarget::Lines(_lines) => {
+                    let line_num = offsets.pop().unwrap_or_default();
+                    if visible {
+                        choices.push(key);
+                        choice_to_line_num.insert(key.to_string(), line_num);
+                    }
                 }
             }
         };
         // Populate through valid choices and infer implicit passage targets.
         for (key, choice) in raw.into_iter().rev() {
             match choice {
-                RawChoice::Target(target) => add_target(key, target),
+                RawChoice::Target(target) => add_target(key, target, true),
                 // Populate all choices are behind a true conditional.
                 RawChoice::Conditional(conditional) => {
-                    if !Value::from_conditional(key, bookmark)? {
-                        continue;
-                    }
+                    let visible = Value::from_conditional(key, context)?;
                     for (inner_key, target) in conditional.iter().rev() {
-                        add_target(inner_key, target);
+                        add_target(inner_key, target, visible);
                     }
                 }
             }
@@ -215,7 +260,7 @@ mod tests {
             &mut choice_to_passage,
             &mut choice_to_line_num,
             &raw,
-            &bookmark,
+            &Context::new(&bookmark),
         )
         .unwrap();
         assert_eq!(
@@ -231,20 +276,23 @@ mod tests {
         );
         assert_eq!(
             choice_to_passage,
-            hashmap! {
+            convert_args!(hashmap!(
                 "a" => "A",
                 "b" => "B",
                 "c" => "D",
                 "d" => "D",
-            }
+            ))
         );
 
         assert_eq!(
             choice_to_line_num,
-            hashmap! {
-                "e" => 1,
-                "f" => 3,
-            }
+            convert_args!(
+                keys = String::from,
+                hashmap!(
+                    "e" => 1,
+                    "f" => 3,
+                )
+            )
         );
     }
 }
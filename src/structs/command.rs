@@ -1,7 +1,7 @@
 use linear_map::LinearMap;
 
 use super::QualifiedName;
-use crate::{traits::CopyMerge, Bookmark, Error, Map, Result, Story, StoryGetters, Value};
+use crate::{traits::CopyMerge, Bookmark, Context, Error, Map, Result, Story, StoryGetters, Value};
 use serde::{Deserialize, Serialize};
 
 pub type Params = LinearMap<String, Value>;
@@ -57,11 +57,10 @@ where
     /// Gets the first entry in the command map.
     /// Command is really a pairing, so the map should only have one value.
     fn get_first(&self) -> Result<(&String, &ParamsT)> {
-        for value in self {
-            return Ok(value);
+        match self.into_iter().next() {
+            Some(value) => Ok(value),
+            None => Err(error!("Command was empty")),
         }
-
-        Err(error!("Command was empty"))
     }
 
     /// Checks `story`'s config for default parameters for this command.
@@ -99,7 +98,7 @@ where
         bookmark: &Bookmark,
         command_name: &str,
     ) -> Result<(String, String)> {
-        let split: Vec<&str> = command_name.split(".").collect();
+        let split: Vec<&str> = command_name.split('.').collect();
 
         // Handle character commands
         match split.as_slice() {
@@ -108,12 +107,16 @@ where
                 Self::get_qualified_command(story, bookmark, character, command_name)?,
             )),
             [command_name] => Ok((command_name.to_string(), command_name.to_string())),
-            _ => return Err(error!("Commands can only contain one '.' delimeter.")),
+            _ => Err(Error::UnknownCommand {
+                command: command_name.to_string(),
+                namespace: bookmark.namespace().to_string(),
+            }),
         }
     }
 
     /// Get the vector of qualified commands with default parameters included.
-    fn get_full_command(&self, story: &Story, bookmark: &Bookmark) -> Result<Command> {
+    fn get_full_command(&self, story: &Story, context: &Context) -> Result<Command> {
+        let bookmark = context.bookmark;
         let (command_name, params) = self.get_first()?;
         let mut command = Command::default();
         let (normalized_name, qualified_command) =
@@ -125,7 +128,7 @@ where
 
         // If the params have variable names, replace with variable value.
         for (_var, val) in merged_params.iter_mut() {
-            val.eval_as_expr(bookmark)?;
+            val.eval_as_expr(context)?;
         }
 
         command.name = qualified_command;
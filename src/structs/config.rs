@@ -1,4 +1,4 @@
-use super::{Map, Params, State};
+use super::{attributes::deserialize_attributes, AttributeConfig, Map, Params, State};
 use crate::traits::{FromYaml, Merge};
 use crate::{error::Error, SetCommand};
 use serde::{Deserialize, Serialize};
@@ -19,14 +19,17 @@ pub struct Config {
     pub commands: Map<String, Option<Params>>,
     #[serde(default)]
     pub characters: Map<String, Option<CharacterData>>,
-    #[serde(default)]
-    pub attributes: Map<String, Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_attributes")]
+    pub attributes: AttributeConfig,
     #[serde(default)]
     #[serde(rename = "onEnter")]
     pub on_enter: Option<SetCommand>,
     #[serde(default)]
     #[serde(rename = "onExit")]
     pub on_exit: Option<SetCommand>,
+    /// Passages meant to end the story, which the analyzer doesn't report as dead ends.
+    #[serde(default)]
+    pub endings: Vec<String>,
 }
 
 impl FromYaml for Config {}
@@ -37,6 +40,7 @@ impl Merge for Config {
         self.commands.merge(&mut other.commands)?;
         self.state.merge(&mut other.state)?;
         self.attributes.merge(&mut other.attributes)?;
+        self.endings.append(&mut other.endings);
 
         // Merge automatic setters.
         if self.on_enter.is_none() && other.on_enter.is_some() {
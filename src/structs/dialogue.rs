@@ -1,6 +1,7 @@
-use super::{extract_attr, Attributes, Bookmark, Map, Story};
+use super::{extract_attr, Attributes, Map, Story};
 use crate::error::{Error, Result};
 use crate::vars::replace_vars;
+use crate::Context;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -8,6 +9,9 @@ pub struct Dialogue {
     pub name: String,
     pub text: String,
     pub attributes: Attributes,
+    /// True if the line was already shown, in this or an earlier playthrough.
+    #[serde(default)]
+    pub seen: bool,
 }
 
 impl Dialogue {
@@ -18,30 +22,35 @@ impl Dialogue {
     ) -> Result<(Attributes, String)> {
         match story.get(namespace) {
             Some(section) => extract_attr(text, section.attributes()),
-            None => Err(error!(
-                "No such namespace '{}', required for checking attributes in '{}'",
-                namespace, text
-            )),
+            None => Err(Error::UnknownNamespace {
+                namespace: namespace.to_string(),
+            }),
         }
     }
 
-    pub fn from_map(map: &Map<String, String>, story: &Story, bookmark: &Bookmark) -> Result<Self> {
-        for (name, text) in map {
-            return Self::from(name, text, story, bookmark);
+    pub fn from_map(map: &Map<String, String>, story: &Story, context: &Context) -> Result<Self> {
+        match map.iter().next() {
+            Some((name, text)) => Self::from(name, text, story, context),
+            None => Ok(Self::default()),
         }
-        Ok(Self::default())
     }
 
-    pub fn from(name: &str, text: &str, story: &Story, bookmark: &Bookmark) -> Result<Self> {
-        let (attributes, text) = Self::extract_attr(&text, bookmark.namespace(), story)?;
+    pub fn from(name: &str, text: &str, story: &Story, context: &Context) -> Result<Self> {
+        let bookmark = context.bookmark;
+        // Replace variables first so attribute offsets index into the final text.
+        // Tags in their values are escaped, so only the line's own markup is extracted.
+        let text = replace_vars(&bookmark.translate(text), context);
+        let (mut attributes, text) = Self::extract_attr(&text, bookmark.namespace(), story)?;
+        bookmark.offsets.convert(&text, &mut attributes);
 
         // For local characters, append the namespace to their name.
         let name = bookmark.qualified_character_name(story, name)?;
 
         Ok(Self {
             name,
-            text: replace_vars(&text, bookmark),
-            attributes: attributes,
+            text,
+            attributes,
+            seen: false,
         })
     }
 }
@@ -49,7 +58,7 @@ impl Dialogue {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Config, Map, Section, GLOBAL};
+    use crate::{Attribute, Bookmark, Config, Map, Params, Section, GLOBAL};
 
     #[test]
     fn test_dialogue() {
@@ -73,16 +82,20 @@ mod tests {
         });
         let dialogue_map =
             hashmap! {"Character".to_string() => "Text <attr>annotated</attr>.".to_string()};
-        let dialogue = Dialogue::from_map(&dialogue_map, &story, &bookmark).unwrap();
+        let dialogue = Dialogue::from_map(&dialogue_map, &story, &Context::new(&bookmark)).unwrap();
 
         assert_eq!(
             dialogue,
             Dialogue {
                 name: "Character".to_string(),
                 text: "Text annotated.".to_string(),
-                attributes: hashmap! {
-                    "attr".to_string() => vec![5 as usize, 14]
-                }
+                attributes: vec![Attribute {
+                    name: "attr".to_string(),
+                    start: 5,
+                    end: 14,
+                    params: Params::new(),
+                }],
+                seen: false,
             }
         )
     }
@@ -0,0 +1,139 @@
+use super::{line_len, Passages, RawLine, Return};
+use crate::Map;
+use std::sync::Arc;
+
+/// A passage flattened into a single array of lines that a runner can index into.
+/// Lines embedded in branches, variations and choices follow the line containing them,
+/// with a break before each alternative after the first, and the passage ends with a return.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlatPassage {
+    pub lines: Vec<RawLine>,
+    /// Index of the innermost branches, variations or choices line containing each line.
+    parents: Vec<Option<usize>>,
+}
+
+/// Flattened passages, keyed by passage name. Runners share them while running a passage.
+pub type FlatPassages = Map<String, Arc<FlatPassage>>;
+
+impl FlatPassage {
+    pub fn new(passage: &[RawLine]) -> Self {
+        let mut flat = Self::default();
+        flat.add_lines(passage, None);
+        flat.push(RawLine::Return(Return { r#return: () }), None);
+        flat
+    }
+
+    /// Flattens every passage in `passages`.
+    pub fn flatten_all(passages: &Passages) -> FlatPassages {
+        passages
+            .iter()
+            .map(|(name, passage)| (name.clone(), Arc::new(Self::new(passage))))
+            .collect()
+    }
+
+    /// Returns the break points for a runner starting at `line`,
+    /// which are the ends of the blocks containing it from outermost to innermost.
+    pub fn breaks(&self, line: usize) -> Vec<usize> {
+        let mut breaks = Vec::new();
+        let mut parent = self.parents.get(line).copied().flatten();
+        while let Some(container) = parent {
+            breaks.push(container + line_len(&self.lines[container..=container]));
+            parent = self.parents[container];
+        }
+        breaks.reverse();
+        breaks
+    }
+
+    fn push(&mut self, line: RawLine, parent: Option<usize>) {
+        self.lines.push(line);
+        self.parents.push(parent);
+    }
+
+    /// Adds each alternative of the container at `container`, separated by breaks.
+    fn add_alternatives<'a>(
+        &mut self,
+        alternatives: impl Iterator<Item = &'a [RawLine]>,
+        container: usize,
+    ) {
+        for (i, lines) in alternatives.enumerate() {
+            if i > 0 {
+                self.push(RawLine::Break, Some(container));
+            }
+            self.add_lines(lines, Some(container));
+        }
+    }
+
+    fn add_lines(&mut self, lines: &[RawLine], parent: Option<usize>) {
+        for line in lines {
+            let line_num = self.lines.len();
+            self.push(line.clone(), parent);
+            match line {
+                RawLine::Branches(branches) => self.add_alternatives(
+                    branches.exprs.values().map(|lines| lines.as_slice()),
+                    line_num,
+                ),
+                RawLine::Variations(variations) => self.add_alternatives(
+                    variations.alternatives.iter().map(|lines| lines.as_slice()),
+                    line_num,
+                ),
+                RawLine::Choices(choices) => {
+                    self.add_alternatives(choices.embedded().into_iter(), line_num)
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_passage() {
+        let passage: Vec<RawLine> = serde_yaml::from_str(
+            r#"
+            - Start.
+            - if $a:
+                - A.
+                - if $b:
+                    - B.
+                  else:
+                    - Not B.
+              else:
+                - Not A.
+            - End.
+            "#,
+        )
+        .unwrap();
+        let flat = FlatPassage::new(&passage);
+        assert_eq!(flat.lines.len(), line_len(&passage) + 1);
+        assert_eq!(
+            flat.lines[2..7]
+                .iter()
+                .map(|line| match line {
+                    RawLine::Text(text) => text.as_str(),
+                    RawLine::Branches(_) => "if",
+                    RawLine::Break => "break",
+                    _ => "other",
+                })
+                .collect::<Vec<&str>>(),
+            vec!["A.", "if", "B.", "break", "Not B."]
+        );
+        assert!(matches!(flat.lines.last(), Some(RawLine::Return(_))));
+
+        // Branches are shared with the passage instead of copied.
+        match (&passage[1], &flat.lines[1]) {
+            (RawLine::Branches(original), RawLine::Branches(flattened)) => {
+                assert!(Arc::ptr_eq(original, flattened))
+            }
+            _ => panic!("Expected branches"),
+        }
+
+        // "Not B." is inside both branches, which end at the outer break and at "End.".
+        assert_eq!(flat.breaks(6), vec![9, 7]);
+        assert_eq!(flat.breaks(8), vec![9]);
+        assert_eq!(flat.breaks(9), Vec::<usize>::new());
+        assert_eq!(flat.breaks(1), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,147 @@
+use super::{Map, Position, State};
+use crate::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Everything needed to undo one call to `Runner::next`.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Step {
+    /// Thread the step was taken on.
+    pub thread: String,
+    /// Position before the step.
+    pub position: Position,
+    /// Stack before the step.
+    pub stack: Vec<Position>,
+    /// State of the random number generator before the step.
+    pub rng: u64,
+    /// Total number of transcript entries before the step.
+    #[serde(default)]
+    pub transcript: usize,
+    /// Values of the variables changed during the step from before they were changed,
+    /// keyed by namespace.
+    #[serde(default)]
+    pub state: Map<String, State>,
+    /// Visit counts of the lines visited during the step from before they were visited,
+    /// or None if they had never been visited.
+    #[serde(default)]
+    pub visits: Map<String, Option<usize>>,
+}
+
+/// Bounded list of the most recent steps through the story, for rewinding.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct History {
+    /// Maximum number of steps to keep. No steps are recorded if zero.
+    #[serde(default)]
+    pub limit: usize,
+    #[serde(default)]
+    steps: VecDeque<Step>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            steps: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Sets the maximum number of steps to keep, forgetting the oldest steps past it.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    /// Forgets all steps.
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    /// Starts recording a new step, forgetting the oldest step if over the limit.
+    pub fn push(&mut self, step: Step) {
+        if self.limit > 0 {
+            self.steps.push_back(step);
+            self.trim();
+        }
+    }
+
+    /// Iterates over the most recent steps, newest first.
+    pub fn recent(&self) -> impl Iterator<Item = &Step> {
+        self.steps.iter().rev()
+    }
+
+    /// Removes the most recent step.
+    pub fn pop(&mut self) -> Option<Step> {
+        self.steps.pop_back()
+    }
+
+    /// Records the value of `var` in `namespace` before it's first changed in the current step.
+    pub fn record_value(&mut self, namespace: &str, var: &str, value: &Value) {
+        if let Some(step) = self.steps.back_mut() {
+            let state = step
+                .state
+                .entry(namespace.to_string())
+                .or_insert_with(State::new);
+            if !state.contains_key(var) {
+                state.insert(var.to_string(), value.clone());
+            }
+        }
+    }
+
+    /// Records the visit count of `key` before it's first visited in the current step.
+    pub fn record_visits(&mut self, key: &str, visits: Option<usize>) {
+        if let Some(step) = self.steps.back_mut() {
+            if !step.visits.contains_key(key) {
+                step.visits.insert(key.to_string(), visits);
+            }
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.steps.len() > self.limit {
+            self.steps.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_limit() {
+        let mut history = History::new(2);
+        for line in 0..3 {
+            history.push(Step {
+                position: Position {
+                    line,
+                    ..Position::default()
+                },
+                ..Step::default()
+            });
+        }
+        assert_eq!(history.len(), 2);
+
+        // Only the first change to a variable in a step is kept.
+        history.record_value("global", "gold", &Value::Int(1));
+        history.record_value("global", "gold", &Value::Int(2));
+        let step = history.pop().unwrap();
+        assert_eq!(step.position.line, 2);
+        assert_eq!(step.state["global"]["gold"], Value::Int(1));
+
+        // Nothing is recorded without a limit.
+        history.set_limit(0);
+        assert!(history.is_empty());
+        history.push(Step::default());
+        assert!(history.is_empty());
+    }
+}
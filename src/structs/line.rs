@@ -1,7 +1,9 @@
 use super::{
     Branches, Choices, Command, Dialogue, Map, PositionalCommand, RawChoices, RawCommand, State,
+    Variations,
 };
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Call {
@@ -27,13 +29,15 @@ pub struct Input {
 }
 
 /// Internal representation of a line used for deserializing YAML.
+/// Lines containing other lines share them, so flattening a passage doesn't copy them.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum RawLine {
-    Branches(Branches),
+    Branches(Arc<Branches>),
+    Variations(Arc<Variations>),
     SetCommand(SetCommand),
     Input(Input),
-    Choices(RawChoices),
+    Choices(Arc<RawChoices>),
     Command(RawCommand),
     PositionalCommand(PositionalCommand),
     Call(Call),
@@ -55,13 +59,14 @@ pub enum Line {
     End,
 }
 
-/// All lines take up 1 except for branches,
+/// All lines take up 1 except for branches, variations and choices,
 /// which need their length recursively computed.
 pub fn line_len(lines: &[RawLine]) -> usize {
     let mut length = 0;
     for line in lines {
         match &line {
             RawLine::Branches(branches) => length += branches.line_len(),
+            RawLine::Variations(variations) => length += variations.line_len(),
             RawLine::Choices(choices) => length += choices.line_len(),
             _ => length += 1,
         }
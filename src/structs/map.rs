@@ -16,6 +16,7 @@ fn copy_keys<V>(map: &Map<String, V>) -> Vec<String> {
     keys
 }
 
+#[allow(dead_code)]
 fn copy_keys_linear_map<V>(map: &LinearMap<String, V>) -> Vec<String> {
     let mut keys: Vec<String> = Vec::with_capacity(map.len());
     for key in map.keys() {
@@ -37,6 +38,7 @@ impl<V> MoveValues for LinearMap<String, V> {
 }
 
 impl<V> Merge for Map<String, V> {
+    #[allow(clippy::map_entry)]
     fn merge(&mut self, other: &mut Self) -> Result<()> {
         let keys = copy_keys(other);
         for key in keys {
@@ -5,25 +5,44 @@ mod choices;
 mod command;
 mod config;
 mod dialogue;
+mod flat;
+mod history;
 mod line;
 mod map;
 mod operator;
+mod read_lines;
+mod rng;
 mod section;
+mod span;
 mod state;
 mod story;
+mod transcript;
+mod variations;
 
-pub use attributes::{extract_attr, Attributes};
-pub use bookmark::{Bookmark, Position};
+pub(crate) use attributes::escape_tags;
+pub use attributes::{extract_attr, Attribute, AttributeConfig, Attributes, OffsetUnit};
+pub use bookmark::{Bookmark, Position, Thread, MAIN_THREAD};
 pub use branches::Branches;
 pub use choices::{ChoiceTarget, Choices, RawChoice, RawChoices};
+#[allow(unused_imports)]
 pub use command::{
     Command, CommandGetters, Params, PositionalCommand, PositionalParams, RawCommand,
 };
 pub use config::{CharacterData, Config};
 pub use dialogue::Dialogue;
+pub use flat::{FlatPassage, FlatPassages};
+pub use history::{History, Step};
+#[allow(unused_imports)]
 pub use line::{line_len, Call, Input, Line, RawLine, Return, SetCommand};
+#[allow(unused_imports)]
 pub use map::{Entry, Map};
 pub use operator::{AssignOperator, Operator};
+pub use read_lines::ReadLines;
+pub use rng::Rng;
 pub use section::{QualifiedName, Section, GLOBAL};
+pub use span::{passage_spans, yaml_error, Span, Spans};
 pub use state::{State, StateMod};
-pub use story::{Passage, Passages, Story, StoryGetters};
+pub use story::{story_from_sources, Passage, Passages, Story, StoryGetters};
+pub use transcript::{Transcript, TranscriptEntry};
+pub(crate) use variations::fnv1a;
+pub use variations::{VariationKind, Variations};
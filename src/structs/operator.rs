@@ -6,6 +6,8 @@ use std::fmt;
 pub enum Operator {
     Add,
     Sub,
+    Mul,
+    Div,
     And,
     Or,
     Not,
@@ -16,11 +18,15 @@ pub enum Operator {
     Leq,
     Gt,
     Geq,
+    In,
+    Contains,
 }
 
 impl Operator {
     const ADD: &'static str = "+";
     const SUB: &'static str = "-";
+    const MUL: &'static str = "*";
+    const DIV: &'static str = "/";
     const AND: &'static str = "and";
     const OR: &'static str = "or";
     const NOT: &'static str = "not";
@@ -31,6 +37,8 @@ impl Operator {
     const LEQ: &'static str = "<=";
     const GT: &'static str = ">";
     const GEQ: &'static str = ">=";
+    const IN: &'static str = "in";
+    const CONTAINS: &'static str = "contains";
 }
 
 impl IntoStr for Operator {
@@ -38,6 +46,8 @@ impl IntoStr for Operator {
         match *self {
             Self::Add => Self::ADD,
             Self::Sub => Self::SUB,
+            Self::Mul => Self::MUL,
+            Self::Div => Self::DIV,
             Self::And => Self::AND,
             Self::Or => Self::OR,
             Self::Not => Self::NOT,
@@ -48,6 +58,8 @@ impl IntoStr for Operator {
             Self::Leq => Self::LEQ,
             Self::Gt => Self::GT,
             Self::Geq => Self::GEQ,
+            Self::In => Self::IN,
+            Self::Contains => Self::CONTAINS,
         }
     }
 }
@@ -57,6 +69,8 @@ impl FromStr<'_> for Operator {
         let op = match text {
             Self::ADD => Self::Add,
             Self::SUB => Self::Sub,
+            Self::MUL => Self::Mul,
+            Self::DIV => Self::Div,
             Self::AND => Self::And,
             Self::OR => Self::Or,
             Self::NOT => Self::Not,
@@ -67,6 +81,8 @@ impl FromStr<'_> for Operator {
             Self::LEQ => Self::Leq,
             Self::GT => Self::Gt,
             Self::GEQ => Self::Geq,
+            Self::IN => Self::In,
+            Self::CONTAINS => Self::Contains,
             _ => return Err(error!("No valid Operator matches {}", text)),
         };
         Ok(op)
@@ -0,0 +1,55 @@
+use crate::traits::{
+    FromMessagePack, FromYaml, Load, LoadMessagePack, LoadYaml, Save, SaveMessagePack, SaveYaml,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Lines of dialogue that have been shown to the player, across every playthrough.
+/// Unlike the bookmark, this is meant to be saved once per player rather than once per save,
+/// so that text read in an earlier playthrough can be skipped.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct ReadLines {
+    /// Lines keyed by their IDs as given by `line_id`, prefixed with `speaker:` for dialogue.
+    #[serde(default)]
+    pub lines: BTreeSet<String>,
+}
+
+impl ReadLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the line at `key` has been read.
+    pub fn contains(&self, key: &str) -> bool {
+        self.lines.contains(key)
+    }
+
+    /// Marks the line at `key` as read, returning true if it was already read.
+    pub fn read(&mut self, key: String) -> bool {
+        !self.lines.insert(key)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Marks every line read in `other` as read.
+    pub fn merge(&mut self, other: &Self) {
+        self.lines.extend(other.lines.iter().cloned());
+    }
+}
+
+impl FromYaml for ReadLines {}
+impl FromMessagePack for ReadLines {}
+impl SaveYaml for ReadLines {}
+impl SaveMessagePack for ReadLines {}
+impl Save for ReadLines {}
+impl LoadYaml for ReadLines {}
+impl LoadMessagePack for ReadLines {}
+impl Load for ReadLines {}
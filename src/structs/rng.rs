@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+#[derive(Deserialize)]
+pub struct RngShadow {
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    state: Option<u64>,
+}
+
+/// Seedable random number generator that is saved with the bookmark,
+/// so that a reloaded save replays the same rolls.
+/// The state is kept in a `Cell` so that expressions can roll while only reading the bookmark.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(from = "RngShadow")]
+pub struct Rng {
+    /// Seed the generator started from.
+    pub seed: u64,
+    /// Current state, advanced by each roll. Defaults to `seed` when loading.
+    state: Cell<u64>,
+}
+
+impl From<RngShadow> for Rng {
+    fn from(shadow: RngShadow) -> Self {
+        Self {
+            seed: shadow.seed,
+            state: Cell::new(shadow.state.unwrap_or(shadow.seed)),
+        }
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            state: Cell::new(seed),
+        }
+    }
+
+    #[inline]
+    pub fn state(&self) -> u64 {
+        self.state.get()
+    }
+
+    /// Restores a state previously returned by `state`.
+    #[inline]
+    pub fn set_state(&self, state: u64) {
+        self.state.set(state)
+    }
+
+    /// Returns 64 random bits using SplitMix64.
+    pub fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in `[0, 1)`.
+    pub fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a random integer in `[min, max]`.
+    pub fn range(&self, min: i64, max: i64) -> i64 {
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+        let span = (max as i128 - min as i128 + 1) as u128;
+        let offset = (self.next_u64() as u128 * span) >> 64;
+        (min as i128 + offset as i128) as i64
+    }
+
+    /// Returns true with probability `p`.
+    pub fn chance(&self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_replay() {
+        let rng = Rng::new(7);
+        let rolls: Vec<i64> = (0..20).map(|_| rng.range(1, 6)).collect();
+        assert!(rolls.iter().all(|roll| (1..=6).contains(roll)));
+        assert_ne!(rng.state(), rng.seed);
+
+        // A reloaded generator continues from the saved state.
+        let saved = serde_yaml::to_string(&rng).unwrap();
+        let reloaded: Rng = serde_yaml::from_str(&saved).unwrap();
+        assert_eq!(reloaded, rng);
+        assert_eq!(reloaded.next_u64(), rng.next_u64());
+
+        // Without a saved state, the generator starts from its seed.
+        let seeded: Rng = serde_yaml::from_str("seed: 7").unwrap();
+        let replay: Vec<i64> = (0..20).map(|_| seeded.range(1, 6)).collect();
+        assert_eq!(replay, rolls);
+    }
+}
@@ -1,12 +1,15 @@
 use crate::{
     error::Error,
-    structs::{CharacterData, Config, Params, Passage, Passages},
+    structs::{
+        passage_spans, AttributeConfig, CharacterData, Config, FlatPassage, FlatPassages, Params,
+        Passage, Passages, Span, Spans,
+    },
     traits::{FromYaml, LoadYaml, Merge},
-    Map, SetCommand, Value,
+    Exprs, Map, SetCommand, Value,
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{fmt, str::CharIndices};
+use std::{fmt, str::CharIndices, sync::Arc};
 use std::{iter::Rev, path::Path};
 
 lazy_static! {
@@ -32,10 +35,7 @@ impl<'a> QualifiedName<'a> {
                 namespace: explicit_namespace,
                 name: split_name,
             },
-            _ => Self {
-                namespace,
-                name: name,
-            },
+            _ => Self { namespace, name },
         }
     }
 
@@ -74,11 +74,11 @@ impl<'a> Iterator for NamespaceResolver<'a> {
             ResolverState::Start => {
                 // First `next` should just return the namespace.
                 self.state = ResolverState::Iter;
-                Some(&self.namespace)
+                Some(self.namespace)
             }
             ResolverState::Iter => {
                 // All subsequent `next` calls should return the parent namespaces in order.
-                while let Some((i, c)) = self.char_indices.next() {
+                for (i, c) in self.char_indices.by_ref() {
                     if c == ':' {
                         return Some(&self.namespace[0..i]);
                     }
@@ -95,18 +95,79 @@ impl<'a> Iterator for NamespaceResolver<'a> {
     }
 }
 
+#[derive(Deserialize)]
+pub struct SectionShadow {
+    config: Config,
+    passages: Passages,
+}
+
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SectionShadow")]
 pub struct Section {
     config: Config,
     pub passages: Passages,
+    /// Source locations of each passage's flattened lines. Only present when loaded from YAML files.
+    #[serde(skip)]
+    spans: Spans,
+    /// Expressions used by this section, compiled when it is loaded.
+    #[serde(skip)]
+    exprs: Exprs,
+    /// Each passage flattened for runners, built when the section is loaded.
+    #[serde(skip)]
+    flattened: FlatPassages,
+}
+
+impl From<SectionShadow> for Section {
+    fn from(shadow: SectionShadow) -> Self {
+        Self::from_parts(shadow.config, shadow.passages, Spans::new())
+    }
 }
 
 impl<'a> Section {
     #[cfg(test)]
     pub fn new(config: Config) -> Self {
+        Self::from_parts(config, Passages::new(), Spans::new())
+    }
+
+    /// Creates a section, compiling its expressions and flattening its passages.
+    fn from_parts(config: Config, passages: Passages, spans: Spans) -> Self {
+        let exprs = Exprs::compile(&config, &passages);
+        let flattened = FlatPassage::flatten_all(&passages);
         Self {
             config,
-            passages: Passages::new(),
+            passages,
+            spans,
+            exprs,
+            flattened,
+        }
+    }
+
+    /// Whether `source` is a section file, with passages after its config.
+    pub(crate) fn is_source(source: &str) -> bool {
+        SEPARATOR_RE.is_match(source)
+    }
+
+    /// Parses the source of a section file, which holds a config and then passages after `---`.
+    /// `file` is the name of the source used to locate errors.
+    pub fn from_source(source: &str, file: &str) -> Result<Self, Error> {
+        let split: Vec<&str> = SEPARATOR_RE.split(source).collect();
+        match &split[..] {
+            [config_str, passages_str] => {
+                let config = Config::from_yml(config_str).map_err(|e| e.in_file(file, 0))?;
+
+                // Passages start on the separator line, just after the `---`.
+                let line_offset = config_str.matches('\n').count() + 1;
+                let passages =
+                    Passages::from_yml(passages_str).map_err(|e| e.in_file(file, line_offset))?;
+                let spans = passage_spans(passages_str, &passages, file, line_offset, 3);
+                Ok(Self::from_parts(config, passages, spans))
+            }
+            [config_str] => Ok(Self::from_parts(
+                Config::from_yml(config_str).map_err(|e| e.in_file(file, 0))?,
+                Passages::new(),
+                Spans::new(),
+            )),
+            _ => Err(error!("Unable to parse file.").in_file(file, 0)),
         }
     }
 
@@ -121,7 +182,7 @@ impl<'a> Section {
     }
 
     #[inline]
-    pub fn attributes(&self) -> &Map<String, Option<String>> {
+    pub fn attributes(&self) -> &AttributeConfig {
         &self.config.attributes
     }
 
@@ -141,7 +202,7 @@ impl<'a> Section {
     }
 
     #[inline]
-    pub fn namespace(&'a self) -> &str {
+    pub fn namespace(&'a self) -> &'a str {
         &self.config.namespace
     }
 
@@ -150,6 +211,22 @@ impl<'a> Section {
         self.config.commands.get(name)
     }
 
+    #[inline]
+    pub fn characters(&self) -> &Map<String, Option<CharacterData>> {
+        &self.config.characters
+    }
+
+    #[inline]
+    pub fn commands(&self) -> &Map<String, Option<Params>> {
+        &self.config.commands
+    }
+
+    /// Whether `passage` is configured as an ending of the story.
+    #[inline]
+    pub fn is_ending(&self, passage: &str) -> bool {
+        self.config.endings.iter().any(|ending| ending == passage)
+    }
+
     #[inline]
     pub fn character(&'a self, name: &str) -> Option<&'a Option<CharacterData>> {
         self.config.characters.get(name)
@@ -159,12 +236,33 @@ impl<'a> Section {
     pub fn value(&'a self, name: &str) -> Option<&'a Value> {
         self.config.state.get(name)
     }
+
+    /// Gets the flattened form of passage `name`.
+    #[inline]
+    pub fn flattened(&'a self, name: &str) -> Option<&'a Arc<FlatPassage>> {
+        self.flattened.get(name)
+    }
+
+    /// Gets the expressions compiled from this section.
+    #[inline]
+    pub fn exprs(&self) -> &Exprs {
+        &self.exprs
+    }
+
+    /// Gets the source location of line `line` in the flattened passage `passage`.
+    #[inline]
+    pub fn span(&'a self, passage: &str, line: usize) -> Option<&'a Span> {
+        self.spans.get(passage)?.get(line)
+    }
 }
 
 impl Merge for Section {
     fn merge(&mut self, other: &mut Self) -> Result<(), Error> {
         self.config.merge(&mut other.config)?;
         self.passages.merge(&mut other.passages)?;
+        self.spans.merge(&mut other.spans)?;
+        self.exprs.extend(&other.exprs);
+        self.flattened.merge(&mut other.flattened)?;
         Ok(())
     }
 }
@@ -173,19 +271,8 @@ impl FromYaml for Section {}
 
 impl LoadYaml for Section {
     fn load_yml<P: AsRef<Path> + fmt::Debug>(path: P) -> Result<Self, Error> {
-        let source = Self::load_string(path)?;
-        let split: Vec<&str> = SEPARATOR_RE.split(&source).collect();
-        match &split[..] {
-            [config_str, passages_str] => Ok(Self {
-                config: Config::from_yml(config_str)?,
-                passages: Passages::from_yml(passages_str)?,
-            }),
-            [config_str] => Ok(Self {
-                config: Config::from_yml(config_str)?,
-                passages: Passages::new(),
-            }),
-            _ => Err(error!("Unable to parse file.")),
-        }
+        let source = Self::load_string(&path)?;
+        Self::from_source(&source, &path.as_ref().to_string_lossy())
     }
 }
 
@@ -0,0 +1,329 @@
+use super::{line_len, ChoiceTarget, Map, Passages, RawChoice, RawLine};
+use crate::{error::Error, traits::IntoStr};
+use std::fmt;
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+/// A location in a source file.
+/// Lines and columns start at 1. A line of 0 means only the file is known.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(file: &str, line: usize, column: usize) -> Self {
+        Self {
+            file: file.to_string(),
+            line,
+            column,
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.file)
+        } else {
+            write!(f, "{}:{}:{}", self.file, self.line, self.column)
+        }
+    }
+}
+
+/// Mapping of passage name to the span of each line in its flattened form.
+pub type Spans = Map<String, Vec<Span>>;
+
+/// Converts a YAML error into an error located where serde_yaml reports it.
+/// The location is relative to the parsed text and has no file until `Error::in_file` is called.
+pub fn yaml_error(e: serde_yaml::Error) -> Error {
+    let message = e.to_string();
+    match e.location() {
+        Some(location) => {
+            // serde_yaml appends the location to its message, which the span replaces.
+            let suffix = format!(" at line {} column {}", location.line(), location.column());
+            let message = message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string();
+            Error::YamlSyntax { message }.at(Span::new("", location.line(), location.column()))
+        }
+        None => Error::YamlSyntax { message },
+    }
+}
+
+/// A YAML node annotated with where it starts in the source.
+enum Node {
+    Scalar(Marker, String),
+    Seq(Marker, Vec<Node>),
+    Map(Marker, Vec<(Node, Node)>),
+}
+
+impl Node {
+    fn marker(&self) -> &Marker {
+        match self {
+            // Block mappings are marked where their first value starts, so use the first key instead.
+            Self::Map(_, entries) if !entries.is_empty() => entries[0].0.marker(),
+            Self::Scalar(marker, _) | Self::Seq(marker, _) | Self::Map(marker, _) => marker,
+        }
+    }
+
+    /// Gets the value node for `key` if this is a mapping.
+    fn get(&self, key: &str) -> Option<&Node> {
+        if let Self::Map(_, entries) = self {
+            for (entry_key, value) in entries {
+                if let Self::Scalar(_, text) = entry_key {
+                    if text == key {
+                        return Some(value);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Gets the key node for `key` if this is a mapping.
+    fn get_key(&self, key: &str) -> Option<&Node> {
+        if let Self::Map(_, entries) = self {
+            for (entry_key, _value) in entries {
+                if let Self::Scalar(_, text) = entry_key {
+                    if text == key {
+                        return Some(entry_key);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Builds a tree of `Node` from the yaml_rust event stream.
+#[derive(Default)]
+struct NodeBuilder {
+    stack: Vec<(Node, Option<Node>)>,
+    root: Option<Node>,
+}
+
+impl NodeBuilder {
+    fn complete(&mut self, node: Node) {
+        match self.stack.last_mut() {
+            Some((Node::Seq(_, items), _)) => items.push(node),
+            Some((Node::Map(_, entries), pending_key)) => match pending_key.take() {
+                Some(key) => entries.push((key, node)),
+                None => *pending_key = Some(node),
+            },
+            _ => self.root = Some(node),
+        }
+    }
+}
+
+impl MarkedEventReceiver for NodeBuilder {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        match event {
+            Event::Scalar(text, ..) => self.complete(Node::Scalar(marker, text)),
+            Event::Alias(_) => self.complete(Node::Scalar(marker, String::new())),
+            Event::SequenceStart(_) => self.stack.push((Node::Seq(marker, Vec::new()), None)),
+            Event::MappingStart(_) => self.stack.push((Node::Map(marker, Vec::new()), None)),
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Some((node, _)) = self.stack.pop() {
+                    self.complete(node)
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Records spans for passage lines in the same order as `Runner::load_lines` flattens them.
+struct SpanRecorder<'a> {
+    file: &'a str,
+    line_offset: usize,
+    column_offset: usize,
+}
+
+impl<'a> SpanRecorder<'a> {
+    fn span(&self, node: &Node) -> Span {
+        let marker = node.marker();
+        let column = if marker.line() == 1 {
+            marker.col() + self.column_offset
+        } else {
+            marker.col()
+        };
+        Span::new(self.file, marker.line() + self.line_offset, column + 1)
+    }
+
+    /// Fills in `count` spans pointing at `node` when the tree doesn't have the expected shape.
+    fn pad(&self, node: &Node, count: usize, spans: &mut Vec<Span>) {
+        for _ in 0..count {
+            spans.push(self.span(node));
+        }
+    }
+
+    fn record_target(
+        &self,
+        target: &ChoiceTarget,
+        key: &Node,
+        value: Option<&Node>,
+        is_first: &mut bool,
+        spans: &mut Vec<Span>,
+    ) {
+        if let ChoiceTarget::Lines(lines) = target {
+            if !*is_first {
+                spans.push(self.span(key));
+            }
+            match value {
+                Some(value) => self.record_lines(lines, value, spans),
+                None => self.pad(key, line_len(lines), spans),
+            }
+            *is_first = false;
+        }
+    }
+
+    fn record_line(&self, line: &RawLine, node: &Node, spans: &mut Vec<Span>) {
+        spans.push(self.span(node));
+        match line {
+            RawLine::Branches(branches) => {
+                let mut is_first = true;
+                for (expression, lines) in &branches.exprs {
+                    if !is_first {
+                        let key = node.get_key(expression).unwrap_or(node);
+                        spans.push(self.span(key));
+                    }
+                    match node.get(expression) {
+                        Some(value) => self.record_lines(lines, value, spans),
+                        None => self.pad(node, line_len(lines), spans),
+                    }
+                    is_first = false;
+                }
+            }
+            RawLine::Choices(choices) => {
+                let choices_node = node.get("choices").unwrap_or(node);
+                let mut is_first = true;
+                for (key, choice) in choices.as_ref() {
+                    let key_node = choices_node.get_key(key).unwrap_or(choices_node);
+                    let value = choices_node.get(key);
+                    match choice {
+                        RawChoice::Target(target) => {
+                            self.record_target(target, key_node, value, &mut is_first, spans)
+                        }
+                        RawChoice::Conditional(conditional) => {
+                            let conditional_node = value.unwrap_or(key_node);
+                            for (inner_key, target) in conditional {
+                                let inner_key_node =
+                                    conditional_node.get_key(inner_key).unwrap_or(key_node);
+                                self.record_target(
+                                    target,
+                                    inner_key_node,
+                                    conditional_node.get(inner_key),
+                                    &mut is_first,
+                                    spans,
+                                )
+                            }
+                        }
+                    }
+                }
+                let default_key = node.get_key("default").unwrap_or(node);
+                self.record_target(
+                    &choices.default,
+                    default_key,
+                    node.get("default"),
+                    &mut is_first,
+                    spans,
+                );
+            }
+            RawLine::Variations(variations) => {
+                let alternatives_node = node.get(variations.kind.into_str()).unwrap_or(node);
+                let items: &[Node] = match alternatives_node {
+                    Node::Seq(_, items) => items,
+                    _ => &[],
+                };
+                for (i, lines) in variations.alternatives.iter().enumerate() {
+                    let item = items.get(i).unwrap_or(alternatives_node);
+                    if i > 0 {
+                        spans.push(self.span(item));
+                    }
+                    match (item, lines.as_slice()) {
+                        (Node::Seq(..), _) => self.record_lines(lines, item, spans),
+                        // An alternative written as a single line.
+                        (_, [line]) if i < items.len() => self.record_line(line, item, spans),
+                        _ => self.pad(item, line_len(lines), spans),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn record_lines(&self, lines: &[RawLine], node: &Node, spans: &mut Vec<Span>) {
+        match node {
+            Node::Seq(_, items) => {
+                for (line, item) in lines.iter().zip(items) {
+                    self.record_line(line, item, spans);
+                }
+            }
+            _ => self.pad(node, line_len(lines), spans),
+        }
+    }
+}
+
+/// Computes the span of every flattened line in `passages`, parsed from `source`.
+/// `source` begins `line_offset` lines and `column_offset` columns into `file`.
+pub fn passage_spans(
+    source: &str,
+    passages: &Passages,
+    file: &str,
+    line_offset: usize,
+    column_offset: usize,
+) -> Spans {
+    let mut builder = NodeBuilder::default();
+    let mut spans = Spans::new();
+    if Parser::new(source.chars())
+        .load(&mut builder, false)
+        .is_err()
+    {
+        return spans;
+    }
+
+    let root = match builder.root {
+        Some(root) => root,
+        None => return spans,
+    };
+    let recorder = SpanRecorder {
+        file,
+        line_offset,
+        column_offset,
+    };
+    for (passage_name, passage) in passages {
+        if let Some(node) = root.get(passage_name) {
+            let mut passage_spans = Vec::with_capacity(line_len(passage));
+            recorder.record_lines(passage, node, &mut passage_spans);
+            spans.insert(passage_name.to_string(), passage_spans);
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FromYaml;
+
+    #[test]
+    fn test_passage_spans() {
+        let source = "Start:\n  - Alice: Hi\n  - if true:\n      - Yes\n    else:\n      - No\n";
+        let passages = Passages::from_yml(source).unwrap();
+        let spans = passage_spans(source, &passages, "story.yml", 10, 0);
+
+        let lines: Vec<(usize, usize)> = spans["Start"]
+            .iter()
+            .map(|span| (span.line, span.column))
+            .collect();
+        // Alice line, if line, Yes, break at else, No.
+        assert_eq!(lines, vec![(12, 5), (13, 5), (14, 9), (15, 5), (16, 9)]);
+        assert_eq!(spans["Start"][0].to_string(), "story.yml:12:5");
+    }
+}
@@ -35,16 +35,50 @@ impl<'a> FromStr<'a> for StateMod<'a> {
 }
 
 impl<'a> StateMod<'a> {
-    pub fn apply(&self, state: &mut State, value: Value) -> Result<()> {
+    /// Applies this modification with `value` to `state`, the state of `namespace`.
+    /// Unless `lenient`, operations on unsupported types are errors instead of being ignored.
+    pub fn apply(
+        &self,
+        namespace: &str,
+        state: &mut State,
+        value: Value,
+        lenient: bool,
+    ) -> Result<()> {
         if let Some(state_value) = state.get_mut(self.var) {
             match self.op {
-                AssignOperator::None => *state_value = value.clone(),
-                AssignOperator::Add => *state_value += value,
-                AssignOperator::Sub => *state_value -= value,
+                // Integers assigned to a float variable keep it a float.
+                AssignOperator::None => match (&state_value, value) {
+                    (Value::Number(_), Value::Int(n)) => *state_value = Value::Number(n as f64),
+                    (_, value) => *state_value = value,
+                },
+                AssignOperator::Add if lenient => *state_value += value,
+                AssignOperator::Add => state_value.checked_add_assign(value)?,
+                AssignOperator::Sub if lenient => *state_value -= value,
+                AssignOperator::Sub => state_value.checked_sub_assign(value)?,
             };
             Ok(())
         } else {
-            Err(error!("Undefined state variable '{}'", self.var))
+            Err(Error::UndefinedVariable {
+                var: self.var.to_string(),
+                namespace: namespace.to_string(),
+            })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_undefined() {
+        let statemod = StateMod::from_str("$missing +").unwrap();
+        let error = statemod
+            .apply("town", &mut State::new(), Value::Int(1), false)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::UndefinedVariable { var, namespace } if var == "missing" && namespace == "town"
+        ));
+    }
+}
@@ -1,11 +1,11 @@
-use super::{CharacterData, Map, Params, QualifiedName, RawLine, Section};
+use super::{yaml_error, CharacterData, Map, Params, QualifiedName, RawLine, Section};
 use crate::error::{Error, Result};
 use crate::traits::SaveYaml;
 use crate::{
     traits::{FromMessagePack, FromYaml, Load, LoadYaml, Merge, Save, SaveMessagePack},
     LoadMessagePack,
 };
-use crate::{Bookmark, SetCommand, Value};
+use crate::{Bookmark, Functions, SetCommand, Value};
 use glob::glob;
 use std::{fmt, path::Path};
 
@@ -21,7 +21,7 @@ impl FromYaml for Passages {
 
         match serde_yaml::from_str(text) {
             Ok(config) => Ok(config),
-            Err(e) => Err(error!("Invalid YAML for passages: {}", e)),
+            Err(e) => Err(yaml_error(e)),
         }
     }
 }
@@ -35,16 +35,17 @@ pub trait StoryGetters<'a> {
         &'a self,
         qname: &QualifiedName,
         getter: fn(&'a Section, &str) -> Option<T>,
-    ) -> Result<T>;
+    ) -> Result<Option<T>>;
     fn resolve_with_section<'n, T>(
         &'a self,
         qname: &'n QualifiedName,
         getter: fn(&'a Section, &'n str) -> Option<T>,
-    ) -> Result<(&'n str, &'a Section, T)>;
+    ) -> Result<Option<(&'n str, &'a Section, T)>>;
     fn apply_set_commands(
         &'a self,
         getter: fn(&'a Section) -> &Option<SetCommand>,
         bookmark: &mut Bookmark,
+        functions: Option<&Functions>,
     ) -> Result<()>;
     fn passage<'n>(
         &'a self,
@@ -60,60 +61,62 @@ pub trait StoryGetters<'a> {
 
 impl<'a> StoryGetters<'a> for Story {
     /// Iterates over possible resolutions of the identifier.
-    /// Returns None if any of the namespaces don't exist or the identifier could not be found.
+    /// Returns an error if any of the namespaces don't exist, or None if the identifier could not be found.
     fn resolve<T>(
         &'a self,
         qname: &QualifiedName,
         getter: fn(&'a Section, &str) -> Option<T>,
-    ) -> Result<T> {
-        let (_namespace, _section, data) = self.resolve_with_section(qname, getter)?;
-        Ok(data)
+    ) -> Result<Option<T>> {
+        Ok(self
+            .resolve_with_section(qname, getter)?
+            .map(|(_namespace, _section, data)| data))
     }
 
-    // / Iterates over possible resolutions of the identifier.
-    // / Returns None if any of the namespaces don't exist or the identifier could not be found.
+    /// Iterates over possible resolutions of the identifier.
+    /// Returns an error if any of the namespaces don't exist, or None if the identifier could not be found.
     fn resolve_with_section<'n, T>(
         &'a self,
         qname: &'n QualifiedName,
         getter: fn(&'a Section, &'n str) -> Option<T>,
-    ) -> Result<(&'n str, &'a Section, T)> {
+    ) -> Result<Option<(&'n str, &'a Section, T)>> {
         for namespace in qname.resolve() {
-            // println!("Resolving '{}' in namespace '{}'", qname.name, namespace);
             if let Some(section) = self.get(namespace) {
                 if let Some(data) = getter(section, qname.name) {
-                    return Ok((namespace, section, data));
+                    return Ok(Some((namespace, section, data)));
                 }
             } else {
-                return Err(error!("Namespace '{}' does not exist", namespace));
+                return Err(Error::UnknownNamespace {
+                    namespace: namespace.to_string(),
+                });
             }
         }
-        Err(error!(
-            "Identifier '{}' was not found in any namespaces.",
-            qname.name
-        ))
+        Ok(None)
     }
 
     fn apply_set_commands(
         &'a self,
         getter: fn(&'a Section) -> &Option<SetCommand>,
         bookmark: &mut Bookmark,
+        functions: Option<&Functions>,
     ) -> Result<()> {
-        let mut set_commands: Vec<&SetCommand> = Vec::new();
+        let mut set_commands: Vec<(&Section, &SetCommand)> = Vec::new();
 
         // Collect all set commands to run.
         let qname = QualifiedName::from(bookmark.namespace(), "");
         for namespace in qname.resolve() {
             if let Some(section) = self.get(namespace) {
                 if let Some(set_cmd) = getter(section) {
-                    set_commands.push(&set_cmd);
+                    set_commands.push((section, set_cmd));
                 }
             } else {
-                return Err(error!("Invalid namespace '{}'", namespace));
+                return Err(Error::UnknownNamespace {
+                    namespace: namespace.to_string(),
+                });
             }
         }
-        // Apply all  set commands to bookmark.
-        for set_command in set_commands {
-            bookmark.set_state(&set_command.set)?;
+        // Apply all  set commands to bookmark, using the expressions compiled by their own section.
+        for (section, set_command) in set_commands {
+            bookmark.set_state_with(&set_command.set, Some(section.exprs()), functions)?;
         }
         Ok(())
     }
@@ -122,43 +125,47 @@ impl<'a> StoryGetters<'a> for Story {
         &'a self,
         qname: &'n QualifiedName,
     ) -> Result<(&'n str, &'a Section, &'a Option<CharacterData>)> {
-        match self.resolve_with_section(qname, |section, name| section.character(name)) {
-            Ok((namespace, section, data)) => Ok((namespace, section, data)),
-            Err(e) => Err(error!("Invalid character: {}", e)),
-        }
+        self.resolve_with_section(qname, |section, name| section.character(name))?
+            .ok_or_else(|| Error::UnknownCharacter {
+                character: qname.name.to_string(),
+                namespace: qname.namespace.to_string(),
+            })
     }
     fn value(&'a self, qname: &QualifiedName) -> Result<&'a Value> {
-        match self.resolve(qname, |section, name| section.value(name)) {
-            Ok(data) => Ok(data),
-            Err(e) => Err(error!("Invalid variable: {}", e)),
-        }
+        self.resolve(qname, |section, name| section.value(name))?
+            .ok_or_else(|| Error::UndefinedVariable {
+                var: qname.name.to_string(),
+                namespace: qname.namespace.to_string(),
+            })
     }
     fn params(&'a self, qname: &QualifiedName) -> Result<&'a Option<Params>> {
-        match self.resolve(qname, |section, name| section.params(name)) {
-            Ok(data) => Ok(data),
-            Err(e) => Err(error!("Invalid command: {}", e)),
-        }
+        self.resolve(qname, |section, name| section.params(name))?
+            .ok_or_else(|| Error::UnknownCommand {
+                command: qname.name.to_string(),
+                namespace: qname.namespace.to_string(),
+            })
     }
     fn passage<'n>(
         &'a self,
         qname: &'n QualifiedName,
     ) -> Result<(&'n str, &'a Section, &'a Passage)> {
-        match self.resolve_with_section(qname, |section, name| section.passage(name)) {
-            Ok(data) => Ok(data),
-            Err(e) => Err(error!("Invalid passage: {}", e)),
-        }
+        self.resolve_with_section(qname, |section, name| section.passage(name))?
+            .ok_or_else(|| Error::UnknownPassage {
+                passage: qname.name.to_string(),
+                namespace: qname.namespace.to_string(),
+            })
     }
 }
 
-impl<'a> FromMessagePack for Story {}
+impl FromMessagePack for Story {}
 
 impl SaveYaml for Story {}
 impl SaveMessagePack for Story {}
 impl Save for Story {}
 impl FromYaml for Story {}
 
-fn load_section<P: AsRef<Path> + fmt::Debug>(story: &mut Story, section_path: P) -> Result<()> {
-    let mut section = Section::load_yml(section_path)?;
+/// Adds `section` to `story`, merging it into any section already in its namespace.
+fn add_section(story: &mut Story, mut section: Section) -> Result<()> {
     let namespace = section.namespace();
     match story.get_mut(namespace) {
         Some(story_section) => {
@@ -171,15 +178,37 @@ fn load_section<P: AsRef<Path> + fmt::Debug>(story: &mut Story, section_path: P)
     Ok(())
 }
 
+/// Parses a story from the sources of its section files, given as pairs of file name and source.
+/// Sections in the same namespace are merged, as when loading a story directory.
+pub fn story_from_sources<'s, I>(sources: I) -> Result<Story>
+where
+    I: IntoIterator<Item = (&'s str, &'s str)>,
+{
+    let mut story = Story::new();
+    for (file, source) in sources {
+        add_section(&mut story, Section::from_source(source, file)?)?;
+    }
+    Ok(story)
+}
+
 impl LoadYaml for Story {
     /// Loads a story from a given directory or YAML file.
+    /// The file can be a single section file or a whole story saved as YAML.
     fn load_yml<P: AsRef<Path> + fmt::Debug>(path: P) -> Result<Self> {
         let mut story = Self::new();
 
         // Handle loading a single path story.
         if path.as_ref().is_file() {
-            return match Self::load_string(path) {
-                Ok(source) => Self::from_yml(&source),
+            return match Self::load_string(&path) {
+                Ok(source) if Section::is_source(&source) => {
+                    add_section(
+                        &mut story,
+                        Section::from_source(&source, &path.as_ref().to_string_lossy())?,
+                    )?;
+                    Ok(story)
+                }
+                Ok(source) => Self::from_yml(&source)
+                    .map_err(|e| e.in_file(&path.as_ref().to_string_lossy(), 0)),
                 Err(e) => Err(error!("Error loading YAML: {}", e)),
             };
         }
@@ -190,10 +219,11 @@ impl LoadYaml for Story {
             .into_os_string()
             .into_string()
             .unwrap();
-        for entry in glob(pattern).expect("Failed to read glob pattern") {
-            if let Ok(path) = entry {
-                load_section(&mut story, path)?;
-            }
+        for path in glob(pattern)
+            .expect("Failed to read glob pattern")
+            .flatten()
+        {
+            add_section(&mut story, Section::load_yml(path)?)?;
         }
         Ok(story)
     }
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{vec_deque, VecDeque};
+
+/// A line of dialogue shown to the player, or a choice they picked.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct TranscriptEntry {
+    pub namespace: String,
+    pub passage: String,
+    /// Name of the speaker. Empty for choices.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub speaker: String,
+    pub text: String,
+    /// True if this entry is a choice the player picked.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub choice: bool,
+}
+
+/// Bounded log of the most recent dialogue and choices, for showing a backlog.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Transcript {
+    /// Maximum number of entries to keep. Nothing is logged if zero.
+    #[serde(default)]
+    pub limit: usize,
+    /// Number of entries ever logged, including those forgotten past the limit.
+    #[serde(default)]
+    total: usize,
+    #[serde(default)]
+    entries: VecDeque<TranscriptEntry>,
+}
+
+impl Transcript {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Iterates over the entries from oldest to newest.
+    pub fn iter(&self) -> vec_deque::Iter<'_, TranscriptEntry> {
+        self.entries.iter()
+    }
+
+    /// Iterates over the last `count` entries from oldest to newest.
+    pub fn recent(&self, count: usize) -> vec_deque::Iter<'_, TranscriptEntry> {
+        self.entries.range(self.len().saturating_sub(count)..)
+    }
+
+    /// Sets the maximum number of entries to keep, forgetting the oldest entries past it.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    /// Forgets all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Logs `entry`, forgetting the oldest entry if over the limit.
+    pub fn push(&mut self, entry: TranscriptEntry) {
+        if self.limit > 0 {
+            self.entries.push_back(entry);
+            self.total += 1;
+            self.trim();
+        }
+    }
+
+    /// Removes the entries logged after the first `total` entries.
+    pub fn truncate(&mut self, total: usize) {
+        while self.total > total {
+            self.entries.pop_back();
+            self.total -= 1;
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.limit {
+            self.entries.pop_front();
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Transcript {
+    type Item = &'a TranscriptEntry;
+    type IntoIter = vec_deque::Iter<'a, TranscriptEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str) -> TranscriptEntry {
+        TranscriptEntry {
+            text: text.to_string(),
+            ..TranscriptEntry::default()
+        }
+    }
+
+    #[test]
+    fn test_transcript() {
+        let mut transcript = Transcript::new(3);
+        for text in &["A", "B", "C", "D"] {
+            transcript.push(entry(text));
+        }
+        let texts = |entries: vec_deque::Iter<TranscriptEntry>| -> Vec<String> {
+            entries.map(|entry| entry.text.clone()).collect()
+        };
+        assert_eq!(texts(transcript.iter()), vec!["B", "C", "D"]);
+        assert_eq!(texts(transcript.recent(2)), vec!["C", "D"]);
+        assert_eq!(texts(transcript.recent(5)), vec!["B", "C", "D"]);
+
+        transcript.truncate(3);
+        assert_eq!(texts(transcript.iter()), vec!["B", "C"]);
+        assert_eq!(transcript.total(), 3);
+
+        let saved = serde_yaml::to_string(&transcript).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Transcript>(&saved).unwrap(),
+            transcript
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use super::{line_len, Bookmark, RawLine};
+use crate::{
+    error::{Error, Result},
+    locale::line_id,
+    traits::{FromStr, IntoStr},
+};
+use linear_map::LinearMap;
+use serde::{Deserialize, Serialize};
+
+/// How a variations line picks an alternative each time it is visited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariationKind {
+    /// Plays each alternative in order, then keeps repeating the last one.
+    Sequence,
+    /// Plays each alternative in order, then starts over.
+    Cycle,
+    /// Plays every alternative once in a shuffled order, then reshuffles.
+    Shuffle,
+    /// Plays each alternative in order, then plays nothing.
+    Once,
+}
+
+impl VariationKind {
+    const SEQUENCE: &'static str = "sequence";
+    const CYCLE: &'static str = "cycle";
+    const SHUFFLE: &'static str = "shuffle";
+    const ONCE: &'static str = "once";
+
+    /// Returns the index of the alternative to play after `visits` previous visits out of `len` alternatives.
+    /// Returns None if nothing should be played.
+    pub fn pick(&self, visits: usize, len: usize, seed: u64) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        match self {
+            Self::Sequence => Some(visits.min(len - 1)),
+            Self::Cycle => Some(visits % len),
+            Self::Shuffle => {
+                let round = (visits / len) as u64;
+                let order = permutation(len, seed ^ round.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                Some(order[visits % len])
+            }
+            Self::Once => {
+                if visits < len {
+                    Some(visits)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl IntoStr for VariationKind {
+    fn into_str(&self) -> &str {
+        match *self {
+            Self::Sequence => Self::SEQUENCE,
+            Self::Cycle => Self::CYCLE,
+            Self::Shuffle => Self::SHUFFLE,
+            Self::Once => Self::ONCE,
+        }
+    }
+}
+
+impl FromStr<'_> for VariationKind {
+    fn from_str(text: &str) -> Result<Self> {
+        match text {
+            Self::SEQUENCE => Ok(Self::Sequence),
+            Self::CYCLE => Ok(Self::Cycle),
+            Self::SHUFFLE => Ok(Self::Shuffle),
+            Self::ONCE => Ok(Self::Once),
+            _ => Err(error!("Invalid variation kind '{}'", text)),
+        }
+    }
+}
+
+/// An alternative written either as a list of lines or as a single line.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Alternative {
+    Lines(Vec<RawLine>),
+    Line(RawLine),
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct VariationsShadow {
+    #[serde(flatten)]
+    alternatives: LinearMap<String, Vec<Alternative>>,
+}
+
+/// A line that plays one of several alternatives depending on how many times it has been visited.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "VariationsShadow", into = "VariationsShadow")]
+pub struct Variations {
+    pub kind: VariationKind,
+    pub alternatives: Vec<Vec<RawLine>>,
+}
+
+impl std::convert::TryFrom<VariationsShadow> for Variations {
+    type Error = &'static str;
+    fn try_from(shadow: VariationsShadow) -> std::result::Result<Self, Self::Error> {
+        if shadow.alternatives.len() != 1 {
+            return Err("Invalid.");
+        }
+        let (key, alternatives) = shadow.alternatives.into_iter().next().unwrap();
+        let kind = VariationKind::from_str(&key).map_err(|_: Error| "Invalid.")?;
+        if alternatives.is_empty() {
+            return Err("Variations need at least one alternative.");
+        }
+        Ok(Self {
+            kind,
+            alternatives: alternatives
+                .into_iter()
+                .map(|alternative| match alternative {
+                    Alternative::Lines(lines) => lines,
+                    Alternative::Line(line) => vec![line],
+                })
+                .collect(),
+        })
+    }
+}
+
+impl From<Variations> for VariationsShadow {
+    fn from(variations: Variations) -> Self {
+        let mut alternatives = LinearMap::new();
+        alternatives.insert(
+            variations.kind.into_str().to_string(),
+            variations
+                .alternatives
+                .into_iter()
+                .map(Alternative::Lines)
+                .collect(),
+        );
+        Self { alternatives }
+    }
+}
+
+impl Variations {
+    /// Counts a visit to this line and picks an alternative based on the visits before it.
+    /// Skips to the first line of that alternative, or past the whole block if nothing is picked.
+    /// Returns the line number after the block.
+    pub fn take(&self, bookmark: &mut Bookmark) -> usize {
+        let next_line = bookmark.line() + self.line_len();
+        // Shuffles depend on the bookmark's seed, but not on its rolls,
+        // so that every visit in a round agrees on the order.
+        let key = self.visit_key(bookmark.namespace(), bookmark.passage());
+        let seed = fnv1a(key.as_bytes()) ^ bookmark.rng.seed;
+        let visits = bookmark.visit(key);
+        match self.kind.pick(visits, self.alternatives.len(), seed) {
+            Some(index) => {
+                // Skip the variations line, then each earlier alternative and the break after it.
+                let mut skip_lines = 1;
+                for lines in &self.alternatives[..index] {
+                    skip_lines += line_len(lines) + 1;
+                }
+                bookmark.skip_lines(skip_lines);
+            }
+            None => bookmark.set_line(next_line),
+        }
+        next_line
+    }
+
+    /// Key identifying this line in `Bookmark::visits`, as `namespace:passage:hash`.
+    /// The hash is of the line IDs of the text in each alternative rather than of the line's position,
+    /// so that visits stay with the line when other lines of the passage are added or removed.
+    /// Identical variations in the same passage share their visits.
+    pub fn visit_key(&self, namespace: &str, passage: &str) -> String {
+        let mut ids = self.kind.into_str().to_string();
+        for lines in &self.alternatives {
+            ids.push('|');
+            for line in lines {
+                let mut texts: Vec<(&str, &str)> = match line {
+                    RawLine::Dialogue(dialogue) => dialogue
+                        .iter()
+                        .map(|(name, text)| (name.as_str(), text.as_str()))
+                        .collect(),
+                    RawLine::Text(text) => vec![("", text.as_str())],
+                    _ => continue,
+                };
+                texts.sort();
+                for (name, text) in texts {
+                    ids.push_str(&format!(
+                        " {}:{}",
+                        name,
+                        line_id(namespace, passage, text).0
+                    ));
+                }
+            }
+        }
+        format!("{}:{}:{:016x}", namespace, passage, fnv1a(ids.as_bytes()))
+    }
+
+    /// Like a branch, variations have one line for each alternative,
+    /// plus the length of all of their contained lines.
+    pub fn line_len(&self) -> usize {
+        let mut length = self.alternatives.len();
+        for lines in &self.alternatives {
+            length += line_len(lines);
+        }
+        length
+    }
+}
+
+/// Hashes `bytes` with 64 bit FNV-1a, which is stable across platforms and releases.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+/// Returns a permutation of `0..len` determined by `seed`.
+fn permutation(len: usize, seed: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..len).collect();
+    let mut state = seed;
+    for i in (1..len).rev() {
+        // SplitMix64.
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        order.swap(i, (z % (i as u64 + 1)) as usize);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_yml(text: &str) -> serde_yaml::Result<RawLine> {
+        serde_yaml::from_str(text)
+    }
+
+    #[test]
+    fn test_variations_from_yml() {
+        let variations = match from_yml(
+            r#"
+            cycle:
+              - Hello.
+              - - Hi.
+                - How are you?
+            "#,
+        )
+        .unwrap()
+        {
+            RawLine::Variations(variations) => variations,
+            line => panic!("Expected variations, got {:?}", line),
+        };
+        assert_eq!(variations.kind, VariationKind::Cycle);
+        assert_eq!(
+            variations.alternatives,
+            vec![
+                vec![RawLine::Text("Hello.".to_string())],
+                vec![
+                    RawLine::Text("Hi.".to_string()),
+                    RawLine::Text("How are you?".to_string())
+                ]
+            ]
+        );
+        assert_eq!(variations.line_len(), 5);
+
+        // Anything else is left for the other kinds of line.
+        for text in &["loop: [Hello.]", "once: []", "{ once: [A], cycle: [B] }"] {
+            assert!(!matches!(from_yml(text), Ok(RawLine::Variations(_))));
+        }
+    }
+
+    #[test]
+    fn test_variation_kind_pick() {
+        let picks = |kind: VariationKind| -> Vec<Option<usize>> {
+            (0..5).map(|visits| kind.pick(visits, 3, 0)).collect()
+        };
+        assert_eq!(
+            picks(VariationKind::Sequence),
+            vec![Some(0), Some(1), Some(2), Some(2), Some(2)]
+        );
+        assert_eq!(
+            picks(VariationKind::Cycle),
+            vec![Some(0), Some(1), Some(2), Some(0), Some(1)]
+        );
+        assert_eq!(
+            picks(VariationKind::Once),
+            vec![Some(0), Some(1), Some(2), None, None]
+        );
+
+        // Each round of a shuffle plays every alternative exactly once.
+        for round in 0..4 {
+            let mut picked: Vec<usize> = (0..3)
+                .map(|i| VariationKind::Shuffle.pick(round * 3 + i, 3, 42).unwrap())
+                .collect();
+            picked.sort_unstable();
+            assert_eq!(picked, vec![0, 1, 2]);
+        }
+    }
+}
@@ -2,43 +2,43 @@ use crate::error::{Error, Result};
 use std::{
     fmt,
     fs::{self, File},
-    io::{BufWriter, Read, Write},
+    io::{self, BufWriter, Read, Write},
     path::Path,
 };
 
 use crate::{FromMessagePack, FromYaml};
 use serde::Serialize;
 
+/// Constructs an `Error::Io` for a failed operation on `path`.
+fn io_error<P: AsRef<Path>>(path: P, e: io::Error) -> Error {
+    Error::Io {
+        path: path.as_ref().display().to_string(),
+        message: e.to_string(),
+    }
+}
+
 /// Trait to load a struct from a file or structured directory.
 pub trait LoadYaml: FromYaml {
     /// Reads a file from a given path into new string.
     fn load_string<P: AsRef<Path> + fmt::Debug>(path: P) -> Result<String> {
-        let mut f = match File::open(path) {
-            Ok(f) => f,
-            Err(e) => return Err(error!("Error opening file: {:?}", e)),
-        };
+        let mut f = File::open(&path).map_err(|e| io_error(&path, e))?;
         let mut s = String::new();
         match f.read_to_string(&mut s) {
             Ok(_) => Ok(s),
-            Err(e) => Err(error!("Error reading file to string: {:?}", e)),
+            Err(e) => Err(io_error(&path, e)),
         }
     }
 
     fn load_yml<P: AsRef<Path> + fmt::Debug>(path: P) -> Result<Self> {
-        match Self::load_string(path) {
-            Ok(source) => Self::from_yml(&source),
-            Err(e) => Err(error!("Error loading YAML: {}", e)),
-        }
+        let source = Self::load_string(&path)?;
+        Self::from_yml(&source).map_err(|e| e.in_file(&path.as_ref().to_string_lossy(), 0))
     }
 }
 
 /// Trait to load a struct from a file or structured directory.
 pub trait LoadMessagePack: FromMessagePack {
     fn load_bytes<P: AsRef<Path> + fmt::Debug>(path: P) -> Result<Vec<u8>> {
-        match fs::read(path) {
-            Ok(vec) => Ok(vec),
-            Err(e) => Err(error!("Error reading file to string: {:?}", e)),
-        }
+        fs::read(&path).map_err(|e| io_error(&path, e))
     }
     fn load_mp<P: AsRef<Path> + fmt::Debug>(path: P) -> Result<Self> {
         let bytes = Self::load_bytes(path)?;
@@ -51,10 +51,7 @@ fn is_yaml<P: AsRef<Path> + fmt::Debug>(path: P) -> bool {
         return true;
     }
     match path.as_ref().extension() {
-        Some(extension) => match extension.to_str() {
-            Some("yml") | Some("yaml") => true,
-            _ => false,
-        },
+        Some(extension) => matches!(extension.to_str(), Some("yml") | Some("yaml")),
         None => false,
     }
 }
@@ -71,10 +68,7 @@ pub trait Load: LoadMessagePack + LoadYaml {
 
 /// Private utility to construct a BufWriter for a file.
 fn bufwriter<P: AsRef<Path> + fmt::Debug>(path: P) -> Result<BufWriter<File>> {
-    let file = match File::create(path) {
-        Ok(f) => f,
-        Err(e) => return Err(error!("Failed to create file: {:?}", e)),
-    };
+    let file = File::create(&path).map_err(|e| io_error(&path, e))?;
     Ok(BufWriter::new(file))
 }
 
@@ -85,19 +79,18 @@ pub trait SaveMessagePack: Serialize {
             Ok(b) => b,
             Err(e) => return Err(error!("Failed to serialize object: {:?}", e)),
         };
-        match bufwriter(path)?.write(&buffer) {
-            Ok(_) => Ok(()),
-            Err(e) => return Err(error!("Error writing MessagePack buffer: {:?}", e)),
-        }
+        bufwriter(&path)?
+            .write_all(&buffer)
+            .map_err(|e| io_error(&path, e))
     }
 }
 
 /// Trait to save a serializable object to a YAML file.
 pub trait SaveYaml: Serialize {
     fn save_yml<P: AsRef<Path> + fmt::Debug>(&self, path: P) -> Result<()> {
-        match serde_yaml::to_writer(bufwriter(path)?, self) {
+        match serde_yaml::to_writer(bufwriter(&path)?, self) {
             Ok(_) => Ok(()),
-            Err(e) => return Err(error!("Failed to write to file: {:?}", e)),
+            Err(e) => Err(error!("Failed to write to file: {:?}", e)),
         }
     }
 }
@@ -17,6 +17,7 @@ pub trait CopyMerge: Sized + Clone {
 }
 
 /// Trait to move values from one object objects together. Used for maps.
+#[allow(dead_code)]
 pub trait MoveValues: Sized {
     fn move_values(other: &mut Self) -> Result<Self>;
 }
@@ -1,7 +1,7 @@
 use crate::error::{Error, Result};
+use crate::structs::yaml_error;
 use serde::de::DeserializeOwned;
 
-use serde_yaml;
 /// Trait for parsable expressions.
 pub trait FromYaml: DeserializeOwned + Default {
     fn from_yml(text: &str) -> Result<Self> {
@@ -15,7 +15,7 @@ pub trait FromYaml: DeserializeOwned + Default {
                 if format!("{:?}", e) == "EndOfStream" {
                     Ok(Self::default())
                 } else {
-                    Err(error!("Invalid YAML: {}", e))
+                    Err(yaml_error(e))
                 }
             }
         }
@@ -31,6 +31,7 @@ pub trait FromStr<'a> {
 
 /// Trait for parsable expressions.
 pub trait IntoStr {
+    #[allow(clippy::wrong_self_convention)]
     fn into_str(&self) -> &str;
 }
 
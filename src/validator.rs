@@ -1,22 +1,104 @@
 use crate::{
     error::{Error, Result},
+    locale::split_id,
     structs::{
-        AssignOperator, Branches, ChoiceTarget, Dialogue, Map, Params, Passage, Passages,
-        QualifiedName, RawChoice, RawChoices, RawCommand, RawLine, State, StateMod, Story,
-        StoryGetters,
+        AssignOperator, ChoiceTarget, Dialogue, Map, Params, QualifiedName, RawChoice, RawChoices,
+        RawCommand, RawLine, Span, State, StateMod, Story, StoryGetters, Variations,
     },
-    traits::FromStr,
-    Bookmark, Value,
+    traits::{FromStr, IntoStr},
+    Bookmark, Expr, Functions, Value,
 };
+use std::{cell::RefCell, fmt};
 
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single problem found in the story.
+/// `line` is the index of the line in the flattened passage, matching `Position::line`.
+/// `error` is the underlying error, so callers can match on its kind.
+/// `span` is the line's location in its source file, when the story was loaded from YAML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub namespace: String,
+    pub passage: String,
+    pub line: usize,
+    pub error: Error,
+    pub severity: Severity,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    /// Converts this diagnostic into an error in its passage, located at its span if it has one.
+    fn into_error(self) -> Error {
+        let error = Error::InPassage {
+            namespace: self.namespace,
+            passage: self.passage,
+            line: self.line + 1,
+            error: Box::new(self.error),
+        };
+        match self.span {
+            Some(span) => error.at(span),
+            None => error,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(span) = &self.span {
+            write!(f, "{}: ", span)?;
+        }
+        write!(
+            f,
+            "{}: Passage '{}:{}' Line {}: {}",
+            self.severity,
+            self.namespace,
+            self.passage,
+            self.line + 1,
+            self.error
+        )
+    }
+}
+
+/// Checks a story without running it.
+/// Expressions are checked for syntax, variables and function calls, but never evaluated,
+/// so host functions are not called and the result doesn't depend on the current state.
 pub struct Validator<'a> {
     story: &'a Story,
     bookmark: &'a mut Bookmark,
+    functions: Option<&'a Functions>,
+    /// Warnings found while validating the current line.
+    warnings: RefCell<Vec<Error>>,
 }
 
 impl<'a> Validator<'a> {
     pub fn new(story: &'a Story, bookmark: &'a mut Bookmark) -> Self {
-        Self { story, bookmark }
+        Self {
+            story,
+            bookmark,
+            functions: None,
+            warnings: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Checks calls against the host's `functions`, so that calling any other function is an error.
+    /// Without them, calls to functions that aren't built in are only reported as warnings.
+    pub fn with_functions(mut self, functions: &'a Functions) -> Self {
+        self.functions = Some(functions);
+        self
     }
 
     fn validate_text(&self, text: &str) -> Result<()> {
@@ -33,37 +115,90 @@ impl<'a> Validator<'a> {
     /// Validate that the dialogue contains valid text and configured characters only.
     fn validate_dialogue(&self, dialogue: &Map<String, String>) -> Result<()> {
         for (name, text) in dialogue {
-            self.validate_character(&name)?;
-            self.validate_text(&text)?;
+            self.validate_character(name)?;
+            self.validate_text(text)?;
         }
         Ok(())
     }
 
+    /// Validates the variables and function calls of an expression without evaluating it.
+    fn validate_expr(&self, expr: &Expr) -> Result<()> {
+        match expr {
+            Expr::Value(_) => Ok(()),
+            Expr::Variable(var) => self.validate_var(var).map(|_| ()),
+            Expr::Unary(_, expr) => self.validate_expr(expr),
+            Expr::Binary(lhs, _, rhs) => {
+                self.validate_expr(lhs)?;
+                self.validate_expr(rhs)
+            }
+            Expr::Call(function, args) => {
+                for arg in args {
+                    self.validate_expr(arg)?;
+                }
+                self.validate_call(function, args.len())
+            }
+            Expr::List(items) => items.iter().try_for_each(|item| self.validate_expr(item)),
+            Expr::Map(entries) => entries
+                .iter()
+                .try_for_each(|(_key, value)| self.validate_expr(value)),
+            Expr::Index(base, indices) => {
+                self.validate_expr(base)?;
+                indices
+                    .iter()
+                    .try_for_each(|index| self.validate_expr(index))
+            }
+        }
+    }
+
+    /// Validates a call to `function` with `arity` arguments.
+    /// Host functions take any number of arguments, and may be registered after validation
+    /// when no registry was given, in which case unknown functions are only warned about.
+    fn validate_call(&self, function: &str, arity: usize) -> Result<()> {
+        match self.functions {
+            Some(functions) if functions.contains(function) => Ok(()),
+            _ if Value::builtin_arity(function).is_some() => Value::check_arity(function, arity),
+            Some(_) => Err(Error::UnknownFunction {
+                function: function.to_string(),
+            }),
+            None => {
+                self.warnings.borrow_mut().push(Error::UnknownFunction {
+                    function: function.to_string(),
+                });
+                Ok(())
+            }
+        }
+    }
+
     /// Validates a conditional statement.
     fn validate_conditional(&self, expr: &str) -> Result<()> {
-        Value::from_conditional(expr, self.bookmark)?;
-        Ok(())
+        let expr = Expr::parse(Value::extract_conditional_expr(expr))?;
+        self.validate_expr(&expr)
     }
 
-    /// Validates conditional branches.
-    fn validate_branches(&self, branches: &Branches) -> Result<()> {
-        for (expression, lines) in &branches.exprs {
-            if expression != "else" {
-                self.validate_conditional(expression)?;
-            }
-            self.validate_passage(lines)?;
+    /// Validates that no command is configured with the name of the variation,
+    /// which would never be called since the line plays as a variation instead.
+    fn validate_variation_kind(&self, variations: &Variations) -> Result<()> {
+        let kind = variations.kind.into_str();
+        match self
+            .story
+            .params(&QualifiedName::from(self.bookmark.namespace(), kind))
+        {
+            Ok(_) => Err(Error::ReservedCommand {
+                command: kind.to_string(),
+                namespace: self.bookmark.namespace().to_string(),
+            }),
+            Err(_) => Ok(()),
         }
-        Ok(())
     }
 
     /// Validates parameters for a function call.
     fn validate_params(command_name: &str, params: &Params, config_params: &Params) -> Result<()> {
         for (param, _val) in params {
             if !config_params.contains_key(param) {
-                return Err(error!(
-                    "No such parameter '{}' for command '{}'",
-                    param, command_name
-                ));
+                return Err(Error::BadParameter {
+                    command: command_name.to_string(),
+                    param: param.to_string(),
+                });
             }
         }
         Ok(())
@@ -77,7 +212,7 @@ impl<'a> Validator<'a> {
     ) -> Result<()> {
         match self
             .story
-            .params(&QualifiedName::from(namespace, &command_name))?
+            .params(&QualifiedName::from(namespace, command_name))?
         {
             Some(config_params) => Self::validate_params(command_name, params, config_params),
             None => Ok(()),
@@ -87,70 +222,80 @@ impl<'a> Validator<'a> {
     /// Validates a list of commands in the Cmd object.
     fn validate_command(&self, cmd: &RawCommand) -> Result<()> {
         for (command, params) in cmd {
-            let split: Vec<&str> = command.split(".").collect();
+            let split: Vec<&str> = command.split('.').collect();
             let command_name = match split.as_slice() {
                 [character, command] => {
-                    self.validate_character(&character)?;
+                    self.validate_character(character)?;
                     format!("$character.{}", command)
                 }
                 [command] => command.to_string(),
-                _ => return Err(error!("Commands can only contain one '.' delimeter.")),
+                _ => {
+                    return Err(Error::UnknownCommand {
+                        command: command.to_string(),
+                        namespace: self.bookmark.namespace().to_string(),
+                    })
+                }
             };
 
-            self.validate_namespace_command(&self.bookmark.namespace(), &command_name, params)?;
+            self.validate_namespace_command(self.bookmark.namespace(), &command_name, params)?;
         }
         Ok(())
     }
 
     /// Validates a line of dialogue.
+    /// Lines holding other lines are checked by `collect_lines`, which visits each nested line.
     fn validate_line(&self, line: &RawLine) -> Result<()> {
         match &line {
             RawLine::Dialogue(dialogue) => self.validate_dialogue(dialogue),
-            RawLine::Branches(branches) => self.validate_branches(branches),
-            RawLine::Choices(choices) => self.validate_choices(choices),
             RawLine::Call(call) => self.validate_goto(&call.passage),
             RawLine::SetCommand(set_command) => self.validate_state(&set_command.set),
-            RawLine::Command(command) => self.validate_command(&command),
+            RawLine::Command(command) => self.validate_command(command),
             _ => Ok(()),
         }
     }
 
-    /// Validates that a line (either text or dialogue) has valid HTML and valid speakers.
-    fn validate_passage(&self, lines: &Passage) -> Result<()> {
-        for (i, line) in lines.iter().enumerate() {
-            if let Err(e) = self.validate_line(line) {
-                return Err(error!("Line {}: {}", i + 1, e));
-            }
-        }
-        Ok(())
-    }
-
     /// Validates an operator on a given value.
-    /// Any value supports assignment, but only Numbers can be added or subtracted.
+    /// Any value supports assignment of the same type, and integers and floats can be assigned to each other.
+    /// Numbers can be added or subtracted; an integer involved with a float becomes a float.
+    /// Anything can be added to or removed from a list,
+    /// and maps can have maps added or keys removed.
     fn validate_assign(v1: &Value, v2: &Value, op: AssignOperator) -> Result<()> {
-        match op {
-            AssignOperator::None => {
-                if v1.same_type(v2) {
-                    Ok(())
-                } else {
-                    Err(error!(
-                        "Operators require operands of the same type, not {:?} and {:?}",
-                        v1, v2
-                    ))
-                }
+        let expected = match (&op, v1, v2) {
+            (AssignOperator::None, _, _) if v1.same_type(v2) => return Ok(()),
+            // Integers and floats mix freely, becoming a float when one is involved.
+            (
+                AssignOperator::None,
+                Value::Int(_) | Value::Number(_),
+                Value::Int(_) | Value::Number(_),
+            ) => return Ok(()),
+            (AssignOperator::None, Value::Int(_), _) => "a number".to_string(),
+            (AssignOperator::None, _, _) => format!("a {}", v1.type_name()),
+            (_, Value::Int(_) | Value::Number(_), Value::Int(_) | Value::Number(_)) => {
+                return Ok(())
             }
-            AssignOperator::Add | AssignOperator::Sub => match (v1, v2) {
-                (Value::Number(_), Value::Number(_)) => Ok(()),
-                _ => Err(error!(
-                    "Comparators '+,-' can only be used on two numbers, not {:?} and {:?}.",
-                    v1, v2
-                )),
-            },
-        }
+            (_, Value::Int(_) | Value::Number(_), _) => "a number".to_string(),
+            (_, Value::List(_), _) => return Ok(()),
+            (AssignOperator::Add, Value::Map(_), Value::Map(_)) => return Ok(()),
+            (AssignOperator::Add, Value::Map(_), _) => "a map".to_string(),
+            (AssignOperator::Sub, Value::Map(_), Value::String(_) | Value::List(_)) => {
+                return Ok(())
+            }
+            (AssignOperator::Sub, Value::Map(_), _) => "a key or list of keys".to_string(),
+            _ => {
+                return Err(Error::TypeMismatch {
+                    expected: "a number, list or map".to_string(),
+                    found: v1.clone(),
+                })
+            }
+        };
+        Err(Error::TypeMismatch {
+            expected,
+            found: v2.clone(),
+        })
     }
     /// Validates a variable and returns a reference to it's value.
     fn validate_var(&self, var: &str) -> Result<&Value> {
-        let split: Vec<&str> = var.split(".").collect();
+        let split: Vec<&str> = var.split('.').collect();
         match split.as_slice() {
             [prefix, suffix] => {
                 // First check passage variables.
@@ -173,40 +318,59 @@ impl<'a> Validator<'a> {
                     return Ok(value);
                 }
 
-                Err(error!(
-                    "Variable '{}' did not match any character or passage variables.",
-                    var
-                ))
-            }
-            [var] => {
-                if let Ok(value) = self
-                    .story
-                    .value(&QualifiedName::from(self.bookmark.namespace(), &var))
-                {
-                    Ok(value)
-                } else {
-                    Err(error!("Variable '{}' was undefined.", var))
-                }
+                Err(Error::UndefinedVariable {
+                    var: var.to_string(),
+                    namespace: self.bookmark.namespace().to_string(),
+                })
             }
+            [var] => self
+                .story
+                .value(&QualifiedName::from(self.bookmark.namespace(), var)),
             _ => Err(error!("Variables can only contain one '.' delimeter.")),
         }
     }
 
-    /// Validates the state only contains configured keys.
+    /// Validates a value assigned to state, returning it if it's known without evaluating it.
+    /// Text that fails to validate is kept as a string, as it is when evaluated,
+    /// unless it was clearly meant to be an expression.
+    fn validate_value(&self, value: &Value) -> Result<Option<Value>> {
+        let text = match value {
+            Value::String(text) => text,
+            _ => return Ok(Some(value.clone())),
+        };
+        let warnings = self.warnings.borrow().len();
+        let checked = Expr::parse(text).and_then(|expr| {
+            self.validate_expr(&expr)?;
+            Ok(expr)
+        });
+        match checked {
+            Ok(Expr::Value(value)) => Ok(Some(value)),
+            Ok(_) => Ok(None),
+            Err(e) if Value::is_intended_expr(text) => Err(e),
+            Err(_) => {
+                self.warnings.borrow_mut().truncate(warnings);
+                Ok(Some(value.clone()))
+            }
+        }
+    }
+
+    /// Validates the state only contains configured keys,
+    /// and that values known without evaluating them have the right type.
     fn validate_state(&self, state: &State) -> Result<()> {
         for (key, value) in state {
-            let mut value = value.clone();
-            value.eval_as_expr(self.bookmark)?;
+            let value = self.validate_value(value)?;
             let smod = StateMod::from_str(key)?;
             let state_value = self.validate_var(smod.var)?;
-            Self::validate_assign(state_value, &value, smod.op)?;
+            if let Some(value) = value {
+                Self::validate_assign(state_value, &value, smod.op)?;
+            }
         }
         Ok(())
     }
 
     fn validate_goto(&self, passage_name: &str) -> Result<()> {
         self.story.passage(&QualifiedName::from(
-            &self.bookmark.namespace(),
+            self.bookmark.namespace(),
             passage_name,
         ))?;
         Ok(())
@@ -217,13 +381,13 @@ impl<'a> Validator<'a> {
         for (key, choice) in choices {
             match choice {
                 RawChoice::Target(ChoiceTarget::PassageName(passage_name)) => {
-                    self.validate_goto(&passage_name)?
+                    self.validate_goto(passage_name)?
                 }
                 RawChoice::Conditional(conditional) => {
                     for (_choice_name, passage_name_opt) in conditional {
                         self.validate_conditional(key)?;
                         if let ChoiceTarget::PassageName(passage_name) = passage_name_opt {
-                            self.validate_goto(&passage_name)?;
+                            self.validate_goto(passage_name)?;
                         }
                     }
                 }
@@ -233,29 +397,194 @@ impl<'a> Validator<'a> {
         Ok(())
     }
 
-    fn validate_passages(&mut self, passages: &'a Passages) -> Result<()> {
-        for (passage_name, passage) in passages {
-            self.bookmark.set_passage(passage_name.to_string());
-            if let Err(e) = self.validate_passage(passage) {
-                return Err(error!(
-                    "Passage '{}:{}' {}",
-                    self.bookmark.namespace(),
-                    passage_name,
-                    e
-                ));
+    /// Returns the explicit IDs of the text of `line`.
+    fn explicit_ids(line: &RawLine) -> Vec<&str> {
+        let texts: Vec<&str> = match line {
+            RawLine::Dialogue(dialogue) => dialogue.values().map(String::as_str).collect(),
+            RawLine::Text(text) => vec![text],
+            RawLine::Choices(choices) => choices
+                .as_ref()
+                .into_iter()
+                .flat_map(|(key, choice)| match choice {
+                    RawChoice::Target(_) => vec![key.as_str()],
+                    RawChoice::Conditional(conditional) => {
+                        conditional.keys().map(String::as_str).collect()
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        texts
+            .into_iter()
+            .filter_map(|text| split_id(text).0)
+            .collect()
+    }
+
+    /// Finds lines given an explicit ID that an earlier line already has,
+    /// which would otherwise share its translation.
+    /// Passages are checked in order of namespace and name, so the first use is the same each time.
+    fn duplicate_ids(&self) -> Vec<Diagnostic> {
+        let mut first_uses: Map<&str, (&str, &str)> = Map::new();
+        let mut diagnostics = Vec::new();
+        let mut namespaces: Vec<&String> = self.story.keys().collect();
+        namespaces.sort();
+        for namespace in namespaces {
+            let section = &self.story[namespace];
+            let mut passages: Vec<&String> = section.passages.keys().collect();
+            passages.sort();
+            for passage in passages {
+                let flat = match section.flattened(passage) {
+                    Some(flat) => flat,
+                    None => continue,
+                };
+                for (line_num, line) in flat.lines.iter().enumerate() {
+                    for id in Self::explicit_ids(line) {
+                        match first_uses.get(id) {
+                            Some((first_namespace, first_passage)) => {
+                                diagnostics.push(Diagnostic {
+                                    namespace: namespace.to_string(),
+                                    passage: passage.to_string(),
+                                    line: line_num,
+                                    error: Error::DuplicateLineId {
+                                        id: id.to_string(),
+                                        namespace: first_namespace.to_string(),
+                                        passage: first_passage.to_string(),
+                                    },
+                                    severity: Severity::Error,
+                                    span: section.span(passage, line_num).cloned(),
+                                })
+                            }
+                            None => {
+                                first_uses.insert(id, (namespace, passage));
+                            }
+                        }
+                    }
+                }
             }
         }
-        Ok(())
+        diagnostics
     }
 
     /// Validates an entire story for valid passage references, HTML, conditionals.
+    /// Returns the first error that `validate_all` finds, located at the same line.
     pub fn validate(&mut self) -> Result<()> {
+        match self
+            .validate_all()
+            .into_iter()
+            .find(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            Some(diagnostic) => Err(diagnostic.into_error()),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a diagnostic for the line at `line_num` of the current passage.
+    fn report(
+        &self,
+        diagnostics: &mut Vec<Diagnostic>,
+        line_num: usize,
+        error: Error,
+        severity: Severity,
+    ) {
+        diagnostics.push(Diagnostic {
+            namespace: self.bookmark.namespace().to_string(),
+            passage: self.bookmark.passage().to_string(),
+            line: line_num,
+            error,
+            severity,
+            span: None,
+        });
+    }
+
+    /// Records the error of `result` and any warnings found since the last check
+    /// for the line at `line_num`.
+    fn check(&self, diagnostics: &mut Vec<Diagnostic>, line_num: usize, result: Result<()>) {
+        if let Err(e) = result {
+            self.report(diagnostics, line_num, e, Severity::Error);
+        }
+        for warning in self.warnings.borrow_mut().drain(..) {
+            self.report(diagnostics, line_num, warning, Severity::Warning);
+        }
+    }
+
+    /// Validates each line in `lines`, collecting every error instead of stopping at the first.
+    /// Walks nested lines in the same order as `Runner::load_lines` so that
+    /// `line_num` tracks the flattened line index.
+    fn collect_lines(
+        &self,
+        lines: &[RawLine],
+        line_num: &mut usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for line in lines {
+            let current_line = *line_num;
+            *line_num += 1;
+            match line {
+                RawLine::Branches(branches) => {
+                    let mut is_first = true;
+                    for (expression, branch_lines) in &branches.exprs {
+                        if expression != "else" {
+                            let result = self.validate_conditional(expression);
+                            self.check(diagnostics, current_line, result);
+                        }
+                        if !is_first {
+                            *line_num += 1;
+                        }
+                        self.collect_lines(branch_lines, line_num, diagnostics);
+                        is_first = false;
+                    }
+                }
+                RawLine::Choices(choices) => {
+                    let result = self.validate_choices(choices);
+                    self.check(diagnostics, current_line, result);
+                    let mut is_first = true;
+                    for embedded in choices.embedded() {
+                        if !is_first {
+                            *line_num += 1;
+                        }
+                        self.collect_lines(embedded, line_num, diagnostics);
+                        is_first = false;
+                    }
+                }
+                RawLine::Variations(variations) => {
+                    let result = self.validate_variation_kind(variations);
+                    self.check(diagnostics, current_line, result);
+                    let mut is_first = true;
+                    for alternative in &variations.alternatives {
+                        if !is_first {
+                            *line_num += 1;
+                        }
+                        self.collect_lines(alternative, line_num, diagnostics);
+                        is_first = false;
+                    }
+                }
+                _ => {
+                    let result = self.validate_line(line);
+                    self.check(diagnostics, current_line, result);
+                }
+            }
+        }
+    }
+
+    /// Validates an entire story, returning every problem found.
+    /// Unlike `validate`, this does not stop at the first error.
+    pub fn validate_all(&mut self) -> Vec<Diagnostic> {
         let original_position = self.bookmark.position().clone();
+        let mut diagnostics = Vec::new();
         for (namespace, namespace_val) in self.story {
             self.bookmark.set_namespace(namespace.to_string());
-            self.validate_passages(&namespace_val.passages)?;
+            for (passage_name, passage) in &namespace_val.passages {
+                self.bookmark.set_passage(passage_name.to_string());
+                let first_new = diagnostics.len();
+                let mut line_num = 0;
+                self.collect_lines(passage, &mut line_num, &mut diagnostics);
+                for diagnostic in &mut diagnostics[first_new..] {
+                    diagnostic.span = namespace_val.span(passage_name, diagnostic.line).cloned();
+                }
+            }
         }
         self.bookmark.set_position(original_position);
-        Ok(())
+        diagnostics.extend(self.duplicate_ids());
+        diagnostics
     }
 }
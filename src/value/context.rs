@@ -0,0 +1,38 @@
+use super::{Exprs, Functions};
+use crate::Bookmark;
+
+/// Everything expressions are evaluated against.
+/// Variables and random rolls come from the bookmark, while compiled expressions
+/// belong to the story's sections and host functions to the runner,
+/// so neither is saved with a bookmark.
+#[derive(Debug, Clone, Copy)]
+pub struct Context<'a> {
+    pub bookmark: &'a Bookmark,
+    /// Expressions compiled ahead of time. Others are parsed when evaluated.
+    pub exprs: Option<&'a Exprs>,
+    /// Functions registered by the host. Without them, only built in functions can be called.
+    pub functions: Option<&'a Functions>,
+}
+
+impl<'a> Context<'a> {
+    /// Creates a context that parses each expression when it is evaluated.
+    pub fn new(bookmark: &'a Bookmark) -> Self {
+        Self {
+            bookmark,
+            exprs: None,
+            functions: None,
+        }
+    }
+
+    /// Evaluates expressions compiled in `exprs` without parsing them again.
+    pub fn with_exprs(mut self, exprs: &'a Exprs) -> Self {
+        self.exprs = Some(exprs);
+        self
+    }
+
+    /// Lets expressions call the host's `functions`.
+    pub fn with_functions(mut self, functions: &'a Functions) -> Self {
+        self.functions = Some(functions);
+        self
+    }
+}
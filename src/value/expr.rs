@@ -0,0 +1,333 @@
+use super::{Context, Value};
+use crate::{
+    error::{Error, Result},
+    structs::{Config, Operator, Passages, RawChoice, RawLine, State},
+    vars::interpolated_exprs,
+    Map,
+};
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+/// An expression parsed into a tree, so that it can be evaluated many times without parsing it again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Value(Value),
+    Variable(String),
+    Unary(Operator, Box<Expr>),
+    Binary(Box<Expr>, Operator, Box<Expr>),
+    Call(String, Vec<Expr>),
+    List(Vec<Expr>),
+    Map(Vec<(String, Expr)>),
+    /// A list or map followed by one or more indices.
+    Index(Box<Expr>, Vec<Expr>),
+}
+
+impl Expr {
+    /// Evaluates this expression. Uses the context's bookmark for $variable lookup.
+    pub fn eval(&self, context: &Context) -> Result<Value> {
+        let lenient = context.bookmark.lenient;
+        match self {
+            Self::Value(value) => Ok(value.clone()),
+            Self::Variable(var) => Value::from_var(var, context.bookmark),
+            Self::Unary(op, expr) => Self::eval_unary(*op, expr.eval(context)?, lenient),
+            Self::Binary(lhs, op, rhs) => {
+                let lhs = lhs.eval(context)?;
+                // Skip the right side once the left decides the result, so it can act as a guard.
+                match (op, &lhs) {
+                    (Operator::And, Value::Bool(false)) | (Operator::Or, Value::Bool(true)) => {
+                        Ok(lhs)
+                    }
+                    _ => Self::eval_binary(lhs, *op, rhs.eval(context)?, lenient),
+                }
+            }
+            Self::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(context))
+                    .collect::<Result<Vec<Value>>>()?;
+                Value::call_function(function, &args, context)
+            }
+            Self::List(items) => Ok(Value::List(
+                items
+                    .iter()
+                    .map(|item| item.eval(context))
+                    .collect::<Result<Vec<Value>>>()?,
+            )),
+            Self::Map(entries) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    map.insert(key.clone(), value.eval(context)?);
+                }
+                Ok(Value::Map(map))
+            }
+            Self::Index(base, indices) => {
+                let mut value = base.eval(context)?;
+                for index in indices {
+                    let index = index.eval(context)?;
+                    value = value.index(&index)?.clone();
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    /// Evaluates a binary expression.
+    /// Unless `lenient`, unsupported operand types and division by zero are errors.
+    fn eval_binary(lhs: Value, op: Operator, rhs: Value, lenient: bool) -> Result<Value> {
+        let result = match op {
+            Operator::Add if lenient => lhs + rhs,
+            Operator::Add => lhs.checked_add(rhs)?,
+            Operator::Sub if lenient => lhs - rhs,
+            Operator::Sub => lhs.checked_sub(rhs)?,
+            Operator::Mul if lenient => lhs * rhs,
+            Operator::Mul => lhs.checked_mul(rhs)?,
+            Operator::Div if lenient => lhs / rhs,
+            Operator::Div => lhs.checked_div(rhs)?,
+            Operator::And if lenient => lhs & rhs,
+            Operator::And => lhs.checked_and(rhs)?,
+            Operator::Or if lenient => lhs | rhs,
+            Operator::Or => lhs.checked_or(rhs)?,
+            Operator::Lt => Value::Bool(lhs < rhs),
+            Operator::Leq => Value::Bool(lhs <= rhs),
+            Operator::Gt => Value::Bool(lhs > rhs),
+            Operator::Geq => Value::Bool(lhs >= rhs),
+            Operator::Eq => Value::Bool(lhs == rhs),
+            Operator::Neq => Value::Bool(lhs != rhs),
+            Operator::In => Value::Bool(rhs.contains(&lhs)),
+            Operator::Contains => Value::Bool(lhs.contains(&rhs)),
+            Operator::Not => return Err(error!("Invalid binary expression.")),
+        };
+        Ok(result)
+    }
+
+    /// Evaluates a unary expression.
+    fn eval_unary(op: Operator, value: Value, lenient: bool) -> Result<Value> {
+        let result = match op {
+            Operator::Not if lenient => !value,
+            Operator::Not => value.checked_not()?,
+            Operator::Add => value,
+            Operator::Sub if lenient => -value,
+            Operator::Sub => value.checked_neg()?,
+            _ => return Err(error!("Invalid unary expression.")),
+        };
+        Ok(result)
+    }
+}
+
+/// Expressions compiled from a section, keyed by their source text.
+/// Text that failed to compile keeps its error, so that it isn't parsed again either.
+/// Cloning only copies a reference to the compiled expressions.
+#[derive(Clone, Default, PartialEq)]
+pub struct Exprs(Arc<Map<String, Result<Expr>>>);
+
+impl Exprs {
+    /// Compiles every expression used by the lines and set commands of a section.
+    pub fn compile(config: &Config, passages: &Passages) -> Self {
+        let mut compiler = ExprCompiler::default();
+        for set_command in config.on_enter.iter().chain(config.on_exit.iter()) {
+            compiler.add_state(&set_command.set);
+        }
+        for params in config.commands.values().flatten() {
+            compiler.add_values(params.values());
+        }
+        for lines in passages.values() {
+            compiler.add_lines(lines);
+        }
+        Self(Arc::new(compiler.exprs))
+    }
+
+    /// Gets the compiled expression for `text`, or None if it wasn't compiled ahead of time.
+    #[inline]
+    pub fn get(&self, text: &str) -> Option<&Result<Expr>> {
+        self.0.get(text)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Adds all of `other`'s expressions to these.
+    pub fn extend(&mut self, other: &Self) {
+        let exprs = Arc::make_mut(&mut self.0);
+        for (text, expr) in other.0.iter() {
+            exprs.insert(text.clone(), expr.clone());
+        }
+    }
+}
+
+/// Lists the number of expressions, since there may be a great many.
+impl fmt::Debug for Exprs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Exprs({})", self.len())
+    }
+}
+
+/// Walks a section's lines to compile each expression once.
+#[derive(Default)]
+struct ExprCompiler {
+    exprs: Map<String, Result<Expr>>,
+}
+
+impl ExprCompiler {
+    fn add(&mut self, text: &str) {
+        if !self.exprs.contains_key(text) {
+            self.exprs.insert(text.to_string(), Expr::parse(text));
+        }
+    }
+
+    fn add_values<'a>(&mut self, values: impl Iterator<Item = &'a Value>) {
+        for value in values {
+            if let Value::String(text) = value {
+                self.add(text);
+            }
+        }
+    }
+
+    fn add_state(&mut self, state: &State) {
+        self.add_values(state.values());
+    }
+
+    fn add_conditional(&mut self, expr: &str) {
+        if expr != "else" {
+            self.add(Value::extract_conditional_expr(expr));
+        }
+    }
+
+    fn add_text(&mut self, text: &str) {
+        for expr in interpolated_exprs(text) {
+            self.add(expr);
+        }
+    }
+
+    fn add_lines(&mut self, lines: &[RawLine]) {
+        for line in lines {
+            match line {
+                RawLine::Branches(branches) => {
+                    for (expr, lines) in &branches.exprs {
+                        self.add_conditional(expr);
+                        self.add_lines(lines);
+                    }
+                }
+                RawLine::Variations(variations) => {
+                    for lines in &variations.alternatives {
+                        self.add_lines(lines);
+                    }
+                }
+                RawLine::Choices(choices) => {
+                    for (key, choice) in choices.as_ref() {
+                        if let RawChoice::Conditional(_) = choice {
+                            self.add_conditional(key);
+                        }
+                    }
+                    for lines in choices.embedded() {
+                        self.add_lines(lines);
+                    }
+                }
+                RawLine::SetCommand(set_command) => self.add_state(&set_command.set),
+                RawLine::Command(command) => {
+                    for params in command.values() {
+                        self.add_values(params.values());
+                    }
+                }
+                RawLine::PositionalCommand(command) => {
+                    for params in command.values() {
+                        self.add_values(params.iter());
+                    }
+                }
+                RawLine::Text(text) => self.add_text(text),
+                RawLine::Dialogue(dialogue) => {
+                    for text in dialogue.values() {
+                        self.add_text(text);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, FromYaml};
+
+    #[test]
+    fn test_compile_section() {
+        let passages = Passages::from_yml(
+            r#"
+            Start:
+              - if $gold > 1:
+                  - "You have {$gold} gold."
+                elif $gold == 1:
+                  - Alice: "Just {$gold + 0} coin. {{not an expression}}"
+              - set:
+                  $gold +: $gold * 2
+                  $name: Bob
+              - choices:
+                  if $gold > 10:
+                    Buy: Shop
+                  Leave:
+                    - Wave: [$name]
+              - once:
+                  - "{len($name)}"
+            "#,
+        )
+        .unwrap();
+        let exprs = Exprs::compile(&Config::default(), &passages);
+
+        let mut texts: Vec<&String> = exprs.0.keys().collect();
+        texts.sort();
+        assert_eq!(
+            texts,
+            vec![
+                "$gold",
+                "$gold * 2",
+                "$gold + 0",
+                "$gold == 1",
+                "$gold > 1",
+                "$gold > 10",
+                "$name",
+                "Bob",
+                "len($name)",
+            ]
+        );
+        assert_eq!(
+            exprs.get("$gold > 1"),
+            Some(&Ok(Expr::Binary(
+                Box::new(Expr::Variable("gold".to_string())),
+                Operator::Gt,
+                Box::new(Expr::Value(Value::Int(1)))
+            )))
+        );
+        assert!(exprs.get("len(").is_none());
+        assert_eq!(exprs, Exprs::compile(&Config::default(), &passages));
+        assert_ne!(exprs, Exprs::default());
+    }
+
+    #[test]
+    fn test_compiled_errors() {
+        let passages = Passages::from_yml(
+            r#"
+            Start:
+              - set:
+                  $text: just some words
+                  $broken: $gold +
+            "#,
+        )
+        .unwrap();
+        let exprs = Exprs::compile(&Config::default(), &passages);
+        assert!(matches!(
+            exprs.get("just some words"),
+            Some(Err(Error::ExpressionSyntax { .. }))
+        ));
+        assert!(matches!(
+            exprs.get("$gold +"),
+            Some(Err(Error::ExpressionSyntax { .. }))
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+use super::{Context, Value};
+use crate::{Error, Map, Result};
+use std::{fmt, sync::Arc};
+
+/// A function registered by the host, called with the evaluated arguments of an expression.
+pub type Function = Arc<dyn Fn(&[Value]) -> Result<Value> + Send + Sync>;
+
+/// Built in functions and the number of arguments each takes.
+const BUILTINS: &[(&str, usize)] = &[("random", 2), ("chance", 1), ("len", 1)];
+
+/// Functions registered by the host that expressions can call by name.
+/// These live on the runner rather than the bookmark, so they are never saved.
+#[derive(Clone, Default)]
+pub struct Functions(Map<String, Function>);
+
+impl Functions {
+    /// Registers `function` under `name`, replacing any function with the same name.
+    pub fn insert<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.0.insert(name.to_string(), Arc::new(function));
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Function> {
+        self.0.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.0.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+}
+
+impl fmt::Debug for Functions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Functions can't be compared, so registries are equal when they have the same names.
+impl PartialEq for Functions {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.keys().all(|name| other.contains(name))
+    }
+}
+
+impl Value {
+    /// Calls `function` with already evaluated `args`.
+    /// Functions registered by the host are looked up before the built in ones.
+    /// Random functions roll the bookmark's generator.
+    pub(super) fn call_function(
+        function: &str,
+        args: &[Value],
+        context: &Context,
+    ) -> Result<Value> {
+        let bookmark = context.bookmark;
+        if let Some(host_function) = context
+            .functions
+            .and_then(|functions| functions.get(function))
+        {
+            return host_function(args);
+        }
+        match function {
+            // A whole number from `min` to `max`, inclusive.
+            "random" => {
+                Self::check_args(function, args)?;
+                let min = args[0].as_number()?.ceil() as i64;
+                let max = args[1].as_number()?.floor() as i64;
+                Ok(Value::Int(bookmark.rng.range(min, max)))
+            }
+            // True with probability `p`.
+            "chance" => {
+                Self::check_args(function, args)?;
+                Ok(Value::Bool(bookmark.rng.chance(args[0].as_number()?)))
+            }
+            // Number of items in a list or map, or characters in a string.
+            "len" => {
+                Self::check_args(function, args)?;
+                match args[0].len() {
+                    Some(len) => Ok(Value::Int(len as i64)),
+                    None => Err(Error::TypeMismatch {
+                        expected: "a list, map or string".to_string(),
+                        found: args[0].clone(),
+                    }),
+                }
+            }
+            _ => Err(Error::UnknownFunction {
+                function: function.to_string(),
+            }),
+        }
+    }
+
+    /// Number of arguments the built in `function` takes, or None if there is no such function.
+    pub(crate) fn builtin_arity(function: &str) -> Option<usize> {
+        BUILTINS
+            .iter()
+            .find(|(name, _)| *name == function)
+            .map(|(_, arity)| *arity)
+    }
+
+    /// Checks that the built in `function` was called with the right number of `args`.
+    fn check_args(function: &str, args: &[Value]) -> Result<()> {
+        Self::check_arity(function, args.len())
+    }
+
+    /// Checks that the built in `function` was called with `found` arguments.
+    pub(crate) fn check_arity(function: &str, found: usize) -> Result<()> {
+        let expected = Self::builtin_arity(function).unwrap_or(found);
+        if found == expected {
+            Ok(())
+        } else {
+            Err(Error::WrongArgumentCount {
+                function: function.to_string(),
+                expected,
+                found,
+            })
+        }
+    }
+
+    fn as_number(&self) -> Result<f64> {
+        self.as_f64().ok_or_else(|| Error::TypeMismatch {
+            expected: "a number".to_string(),
+            found: self.clone(),
+        })
+    }
+}
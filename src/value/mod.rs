@@ -1,27 +1,99 @@
+mod context;
+mod expr;
+mod functions;
 mod operators;
 mod parser;
 
+pub use context::Context;
+pub use expr::{Expr, Exprs};
+pub use functions::{Function, Functions};
+
 use crate::{
     error::{Error, Result},
+    structs::yaml_error,
     Bookmark,
 };
 use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::BTreeMap};
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Value {
     String(String),
+    /// Whole numbers. Arithmetic between integers stays integral, and division truncates.
+    Int(i64),
+    /// Floating point numbers. Any arithmetic involving a float produces a float.
     Number(f64),
     Bool(bool),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+/// Integers and floats compare by their numeric value.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::String(s1), Self::String(s2)) => s1 == s2,
+            (Self::Int(n1), Self::Int(n2)) => n1 == n2,
+            (Self::Bool(b1), Self::Bool(b2)) => b1 == b2,
+            (Self::List(l1), Self::List(l2)) => l1 == l2,
+            (Self::Map(m1), Self::Map(m2)) => m1 == m2,
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(n1), Some(n2)) => n1 == n2,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Values of different types are unordered, except for integers and floats.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::String(s1), Self::String(s2)) => s1.partial_cmp(s2),
+            (Self::Int(n1), Self::Int(n2)) => n1.partial_cmp(n2),
+            (Self::Bool(b1), Self::Bool(b2)) => b1.partial_cmp(b2),
+            (Self::List(l1), Self::List(l2)) => l1.partial_cmp(l2),
+            (Self::Map(m1), Self::Map(m2)) => m1.partial_cmp(m2),
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(n1), Some(n2)) => n1.partial_cmp(&n2),
+                _ => None,
+            },
+        }
+    }
 }
 
 impl Value {
     pub fn same_type(&self, rhs: &Self) -> bool {
-        match (self, rhs) {
-            (Value::Bool(_), Value::Bool(_)) => true,
-            (Value::Number(_), Value::Number(_)) => true,
-            (Value::String(_), Value::String(_)) => true,
-            _ => false,
+        matches!(
+            (self, rhs),
+            (Value::Bool(_), Value::Bool(_))
+                | (Value::Int(_), Value::Int(_))
+                | (Value::Number(_), Value::Number(_))
+                | (Value::String(_), Value::String(_))
+                | (Value::List(_), Value::List(_))
+                | (Value::Map(_), Value::Map(_))
+        )
+    }
+
+    /// Name of this value's type, for use in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Int(_) => "integer",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    /// Returns this value as a float if it is an integer or a float.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(n) => Some(*n as f64),
+            Value::Number(n) => Some(*n),
+            _ => None,
         }
     }
 
@@ -29,16 +101,37 @@ impl Value {
         match yaml_value {
             serde_yaml::Value::Bool(b) => Ok(Value::Bool(b)),
             serde_yaml::Value::String(s) => Ok(Value::String(s)),
-            serde_yaml::Value::Number(n) => Ok(Value::Number(n.as_f64().unwrap())),
+            serde_yaml::Value::Number(n) => match n.as_i64() {
+                Some(n) => Ok(Value::Int(n)),
+                None => Ok(Value::Number(n.as_f64().unwrap())),
+            },
+            serde_yaml::Value::Sequence(items) => Ok(Value::List(
+                items
+                    .into_iter()
+                    .map(Self::from_yml_value)
+                    .collect::<Result<_>>()?,
+            )),
+            serde_yaml::Value::Mapping(mapping) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in mapping {
+                    match key {
+                        serde_yaml::Value::String(key) => {
+                            map.insert(key, Self::from_yml_value(value)?);
+                        }
+                        _ => return Err(error!("Map keys must be strings, found {:?}", key)),
+                    }
+                }
+                Ok(Value::Map(map))
+            }
             _ => Err(error!("Cannot create value from {:?}", yaml_value)),
         }
     }
 
     /// Parses a single piece of text into a value;
     pub fn from_yml(text: &str) -> Result<Self> {
-        match serde_yaml::from_str(&text) {
+        match serde_yaml::from_str(text) {
             Ok(r) => Self::from_yml_value(r),
-            Err(e) => Err(error!("{}", e)),
+            Err(e) => Err(yaml_error(e)),
         }
     }
 
@@ -47,27 +140,67 @@ impl Value {
         Ok(bookmark.value(var)?.clone())
     }
 
+    /// Number of items in a list or map, or characters in a string.
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Self::String(s) => Some(s.chars().count()),
+            Self::List(items) => Some(items.len()),
+            Self::Map(map) => Some(map.len()),
+            _ => None,
+        }
+    }
+
+    /// Whether a list, map or string has no items.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Returns true if `item` is an item of this list, a key of this map or a substring of this string.
+    pub fn contains(&self, item: &Value) -> bool {
+        match (self, item) {
+            (Self::List(items), _) => items.contains(item),
+            (Self::Map(map), Self::String(key)) => map.contains_key(key),
+            (Self::String(s), Self::String(sub)) => s.contains(sub.as_str()),
+            _ => false,
+        }
+    }
+
+    /// Gets the item at `index` of a list or the value under key `index` of a map.
+    pub fn index(&self, index: &Value) -> Result<&Value> {
+        let item = match (self, index) {
+            (Self::List(items), Self::Int(n)) if *n >= 0 => items.get(*n as usize),
+            (Self::List(items), Self::Number(n)) if *n >= 0. && n.fract() == 0. => {
+                items.get(*n as usize)
+            }
+            (Self::Map(map), Self::String(key)) => map.get(key),
+            _ => None,
+        };
+        item.ok_or_else(|| Error::BadIndex {
+            value: self.clone(),
+            index: index.clone(),
+        })
+    }
+
     /// Gets truthy value.
     pub fn to_bool(self) -> Result<bool> {
         match self {
             Self::Bool(b) => Ok(b),
-            _ => Err(error!("'{}' was not a boolean expression.", self)),
+            _ => Err(Error::TypeMismatch {
+                expected: "a boolean expression".to_string(),
+                found: self,
+            }),
         }
     }
 
-    fn extract_conditional_expr(expr: &str) -> &str {
+    pub(crate) fn extract_conditional_expr(expr: &str) -> &str {
         static IF_PREFIX: &str = "if ";
         static ELIF_PREFIX: &str = "elif ";
-        if expr.starts_with(IF_PREFIX) {
-            &expr[IF_PREFIX.len()..]
-        } else if expr.starts_with(ELIF_PREFIX) {
-            &expr[ELIF_PREFIX.len()..]
-        } else {
-            ""
-        }
+        expr.strip_prefix(IF_PREFIX)
+            .or_else(|| expr.strip_prefix(ELIF_PREFIX))
+            .unwrap_or_default()
     }
 
-    pub fn from_conditional(expr: &str, bookmark: &Bookmark) -> Result<bool> {
-        Self::from_expr(Self::extract_conditional_expr(expr), bookmark)?.to_bool()
+    pub fn from_conditional(expr: &str, context: &Context) -> Result<bool> {
+        Self::from_expr(Self::extract_conditional_expr(expr), context)?.to_bool()
     }
 }
@@ -1,4 +1,5 @@
 use super::Value;
+use crate::error::{Error, Result};
 use std::fmt;
 use std::ops::{
     Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, Div, DivAssign, Mul, MulAssign, Neg,
@@ -9,26 +10,217 @@ impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::String(v) => write!(f, "{}", v),
+            Self::Int(v) => write!(f, "{}", v),
             Self::Number(v) => write!(f, "{}", v),
             Self::Bool(v) => write!(f, "{}", v),
+            Self::List(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Self::Map(map) => {
+                let entries: Vec<String> = map
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
         }
     }
 }
 
+/// Applies an arithmetic operator to two numbers.
+/// Integers use `int_op`, falling back to floats if it overflows.
+/// If either side is a float, both are promoted and use `float_op`.
+/// Returns None if either side is not a number.
+fn arithmetic(
+    lhs: &Value,
+    rhs: &Value,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Option<Value> {
+    match (lhs, rhs) {
+        (Value::Int(n1), Value::Int(n2)) => Some(match int_op(*n1, *n2) {
+            Some(n) => Value::Int(n),
+            None => Value::Number(float_op(*n1 as f64, *n2 as f64)),
+        }),
+        _ => Some(Value::Number(float_op(lhs.as_f64()?, rhs.as_f64()?))),
+    }
+}
+
+fn invalid_operation(operator: &str, lhs: &Value, rhs: Value) -> Error {
+    Error::InvalidOperation {
+        operator: operator.to_string(),
+        lhs: lhs.clone(),
+        rhs,
+    }
+}
+
+/// Defines a by-value version of a checked assignment operator.
+macro_rules! checked_binary {
+    ($(#[$doc:meta])* $name:ident, $assign:ident) => {
+        $(#[$doc])*
+        pub fn $name(mut self, rhs: Self) -> Result<Self> {
+            self.$assign(rhs)?;
+            Ok(self)
+        }
+    };
+}
+
+/// Checked operators, which return an error for unsupported operand types
+/// and leave the value unchanged.
+impl Value {
+    /// Adds numbers or concatenates strings.
+    /// Adding to a list appends an item, or every item of another list.
+    /// Adding a map to a map inserts all of its entries.
+    pub fn checked_add_assign(&mut self, rhs: Self) -> Result<()> {
+        match (&mut *self, rhs) {
+            (Value::String(s1), Value::String(s2)) => s1.push_str(&s2),
+            (Value::List(items), Value::List(mut other)) => items.append(&mut other),
+            (Value::List(items), item) => items.push(item),
+            (Value::Map(map), Value::Map(mut other)) => map.append(&mut other),
+            (lhs, rhs) => {
+                let result = arithmetic(lhs, &rhs, i64::checked_add, |n1, n2| n1 + n2)
+                    .ok_or_else(|| invalid_operation("+", lhs, rhs))?;
+                *self = result;
+            }
+        }
+        Ok(())
+    }
+
+    /// Subtracts numbers.
+    /// Subtracting from a list removes every copy of an item, or of each item of another list.
+    /// Subtracting a key, or a list of keys, from a map removes those entries.
+    pub fn checked_sub_assign(&mut self, rhs: Self) -> Result<()> {
+        match (&mut *self, rhs) {
+            (Value::List(items), Value::List(other)) => items.retain(|item| !other.contains(item)),
+            (Value::List(items), item) => items.retain(|existing| *existing != item),
+            (Value::Map(map), Value::String(key)) => {
+                map.remove(&key);
+            }
+            (Value::Map(map), Value::List(keys))
+                if keys.iter().all(|key| matches!(key, Value::String(_))) =>
+            {
+                for key in keys {
+                    if let Value::String(key) = key {
+                        map.remove(&key);
+                    }
+                }
+            }
+            (lhs, rhs) => {
+                let result = arithmetic(lhs, &rhs, i64::checked_sub, |n1, n2| n1 - n2)
+                    .ok_or_else(|| invalid_operation("-", lhs, rhs))?;
+                *self = result;
+            }
+        }
+        Ok(())
+    }
+
+    /// Multiplies numbers.
+    pub fn checked_mul_assign(&mut self, rhs: Self) -> Result<()> {
+        *self = arithmetic(self, &rhs, i64::checked_mul, |n1, n2| n1 * n2)
+            .ok_or_else(|| invalid_operation("*", self, rhs))?;
+        Ok(())
+    }
+
+    /// Divides numbers. Division between integers truncates towards zero.
+    pub fn checked_div_assign(&mut self, rhs: Self) -> Result<()> {
+        if self.as_f64().is_some() && rhs.as_f64() == Some(0.) {
+            return Err(Error::DivisionByZero);
+        }
+        *self = arithmetic(self, &rhs, i64::checked_div, |n1, n2| n1 / n2)
+            .ok_or_else(|| invalid_operation("/", self, rhs))?;
+        Ok(())
+    }
+
+    /// Logical and of booleans.
+    pub fn checked_and_assign(&mut self, rhs: Self) -> Result<()> {
+        match (&mut *self, rhs) {
+            (Value::Bool(b1), Value::Bool(b2)) => *b1 &= b2,
+            (lhs, rhs) => return Err(invalid_operation("and", lhs, rhs)),
+        }
+        Ok(())
+    }
+
+    /// Logical or of booleans.
+    pub fn checked_or_assign(&mut self, rhs: Self) -> Result<()> {
+        match (&mut *self, rhs) {
+            (Value::Bool(b1), Value::Bool(b2)) => *b1 |= b2,
+            (lhs, rhs) => return Err(invalid_operation("or", lhs, rhs)),
+        }
+        Ok(())
+    }
+
+    checked_binary!(
+        /// See `checked_add_assign`.
+        checked_add,
+        checked_add_assign
+    );
+    checked_binary!(
+        /// See `checked_sub_assign`.
+        checked_sub,
+        checked_sub_assign
+    );
+    checked_binary!(
+        /// See `checked_mul_assign`.
+        checked_mul,
+        checked_mul_assign
+    );
+    checked_binary!(
+        /// See `checked_div_assign`.
+        checked_div,
+        checked_div_assign
+    );
+    checked_binary!(
+        /// See `checked_and_assign`.
+        checked_and,
+        checked_and_assign
+    );
+    checked_binary!(
+        /// See `checked_or_assign`.
+        checked_or,
+        checked_or_assign
+    );
+
+    /// Logical not of a boolean.
+    pub fn checked_not(self) -> Result<Self> {
+        match self {
+            Value::Bool(b) => Ok(Self::Bool(!b)),
+            _ => Err(Error::TypeMismatch {
+                expected: "a boolean".to_string(),
+                found: self,
+            }),
+        }
+    }
+
+    /// Negates a number.
+    pub fn checked_neg(self) -> Result<Self> {
+        match self {
+            Value::Int(n) => Ok(match n.checked_neg() {
+                Some(n) => Self::Int(n),
+                None => Self::Number(-(n as f64)),
+            }),
+            Value::Number(n) => Ok(Self::Number(-n)),
+            _ => Err(Error::TypeMismatch {
+                expected: "a number".to_string(),
+                found: self,
+            }),
+        }
+    }
+}
+
+// The standard operators are lenient, leaving the value unchanged for unsupported operand types
+// and resulting in zero when dividing by zero.
+
 impl AddAssign<Self> for Value {
     fn add_assign(&mut self, rhs: Self) {
-        *self = match (&self, rhs) {
-            (Value::Number(n1), Value::Number(n2)) => Self::Number(n1 + n2),
-            (Value::String(s1), Value::String(ref s2)) => Self::String(format!("{}{}", s1, s2)),
-            _ => return,
-        };
+        let _ = self.checked_add_assign(rhs);
     }
 }
 
 impl Add<Self> for Value {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
-        let mut result = self.clone();
+        let mut result = self;
         result += rhs;
         result
     }
@@ -36,17 +228,14 @@ impl Add<Self> for Value {
 
 impl SubAssign<Self> for Value {
     fn sub_assign(&mut self, rhs: Self) {
-        *self = match (&self, rhs) {
-            (Value::Number(n1), Value::Number(n2)) => Self::Number(n1 - n2),
-            _ => return,
-        };
+        let _ = self.checked_sub_assign(rhs);
     }
 }
 
 impl Sub<Self> for Value {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self::Output {
-        let mut result = self.clone();
+        let mut result = self;
         result -= rhs;
         result
     }
@@ -54,26 +243,20 @@ impl Sub<Self> for Value {
 
 impl MulAssign<Self> for Value {
     fn mul_assign(&mut self, rhs: Self) {
-        *self = match (&self, rhs) {
-            (Value::Number(n1), Value::Number(n2)) => Self::Number(n1 * n2),
-            _ => return,
-        }
+        let _ = self.checked_mul_assign(rhs);
     }
 }
 
 impl MulAssign<f64> for Value {
     fn mul_assign(&mut self, rhs: f64) {
-        match &self {
-            Value::Number(n1) => *self = Self::Number(n1 * rhs),
-            _ => (),
-        }
+        *self *= Self::Number(rhs);
     }
 }
 
 impl Mul<Self> for Value {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self::Output {
-        let mut result = self.clone();
+        let mut result = self;
         result *= rhs;
         result
     }
@@ -81,40 +264,26 @@ impl Mul<Self> for Value {
 
 impl DivAssign<Self> for Value {
     fn div_assign(&mut self, rhs: Self) {
-        *self = match (&self, rhs) {
-            (Value::Number(n1), Value::Number(n2)) => {
-                if n2 == 0. {
-                    Self::Number(0.)
-                } else {
-                    Self::Number(n1 / n2)
-                }
-            }
-            _ => return,
+        let zero = match (&*self, &rhs) {
+            (Value::Int(_), Value::Int(_)) => Self::Int(0),
+            _ => Self::Number(0.),
+        };
+        if let Err(Error::DivisionByZero) = self.checked_div_assign(rhs) {
+            *self = zero;
         }
     }
 }
 
 impl DivAssign<f64> for Value {
     fn div_assign(&mut self, rhs: f64) {
-        match &self {
-            Value::Number(n1) => {
-                *self = {
-                    if rhs == 0. {
-                        Self::Number(0.)
-                    } else {
-                        Self::Number(n1 / rhs)
-                    }
-                }
-            }
-            _ => (),
-        }
+        *self /= Self::Number(rhs);
     }
 }
 
 impl Div<Self> for Value {
     type Output = Self;
     fn div(self, rhs: Self) -> Self::Output {
-        let mut result = self.clone();
+        let mut result = self;
         result /= rhs;
         result
     }
@@ -122,17 +291,14 @@ impl Div<Self> for Value {
 
 impl BitAndAssign<Self> for Value {
     fn bitand_assign(&mut self, rhs: Self) {
-        *self = match (&self, rhs) {
-            (Value::Bool(b1), Value::Bool(b2)) => Self::Bool(b1 & b2),
-            _ => return,
-        }
+        let _ = self.checked_and_assign(rhs);
     }
 }
 
 impl BitAnd<Self> for Value {
     type Output = Self;
     fn bitand(self, rhs: Self) -> Self::Output {
-        let mut result = self.clone();
+        let mut result = self;
         result &= rhs;
         result
     }
@@ -140,17 +306,14 @@ impl BitAnd<Self> for Value {
 
 impl BitOrAssign<Self> for Value {
     fn bitor_assign(&mut self, rhs: Self) {
-        *self = match (&self, rhs) {
-            (Value::Bool(b1), Value::Bool(b2)) => Self::Bool(b1 | b2),
-            _ => return,
-        }
+        let _ = self.checked_or_assign(rhs);
     }
 }
 
 impl BitOr<Self> for Value {
     type Output = Self;
     fn bitor(self, rhs: Self) -> Self::Output {
-        let mut result = self.clone();
+        let mut result = self;
         result |= rhs;
         result
     }
@@ -169,9 +332,6 @@ impl Not for Value {
 impl Neg for Value {
     type Output = Value;
     fn neg(self) -> Self::Output {
-        match self {
-            Value::Number(n) => Self::Number(-n),
-            _ => self,
-        }
+        self.clone().checked_neg().unwrap_or(self)
     }
 }
@@ -1,10 +1,12 @@
-use super::Value;
-use crate::{Bookmark, Error, Result};
+use super::{Context, Expr, Value};
+use crate::{structs::Operator, Error, Result};
 use pest::{
+    error::LineColLocation,
     iterators::Pair,
     prec_climber::{Assoc, Operator as PrecOp, PrecClimber},
     Parser,
 };
+use regex::Regex;
 
 lazy_static! {
     /// Static climber to be reused each `eval` call.
@@ -16,10 +18,15 @@ lazy_static! {
             | PrecOp::new(Rule::Lt, Assoc::Left)
             | PrecOp::new(Rule::Leq, Assoc::Left)
             | PrecOp::new(Rule::Gt, Assoc::Left)
-            | PrecOp::new(Rule::Geq, Assoc::Left),
+            | PrecOp::new(Rule::Geq, Assoc::Left)
+            | PrecOp::new(Rule::In, Assoc::Left)
+            | PrecOp::new(Rule::Contains, Assoc::Left),
         PrecOp::new(Rule::Add, Assoc::Left) | PrecOp::new(Rule::Sub, Assoc::Left),
         PrecOp::new(Rule::Mul, Assoc::Left) | PrecOp::new(Rule::Div, Assoc::Left),
     ]);
+
+    /// Text starting with a variable, parenthesis or function call was meant to be an expression.
+    static ref INTENDED_EXPR_RE: Regex = Regex::new(r"^\s*(\$[A-Za-z_]|\(|[A-Za-z_]\w*\()").unwrap();
 }
 
 /// Pest parser generated from ast/grammar.pest.
@@ -27,25 +34,99 @@ lazy_static! {
 #[grammar = "value/grammar.pest"]
 struct ExprParser;
 
-impl Value {
-    /// Evaluates an expression `expr`. Uses `bookmark` for $variable lookup.
-    pub fn from_expr(expr: &str, bookmark: &Bookmark) -> Result<Self> {
-        let mut pairs = ExprParser::parse(Rule::Program, expr)?;
+impl Expr {
+    /// Parses `text` into an expression tree.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut pairs = ExprParser::parse(Rule::Program, text).map_err(|e| {
+            let column = match e.line_col {
+                LineColLocation::Pos((_, column)) | LineColLocation::Span((_, column), _) => column,
+            };
+            Error::ExpressionSyntax {
+                expr: text.to_string(),
+                column,
+            }
+        })?;
         if let Some(pair) = pairs.next() {
-            Self::eval_expr(pair, bookmark)
+            if let Some(start) = Self::find_prose_membership(pair.clone()) {
+                return Err(Error::ExpressionSyntax {
+                    expr: text.to_string(),
+                    column: text[..start].chars().count() + 1,
+                });
+            }
+            Self::from_pair(pair)
         } else {
-            Err(Error::Pest("Invalid expression.".to_string()))
+            Err(Error::ExpressionSyntax {
+                expr: text.to_string(),
+                column: 1,
+            })
+        }
+    }
+
+    /// Returns true if `pair` is clearly meant as part of an expression, rather than a word of prose:
+    /// a variable, call, list, map, quoted string or parenthesized expression.
+    fn is_intended_operand(pair: &Pair<Rule>) -> bool {
+        match pair.as_rule() {
+            Rule::Variable
+            | Rule::Call
+            | Rule::List
+            | Rule::Map
+            | Rule::IndexExpr
+            | Rule::String
+            | Rule::BinaryExpr => true,
+            Rule::UnaryExpr => pair
+                .clone()
+                .into_inner()
+                .any(|inner| Self::is_intended_operand(&inner)),
+            _ => false,
         }
     }
 
-    /// Evaluates an expression from a `Pair` tree.
-    fn eval_expr<'i>(pair: Pair<'i, Rule>, bookmark: &Bookmark) -> Result<Value> {
+    /// Finds an `in` or `contains` with no intended operand on either side, such as in
+    /// `sing in tune`, so that such text stays plain text. Returns the operator's byte offset.
+    fn find_prose_membership(pair: Pair<Rule>) -> Option<usize> {
+        let pairs: Vec<Pair<Rule>> = pair.into_inner().collect();
+        let is_membership = |pair: &Pair<Rule>| matches!(pair.as_rule(), Rule::In | Rule::Contains);
+        let is_arithmetic = |pair: &Pair<Rule>| {
+            matches!(
+                pair.as_rule(),
+                Rule::Add | Rule::Sub | Rule::Mul | Rule::Div
+            )
+        };
+        let is_conjunction = |pair: &Pair<Rule>| matches!(pair.as_rule(), Rule::And | Rule::Or);
+        for (i, op) in pairs.iter().enumerate() {
+            if !is_membership(op) {
+                continue;
+            }
+            // Operands reach over operators that bind at least as tightly as the comparison.
+            let mut start = i - 1;
+            while start >= 2 && !is_conjunction(&pairs[start - 1]) {
+                start -= 2;
+            }
+            let mut end = i + 1;
+            while end + 2 < pairs.len() && is_arithmetic(&pairs[end + 1]) {
+                end += 2;
+            }
+            if !pairs[start..=end]
+                .iter()
+                .step_by(2)
+                .any(Self::is_intended_operand)
+            {
+                return Some(op.as_span().start());
+            }
+        }
+        pairs.into_iter().find_map(Self::find_prose_membership)
+    }
+
+    /// Builds an expression from a `Pair` tree.
+    fn from_pair(pair: Pair<Rule>) -> Result<Self> {
         // Define lambdas for use by precedence climber.
-        let primary = |pair| Self::eval_expr(pair, bookmark);
-        let infix = |lhs: Result<Value>, op: Pair<Rule>, rhs: Result<Value>| match (lhs, rhs) {
-            (Ok(lhs), Ok(rhs)) => Self::eval_binary_expr(lhs, op, rhs),
-            (Ok(_), Err(rhs_err)) => Err(rhs_err),
-            (Err(lhs_err), _) => Err(lhs_err),
+        let primary = Self::from_pair;
+        let infix = |lhs: Result<Self>, op: Pair<Rule>, rhs: Result<Self>| {
+            Ok(Self::Binary(
+                Box::new(lhs?),
+                Self::operator(op)?,
+                Box::new(rhs?),
+            ))
         };
 
         match pair.as_rule() {
@@ -56,62 +137,108 @@ impl Value {
                 let inner = it.next();
 
                 if let (Some(op_pair), Some(inner_pair)) = (op, inner) {
-                    let value = Self::eval_expr(inner_pair, bookmark)?;
-                    Self::eval_unary_expr(op_pair, value)
+                    Ok(Self::Unary(
+                        Self::operator(op_pair)?,
+                        Box::new(Self::from_pair(inner_pair)?),
+                    ))
                 } else {
                     Err(error!("Invalid Unary"))
                 }
             }
-            Rule::Variable => Value::from_var(pair.as_str(), bookmark),
-            Rule::Value | Rule::String => Value::from_yml(pair.as_str()),
-            _ => {
-                return Ok(Value::Number(0.));
+            Rule::Call => {
+                let mut it = pair.into_inner();
+                let function = it.next().map(|p| p.as_str()).unwrap_or_default();
+                let args = it.map(Self::from_pair).collect::<Result<Vec<Self>>>()?;
+                Ok(Self::Call(function.to_string(), args))
             }
+            Rule::List => Ok(Self::List(
+                pair.into_inner()
+                    .map(Self::from_pair)
+                    .collect::<Result<Vec<Self>>>()?,
+            )),
+            Rule::Map => {
+                let mut entries = Vec::new();
+                for entry in pair.into_inner() {
+                    let mut it = entry.into_inner();
+                    if let (Some(key), Some(value)) = (it.next(), it.next()) {
+                        entries.push((key.as_str().to_string(), Self::from_pair(value)?));
+                    }
+                }
+                Ok(Self::Map(entries))
+            }
+            Rule::IndexExpr => {
+                let mut it = pair.into_inner();
+                let base = match it.next() {
+                    Some(base) => Self::from_pair(base)?,
+                    None => return Err(error!("Invalid index expression.")),
+                };
+                let indices = it.map(Self::from_pair).collect::<Result<Vec<Self>>>()?;
+                Ok(Self::Index(Box::new(base), indices))
+            }
+            Rule::Variable => Ok(Self::Variable(pair.as_str().to_string())),
+            Rule::Value | Rule::String => Ok(Self::Value(Value::from_yml(pair.as_str())?)),
+            _ => Ok(Self::Value(Value::Number(0.))),
         }
     }
 
-    /// Evaluates a binary expression.
-    fn eval_binary_expr(lhs: Value, op: Pair<Rule>, rhs: Value) -> Result<Value> {
-        // print!("binary expr: ({} {} {}) ", lhs, op.as_str(), rhs);
-        let result = match op.as_rule() {
-            Rule::Add => lhs + rhs,
-            Rule::Sub => lhs - rhs,
-            Rule::Mul => lhs * rhs,
-            Rule::Div => lhs / rhs,
-            Rule::And => lhs & rhs,
-            Rule::Or => lhs | rhs,
-            Rule::Lt => Value::Bool(lhs < rhs),
-            Rule::Leq => Value::Bool(lhs <= rhs),
-            Rule::Gt => Value::Bool(lhs > rhs),
-            Rule::Geq => Value::Bool(lhs >= rhs),
-            Rule::Eq => Value::Bool(lhs == rhs),
-            Rule::Neq => Value::Bool(lhs != rhs),
-            _ => {
-                return Err(error!("Invalid binary expression."));
-            }
+    /// Gets the operator of an operator pair.
+    fn operator(op: Pair<Rule>) -> Result<Operator> {
+        let op = match op.as_rule() {
+            Rule::Add => Operator::Add,
+            Rule::Sub => Operator::Sub,
+            Rule::Mul => Operator::Mul,
+            Rule::Div => Operator::Div,
+            Rule::And => Operator::And,
+            Rule::Or => Operator::Or,
+            Rule::Not => Operator::Not,
+            Rule::Lt => Operator::Lt,
+            Rule::Leq => Operator::Leq,
+            Rule::Gt => Operator::Gt,
+            Rule::Geq => Operator::Geq,
+            Rule::Eq => Operator::Eq,
+            Rule::Neq => Operator::Neq,
+            Rule::In => Operator::In,
+            Rule::Contains => Operator::Contains,
+            _ => return Err(error!("Invalid operator '{}'.", op.as_str())),
         };
-        Ok(result)
+        Ok(op)
     }
+}
 
-    /// Evaluates a unary expression.
-    fn eval_unary_expr(op: Pair<Rule>, value: Value) -> Result<Value> {
-        let result = match op.as_rule() {
-            Rule::Not => !value,
-            Rule::Add => value,
-            Rule::Sub => -value,
-            _ => return Err(error!("Invalid unary expression.")),
-        };
-        Ok(result)
+impl Value {
+    /// Evaluates an expression `expr`. Uses the context's bookmark for $variable lookup.
+    /// Expressions compiled when the story was loaded are evaluated without parsing them again.
+    pub fn from_expr(expr: &str, context: &Context) -> Result<Self> {
+        match context.exprs.and_then(|exprs| exprs.get(expr)) {
+            Some(Ok(compiled)) => compiled.eval(context),
+            Some(Err(e)) => Err(e.clone()),
+            None => Expr::parse(expr)?.eval(context),
+        }
     }
 
-    pub fn eval_as_expr(&mut self, bookmark: &Bookmark) -> Result<()> {
-        let result = match self {
-            Self::String(expr) => Self::from_expr(expr, bookmark),
+    /// Whether `text` was clearly meant to be an expression rather than plain text,
+    /// such as `$gold + 1` or `len($items)`.
+    pub(crate) fn is_intended_expr(text: &str) -> bool {
+        INTENDED_EXPR_RE.is_match(text)
+    }
+
+    /// If this value is a string holding an expression, replaces it with the evaluated result.
+    /// Strings that fail to parse or evaluate, such as `rock and roll`, are left as plain text
+    /// unless they were clearly meant to be expressions, in which case the error is returned.
+    pub fn eval_as_expr(&mut self, context: &Context) -> Result<()> {
+        let expr = match self {
+            Self::String(expr) => expr,
             _ => return Ok(()),
         };
-        match result {
+        // Plain text that was compiled ahead of time is skipped without copying its error.
+        if let Some(Err(_)) = context.exprs.and_then(|exprs| exprs.get(expr.as_str())) {
+            if !Self::is_intended_expr(expr) {
+                return Ok(());
+            }
+        }
+        match Self::from_expr(expr, context) {
             Ok(value) => *self = value,
-            Err(Error::Pest(_)) => (), // Pest errors mean this was a normal string, not an expr.
+            Err(_) if !Self::is_intended_expr(expr) => (),
             Err(e) => return Err(e),
         }
         Ok(())
@@ -120,13 +247,13 @@ impl Value {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Bookmark, Error, Value};
+    use crate::{Bookmark, Context, Error, Expr, Value};
 
     #[test]
     fn test_parse_expr() {
         let bookmark = Bookmark::new(hashmap! {
             "test".to_string() => hashmap! {
-                "var1".to_string() => Value::Number(1.0)
+                "var1".to_string() => Value::Int(1)
             },
             "global".to_string() => hashmap! {
                 "b0".to_string() => Value::Bool(false),
@@ -138,10 +265,10 @@ mod tests {
 
         let tests = vec![
             ("value", Value::String("value".to_string())),
-            ("1 + 2", Value::Number(3.)),
-            ("2 * 1 + 4", Value::Number(6.)),
-            ("2 * (1 + 4)", Value::Number(10.)),
-            ("- (1 / 3)", Value::Number(-1. / 3.)),
+            ("1 + 2", Value::Int(3)),
+            ("2 * 1 + 4", Value::Int(6)),
+            ("2 * (1 + 4)", Value::Int(10)),
+            ("- (1.0 / 3)", Value::Number(-1. / 3.)),
             ("true and false", Value::Bool(false)),
             ("true or false", Value::Bool(true)),
             ("1 < 2", Value::Bool(true)),
@@ -155,10 +282,111 @@ mod tests {
             ("a + b", Value::String("ab".to_string())),
             ("not true", Value::Bool(false)),
             ("1.5 + 2.5", Value::Number(4.0)),
+            ("not (true)", Value::Bool(false)),
+            ("random(3, 3)", Value::Int(3)),
+            ("random(1, 1) + 1", Value::Int(2)),
+            ("chance(0) or chance(1)", Value::Bool(true)),
+            ("chance(1 - 1)", Value::Bool(false)),
         ];
 
         for (expr, expected) in tests {
-            assert_eq!(expected, Value::from_expr(expr, &bookmark).unwrap());
+            assert_eq!(
+                expected,
+                Value::from_expr(expr, &Context::new(&bookmark)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_integers() {
+        let bookmark = Bookmark::default();
+        let eval = |expr: &str| Value::from_expr(expr, &Context::new(&bookmark)).unwrap();
+
+        // Integer arithmetic stays integral and division truncates.
+        assert!(matches!(eval("7 / 2"), Value::Int(3)));
+        assert!(matches!(eval("(0 - 7) / 2"), Value::Int(-3)));
+        assert!(matches!(eval("3 * 4 - 2"), Value::Int(10)));
+
+        // A float anywhere promotes the result to a float.
+        assert!(matches!(eval("7 / 2.0"), Value::Number(n) if n == 3.5));
+        assert!(matches!(eval("1 + 0.5"), Value::Number(n) if n == 1.5));
+        assert!(matches!(eval("2.0 * 3"), Value::Number(n) if n == 6.));
+
+        // Overflowing integers fall back to floats.
+        assert!(matches!(
+            eval("9223372036854775807 + 1"),
+            Value::Number(n) if n == 9_223_372_036_854_775_808.
+        ));
+
+        // Integers and floats compare by value.
+        assert_eq!(eval("3 == 3.0"), Value::Bool(true));
+        assert_eq!(eval("2 < 2.5"), Value::Bool(true));
+
+        assert_eq!(eval("6 / 2").to_string(), "3");
+        assert_eq!(eval("0.1 + 0.2 > 0.3").to_string(), "true");
+        assert_eq!(eval("1.5 * 2").to_string(), "3");
+    }
+
+    #[test]
+    fn test_checked_operations() {
+        let mut bookmark = Bookmark::new(hashmap! {
+            "global".to_string() => hashmap! {
+                "name".to_string() => Value::String("Bob".to_string()),
+            }
+        });
+
+        let invalid = vec![
+            (
+                "$name - 1",
+                "-",
+                Value::String("Bob".to_string()),
+                Value::Int(1),
+            ),
+            ("true + 3", "+", Value::Bool(true), Value::Int(3)),
+            ("2 * a", "*", Value::Int(2), Value::String("a".to_string())),
+            ("1 and true", "and", Value::Int(1), Value::Bool(true)),
+        ];
+        for (expr, operator, lhs, rhs) in &invalid {
+            assert_eq!(
+                Value::from_expr(expr, &Context::new(&bookmark)),
+                Err(Error::InvalidOperation {
+                    operator: operator.to_string(),
+                    lhs: lhs.clone(),
+                    rhs: rhs.clone(),
+                }),
+                "{}",
+                expr
+            );
+        }
+        assert_eq!(
+            Value::from_expr("1 / 0", &Context::new(&bookmark)),
+            Err(Error::DivisionByZero)
+        );
+        assert_eq!(
+            Value::from_expr("1.5 / (1 - 1)", &Context::new(&bookmark)),
+            Err(Error::DivisionByZero)
+        );
+        assert!(matches!(
+            Value::from_expr("not 1", &Context::new(&bookmark)),
+            Err(Error::TypeMismatch { .. })
+        ));
+
+        // Lenient evaluation keeps the old behaviour.
+        bookmark.lenient = true;
+        let lenient = vec![
+            ("$name - 1", Value::String("Bob".to_string())),
+            ("true + 3", Value::Bool(true)),
+            ("1 / 0", Value::Int(0)),
+            ("1.5 / 0", Value::Number(0.)),
+            ("not 1", Value::Int(1)),
+        ];
+        for (expr, expected) in lenient {
+            assert_eq!(
+                Value::from_expr(expr, &Context::new(&bookmark)).unwrap(),
+                expected,
+                "{}",
+                expr
+            );
         }
     }
 
@@ -166,7 +394,161 @@ mod tests {
     fn test_invalid_expr() {
         let bookmark = Bookmark::default();
         let expr = "this is a string";
-        let result = Value::from_expr(expr, &bookmark);
-        assert!(matches!(result, Err(Error::Pest(_))));
+        let result = Value::from_expr(expr, &Context::new(&bookmark));
+        assert!(matches!(result, Err(Error::ExpressionSyntax { .. })));
+    }
+
+    #[test]
+    fn test_collections() {
+        let bookmark = Bookmark::new(hashmap! {
+            "global".to_string() => hashmap! {
+                "items".to_string() => Value::List(vec![
+                    Value::String("sword".to_string()),
+                    Value::String("key".to_string()),
+                ]),
+                "quests".to_string() => Value::Map(btreemap! {
+                    "main".to_string() => Value::Int(2),
+                }),
+            }
+        });
+        let list = |items: Vec<i64>| Value::List(items.into_iter().map(Value::Int).collect());
+
+        let tests = vec![
+            ("[1, 2, 1 + 2]", list(vec![1, 2, 3])),
+            ("[]", list(vec![])),
+            (
+                "{a: 1, \"b c\": [true]}",
+                Value::Map(btreemap! {
+                    "a".to_string() => Value::Int(1),
+                    "b c".to_string() => Value::List(vec![Value::Bool(true)]),
+                }),
+            ),
+            ("$items[1]", Value::String("key".to_string())),
+            ("$quests[main] + 1", Value::Int(3)),
+            ("[[1, 2], [3]][0][1]", Value::Int(2)),
+            ("key in $items", Value::Bool(true)),
+            ("shield in $items", Value::Bool(false)),
+            ("main in $quests", Value::Bool(true)),
+            ("\"sword\" in $items", Value::Bool(true)),
+            ("1 + 1 in [1, 2]", Value::Bool(true)),
+            (
+                "$items contains sword and not ($items contains shield)",
+                Value::Bool(true),
+            ),
+            ("len($items) + len($quests) + len(abc)", Value::Int(6)),
+            ("[1] + [2]", list(vec![1, 2])),
+            ("[1, 2, 1] - 1", list(vec![2])),
+            ("index", Value::String("index".to_string())),
+        ];
+
+        for (expr, expected) in tests {
+            assert_eq!(
+                expected,
+                Value::from_expr(expr, &Context::new(&bookmark)).unwrap(),
+                "{}",
+                expr
+            );
+        }
+
+        // Membership tests need an operand that is clearly an expression.
+        assert!(matches!(
+            Expr::parse("sing in tune"),
+            Err(Error::ExpressionSyntax { column: 6, .. })
+        ));
+
+        assert!(matches!(
+            Value::from_expr("$items[2]", &Context::new(&bookmark)),
+            Err(Error::BadIndex { .. })
+        ));
+        assert!(matches!(
+            Value::from_expr("$quests[side]", &Context::new(&bookmark)),
+            Err(Error::BadIndex { .. })
+        ));
+    }
+
+    #[test]
+    fn test_random_functions() {
+        let mut bookmark = Bookmark::default();
+        bookmark.set_seed(3);
+        let rolls: Vec<Value> = (0..10)
+            .map(|_| Value::from_expr("random(1, 6)", &Context::new(&bookmark)).unwrap())
+            .collect();
+        for roll in &rolls {
+            assert!(matches!(roll, Value::Int(n) if (1..=6).contains(n)));
+        }
+
+        // The same seed replays the same rolls.
+        bookmark.set_seed(3);
+        for roll in &rolls {
+            assert_eq!(
+                &Value::from_expr("random(1, 6)", &Context::new(&bookmark)).unwrap(),
+                roll
+            );
+        }
+
+        assert!(matches!(
+            Value::from_expr("roll(1, 6)", &Context::new(&bookmark)),
+            Err(Error::UnknownFunction { .. })
+        ));
+        assert!(matches!(
+            Value::from_expr("random(6)", &Context::new(&bookmark)),
+            Err(Error::WrongArgumentCount {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            Value::from_expr("chance(often)", &Context::new(&bookmark)),
+            Err(Error::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_eval_as_expr() {
+        let bookmark = Bookmark::default();
+
+        // Plain text is left alone.
+        let mut value = Value::String("this is a string".to_string());
+        value.eval_as_expr(&Context::new(&bookmark)).unwrap();
+        assert_eq!(value, Value::String("this is a string".to_string()));
+
+        // So is plain text that happens to contain an operator.
+        let mut value = Value::String("not today - maybe".to_string());
+        value.eval_as_expr(&Context::new(&bookmark)).unwrap();
+        assert_eq!(value, Value::String("not today - maybe".to_string()));
+
+        // Malformed expressions are reported.
+        let mut value = Value::String("$var +".to_string());
+        assert!(matches!(
+            value.eval_as_expr(&Context::new(&bookmark)),
+            Err(Error::ExpressionSyntax { .. })
+        ));
+
+        // Plain text that parses but can't be evaluated is left alone too.
+        for text in [
+            "rock and roll",
+            "one or two",
+            "ten / 0",
+            "sing in tune",
+            "she contains her anger",
+            "two in a row and three in a line",
+        ] {
+            let mut value = Value::String(text.to_string());
+            value.eval_as_expr(&Context::new(&bookmark)).unwrap();
+            assert_eq!(value, Value::String(text.to_string()));
+        }
+
+        // Errors evaluating intended expressions are reported.
+        let mut value = Value::String("(5 / 0)".to_string());
+        assert!(matches!(
+            value.eval_as_expr(&Context::new(&bookmark)),
+            Err(Error::DivisionByZero)
+        ));
+        let mut value = Value::String("len(1 - true)".to_string());
+        assert!(matches!(
+            value.eval_as_expr(&Context::new(&bookmark)),
+            Err(Error::InvalidOperation { .. })
+        ));
     }
 }
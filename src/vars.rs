@@ -1,52 +1,42 @@
-use crate::{structs::Bookmark, Value};
+use crate::{
+    message::{format_markup, message_exprs},
+    structs::escape_tags,
+    Context,
+};
 use regex::{Captures, Regex};
 use std::borrow::Cow;
 
 static VARS_RE_STR: &str = r"\$((?:[A-Za-z]+:)?(?:\w+\.)?\w+)";
-static BRACKET_EXPR_STR: &str = r"(:?(\{\{)|(\}\})|(\{[^\{\}]+\}))";
 
 lazy_static! {
     static ref VARS_RE_STRING: String = format!(r"{}\b", VARS_RE_STR);
     static ref SINGLE_VAR_RE_STRING: String = format!(r"^{}$", VARS_RE_STR);
     pub static ref VARS_RE: Regex = Regex::new(&VARS_RE_STRING).unwrap();
     pub static ref SINGLE_VAR_RE: Regex = Regex::new(&SINGLE_VAR_RE_STRING).unwrap();
-    pub static ref BRACKET_VARS_RE: Regex = Regex::new(BRACKET_EXPR_STR).unwrap();
-}
-
-fn truncate_ends(s: &str) -> &str {
-    let mut chars = s.chars();
-    chars.next();
-    chars.next_back();
-    chars.as_str()
 }
 
 /// This is a line with var=${var} and var2=${var2}
-pub fn replace_vars(text: &str, bookmark: &Bookmark) -> String {
-    let vars_replaced = BRACKET_VARS_RE.replace_all(&text, |cap: &Captures| {
-        let expr = &cap[1];
-        if expr == "{{" {
-            return Cow::from("{");
-        }
-        if expr == "}}" {
-            return Cow::from("}");
-        }
-        match Value::from_expr(truncate_ends(expr), bookmark) {
-            Ok(value) => Cow::from(value.to_string()),
-            Err(_) => Cow::from(format!("{}", expr).to_string()),
-        }
-    });
+/// Also formats `{expr}` interpolations and selectors, see `format_message`.
+/// Substituted values have `<` escaped, so that `extract_attr` doesn't parse them as tags.
+pub fn replace_vars(text: &str, context: &Context) -> String {
+    let vars_replaced = format_markup(text, context);
 
     VARS_RE
         .replace_all(&vars_replaced, |cap: &Captures| {
             let var = &cap[1];
-            match bookmark.value(var) {
-                Ok(value) => Cow::from(value.to_string()),
+            match context.bookmark.value(var) {
+                Ok(value) => Cow::from(escape_tags(value.to_string())),
                 Err(_) => Cow::from(format!("${}", var).to_string()),
             }
         })
         .to_string()
 }
 
+/// Returns the expressions interpolated into `text` with `{expr}`, including selector arguments.
+pub(crate) fn interpolated_exprs(text: &str) -> Vec<&str> {
+    message_exprs(text)
+}
+
 /// Returns Some(&str) when a variable was successfully extracted.
 /// Otherwise returns None.
 pub fn extract_var(text: &str) -> Option<&str> {
@@ -65,7 +55,7 @@ pub fn contains_var(text: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Value;
+    use crate::{Bookmark, Value};
 
     #[test]
     fn test_str_replace() {
@@ -83,13 +73,13 @@ mod tests {
         assert_eq!(
             replace_vars(
                 "var1 = {$var1}, var2 = {$global:var2}, char.var1 = $char.var1. Tickets cost $10.",
-                &bookmark
+                &Context::new(&bookmark)
             ),
             "var1 = 1, var2 = a, char.var1 = b. Tickets cost $10."
         );
 
         assert_eq!(
-            replace_vars("var1 + 1 = {$var1 + 1}.", &bookmark),
+            replace_vars("var1 + 1 = {$var1 + 1}.", &Context::new(&bookmark)),
             "var1 + 1 = 2."
         );
     }
@@ -98,11 +88,11 @@ mod tests {
     fn test_invalid_vars() {
         let bookmark = Bookmark::default();
         assert_eq!(
-            replace_vars("var1 = {$varx}.", &bookmark),
+            replace_vars("var1 = {$varx}.", &Context::new(&bookmark)),
             "var1 = {$varx}."
         );
         assert_eq!(
-            replace_vars("This string has {{curly braces}}", &bookmark),
+            replace_vars("This string has {{curly braces}}", &Context::new(&bookmark)),
             "This string has {curly braces}"
         )
     }
@@ -0,0 +1,238 @@
+//! JavaScript interface for running stories in the browser, enabled by the `wasm` feature.
+//! Lines and diagnostics are returned as plain objects typed by the definitions below.
+use crate::{
+    error::Error, story_from_sources, Bookmark, Choices, Command, Dialogue, FromMessagePack,
+    FromYaml, Input, Line, OffsetUnit, OwnedRunner, Story, Validator,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::Arc};
+use wasm_bindgen::{prelude::*, JsCast};
+
+#[wasm_bindgen(typescript_custom_section)]
+const TYPES: &str = r#"
+export type Value = string | number | boolean | Value[] | { [key: string]: Value };
+
+export type OffsetUnit = "byte" | "char" | "utf16";
+
+export interface Attribute {
+  name: string;
+  start: number;
+  end: number;
+  params: Record<string, Value>;
+}
+
+export type Line =
+  | { type: "Dialogue"; name: string; text: string; attributes: Attribute[]; seen: boolean }
+  | { type: "Choices"; choices: string[]; timeout: number }
+  | { type: "InvalidChoice" }
+  | { type: "Input"; input: Record<string, string>; timeout: number }
+  | { type: "Command"; name: string; params: Record<string, Value> }
+  | { type: "End" };
+
+export interface Diagnostic {
+  severity: "error" | "warning";
+  namespace: string;
+  passage: string;
+  line: number;
+  message: string;
+  file?: string;
+  sourceLine?: number;
+  column?: number;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Line")]
+    pub type JsLine;
+
+    #[wasm_bindgen(typescript_type = "Diagnostic[]")]
+    pub type JsDiagnostics;
+
+    #[wasm_bindgen(typescript_type = "Record<string, string>")]
+    pub type JsSources;
+
+    #[wasm_bindgen(typescript_type = "OffsetUnit")]
+    pub type JsOffsetUnit;
+}
+
+/// A line, tagged with its `type` for JavaScript.
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum LineObject<'l> {
+    Choices(&'l Choices),
+    InvalidChoice,
+    Dialogue(&'l Dialogue),
+    Input(&'l Input),
+    Command(&'l Command),
+    End,
+}
+
+impl<'l> From<&'l Line> for LineObject<'l> {
+    fn from(line: &'l Line) -> Self {
+        match line {
+            Line::Choices(choices) => Self::Choices(choices),
+            Line::InvalidChoice => Self::InvalidChoice,
+            Line::Dialogue(dialogue) => Self::Dialogue(dialogue),
+            Line::Input(input) => Self::Input(input),
+            Line::Command(command) => Self::Command(command),
+            Line::End => Self::End,
+        }
+    }
+}
+
+/// A diagnostic from the validator, with its error as a message.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiagnosticObject {
+    severity: String,
+    namespace: String,
+    passage: String,
+    line: usize,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+}
+
+fn js_error(error: Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// Converts `value` to a plain JavaScript object.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+/// A story, shared by every runner created from it.
+#[wasm_bindgen(js_name = Story)]
+pub struct WasmStory(Arc<Story>);
+
+#[wasm_bindgen(js_class = Story)]
+impl WasmStory {
+    /// Parses a story from the YAML sources of its section files, keyed by file name.
+    #[wasm_bindgen(js_name = fromYaml)]
+    pub fn from_yaml(sources: JsSources) -> Result<WasmStory, JsError> {
+        let sources: BTreeMap<String, String> = serde_wasm_bindgen::from_value(sources.into())?;
+        let story = story_from_sources(
+            sources
+                .iter()
+                .map(|(file, source)| (file.as_str(), source.as_str())),
+        )
+        .map_err(js_error)?;
+        Ok(Self(Arc::new(story)))
+    }
+
+    /// Loads a story packed into MessagePack by `kataru pack`.
+    #[wasm_bindgen(js_name = fromMsgpack)]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<WasmStory, JsError> {
+        let story = Story::from_mp(bytes).map_err(js_error)?;
+        Ok(Self(Arc::new(story)))
+    }
+
+    /// Returns every error and warning in the story.
+    pub fn validate(&self, bookmark: &WasmBookmark) -> Result<JsDiagnostics, JsError> {
+        let mut bookmark = bookmark.0.clone();
+        bookmark.init_state(&self.0);
+        let diagnostics: Vec<DiagnosticObject> = Validator::new(&self.0, &mut bookmark)
+            .validate_all()
+            .into_iter()
+            .map(|diagnostic| {
+                let span = diagnostic.span.filter(|span| span.line > 0);
+                DiagnosticObject {
+                    severity: diagnostic.severity.to_string(),
+                    namespace: diagnostic.namespace,
+                    passage: diagnostic.passage,
+                    line: diagnostic.line,
+                    message: diagnostic.error.inner().to_string(),
+                    file: span.as_ref().map(|span| span.file.clone()),
+                    source_line: span.as_ref().map(|span| span.line),
+                    column: span.as_ref().map(|span| span.column),
+                }
+            })
+            .collect();
+        Ok(to_js(&diagnostics)?.unchecked_into())
+    }
+}
+
+/// A save of the state and position in a story.
+#[wasm_bindgen(js_name = Bookmark)]
+pub struct WasmBookmark(Bookmark);
+
+impl Default for WasmBookmark {
+    fn default() -> Self {
+        let mut bookmark = Bookmark::default();
+        bookmark.set_passage("Start".to_string());
+        Self(bookmark)
+    }
+}
+
+#[wasm_bindgen(js_class = Bookmark)]
+impl WasmBookmark {
+    /// Creates a bookmark that starts at the global `Start` passage.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmBookmark {
+        Self::default()
+    }
+
+    #[wasm_bindgen(js_name = fromYaml)]
+    pub fn from_yaml(text: &str) -> Result<WasmBookmark, JsError> {
+        Ok(Self(Bookmark::from_yml(text).map_err(js_error)?))
+    }
+
+    #[wasm_bindgen(js_name = fromMsgpack)]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<WasmBookmark, JsError> {
+        Ok(Self(Bookmark::from_mp(bytes).map_err(js_error)?))
+    }
+
+    #[wasm_bindgen(js_name = toYaml)]
+    pub fn to_yaml(&self) -> Result<String, JsError> {
+        Ok(serde_yaml::to_string(&self.0)?)
+    }
+
+    #[wasm_bindgen(js_name = toMsgpack)]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, JsError> {
+        Ok(rmp_serde::to_vec(&self.0)?)
+    }
+}
+
+/// A runner with its own copy of a bookmark.
+#[wasm_bindgen(js_name = Runner)]
+pub struct WasmRunner(OwnedRunner);
+
+#[wasm_bindgen(js_class = Runner)]
+impl WasmRunner {
+    /// Creates a runner for `story` starting from a copy of `bookmark`.
+    /// Variables missing from the bookmark are set to their defaults from the story.
+    /// Attribute offsets are measured in UTF-16 code units, to index JavaScript strings.
+    #[wasm_bindgen(constructor)]
+    pub fn new(story: &WasmStory, bookmark: &WasmBookmark) -> Result<WasmRunner, JsError> {
+        let mut bookmark = bookmark.0.clone();
+        bookmark.init_state(&story.0);
+        let mut runner = OwnedRunner::new(bookmark, story.0.clone()).map_err(js_error)?;
+        runner.set_offset_unit(OffsetUnit::Utf16);
+        Ok(Self(runner))
+    }
+
+    /// Sets the unit that attribute offsets of the following lines are measured in.
+    #[wasm_bindgen(js_name = setOffsetUnit)]
+    pub fn set_offset_unit(&mut self, unit: JsOffsetUnit) -> Result<(), JsError> {
+        let unit: OffsetUnit = serde_wasm_bindgen::from_value(unit.into())?;
+        self.0.set_offset_unit(unit);
+        Ok(())
+    }
+
+    /// Advances to the next line, passing `input` as the picked choice or entered text.
+    pub fn next(&mut self, input: &str) -> Result<JsLine, JsError> {
+        let line = self.0.next(input).map_err(js_error)?;
+        Ok(to_js(&LineObject::from(&line))?.unchecked_into())
+    }
+
+    /// Returns a copy of the runner's bookmark, for saving.
+    pub fn bookmark(&self) -> WasmBookmark {
+        WasmBookmark(self.0.bookmark().clone())
+    }
+}
@@ -0,0 +1,36 @@
+use kataru::{Analyzer, Bookmark, Error, LoadYaml, PassageId, Severity, Story};
+
+/// Tests reachability, dead-end and cycle detection across namespaces.
+#[test]
+fn test_analyzer() {
+    let story: Story = Story::load_yml("./tests/data/analyzer").unwrap();
+    let bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    let analyzer = Analyzer::new(&story, &bookmark);
+
+    let global = |passage: &str| PassageId::new("global", passage);
+    assert_eq!(analyzer.unreachable(), vec![&global("Orphan")]);
+    // Intro and chapter:Begin's call to Shared return to a choice,
+    // and Credits is configured as an ending, so only GoodEnd is reported.
+    assert_eq!(analyzer.dead_ends(), vec![&global("GoodEnd")]);
+    assert_eq!(
+        analyzer.cycles(),
+        vec![vec![&global("LoopA"), &global("LoopB")]]
+    );
+
+    let diagnostics = analyzer.analyze();
+    assert_eq!(diagnostics.len(), 3);
+    for diagnostic in &diagnostics {
+        assert_eq!(diagnostic.severity, Severity::Warning);
+    }
+    assert_eq!(
+        diagnostics[0].error,
+        Error::UnreachablePassage {
+            passage: "Orphan".to_string(),
+            namespace: "global".to_string()
+        }
+    );
+    // The cycle is reported at LoopA's call to LoopB.
+    assert_eq!(diagnostics[2].passage, "LoopA");
+    assert_eq!(diagnostics[2].line, 1);
+    assert_eq!(diagnostics[2].span.as_ref().unwrap().line, 29);
+}
@@ -0,0 +1,146 @@
+use kataru::{
+    Attribute, Bookmark, Error, Line, LoadYaml, OffsetUnit, Params, Runner, Story, Validator, Value,
+};
+
+fn params(values: Vec<(&str, Value)>) -> Params {
+    values
+        .into_iter()
+        .map(|(param, value)| (param.to_string(), value))
+        .collect()
+}
+
+/// Tests that dialogue reports nested and parameterized attribute spans.
+#[test]
+fn test_attributes() {
+    let story: Story = Story::load_yml("./tests/data/attributes").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let dialogue = match runner.next("").unwrap() {
+        Line::Dialogue(dialogue) => dialogue,
+        line => panic!("Expected dialogue, found {:?}", line),
+    };
+    assert_eq!(dialogue.text, "Red and shaking. Bold");
+    assert_eq!(
+        dialogue.attributes,
+        vec![
+            Attribute {
+                name: "color".to_string(),
+                start: 0,
+                end: 15,
+                params: params(vec![("value", Value::String("#ff0000".to_string()))]),
+            },
+            Attribute {
+                name: "shake".to_string(),
+                start: 4,
+                end: 15,
+                params: params(vec![
+                    ("strength", Value::Number(2.0)),
+                    ("speed", Value::Int(1))
+                ]),
+            },
+            Attribute {
+                name: "pause".to_string(),
+                start: 16,
+                end: 16,
+                params: params(vec![("duration", Value::Number(0.25))]),
+            },
+            Attribute {
+                name: "b".to_string(),
+                start: 17,
+                end: 21,
+                params: Params::new(),
+            },
+        ]
+    );
+}
+
+/// Tests that attribute offsets can be measured in bytes, characters or UTF-16 code units.
+#[test]
+fn test_attribute_offsets() {
+    let story: Story = Story::load_yml("./tests/data/attributes").unwrap();
+    // Offsets are into the text after variables are replaced.
+    let tests = vec![
+        (OffsetUnit::Byte, (12, 25), (15, 28)),
+        (OffsetUnit::Char, (4, 8), (5, 9)),
+        (OffsetUnit::Utf16, (4, 9), (5, 10)),
+    ];
+    for (unit, expected, expected_interpolated) in tests {
+        let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+        bookmark.init_state(&story);
+
+        let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+        runner.set_offset_unit(unit);
+        runner.next("").unwrap();
+        for (text, expected) in [
+            ("日本語の揺れる😀文", expected),
+            ("太郎さんの揺れる😀文", expected_interpolated),
+        ] {
+            match runner.next("").unwrap() {
+                Line::Dialogue(dialogue) => {
+                    assert_eq!(dialogue.text, text);
+                    let shake = &dialogue.attributes[0];
+                    assert_eq!((shake.start, shake.end), expected);
+                }
+                line => panic!("Expected dialogue, found {:?}", line),
+            }
+        }
+    }
+}
+
+/// Tests that tags in substituted values are kept as text rather than parsed as markup.
+#[test]
+fn test_substituted_tags() {
+    let story: Story = Story::load_yml("./tests/data/attributes").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("Input".to_string());
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    assert!(matches!(runner.next("").unwrap(), Line::Input(_)));
+    match runner.next("</b><shake>").unwrap() {
+        Line::Dialogue(dialogue) => {
+            assert_eq!(dialogue.text, "Hello </b><shake>, or </b><shake>!");
+            assert_eq!(
+                dialogue.attributes,
+                vec![Attribute {
+                    name: "b".to_string(),
+                    start: 6,
+                    end: 17,
+                    params: Params::new(),
+                }]
+            );
+        }
+        line => panic!("Expected dialogue, found {:?}", line),
+    }
+}
+
+/// Tests that the validator checks attributes against their configured parameters.
+#[test]
+fn test_validate_attributes() {
+    let story: Story = Story::load_yml("./tests/data/attributes").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let mut diagnostics = Validator::new(&story, &mut bookmark).validate_all();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    let errors: Vec<Error> = diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.error)
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            Error::BadAttributeParameter {
+                attribute: "shake".to_string(),
+                param: "size".to_string()
+            },
+            Error::TypeMismatch {
+                expected: "number for parameter 'strength' of attribute <shake>".to_string(),
+                found: Value::String("fast".to_string())
+            },
+            Error::Generic("Mismatched closing tag </b>, expected </color>".to_string()),
+        ]
+    );
+}
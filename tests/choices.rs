@@ -1,5 +1,4 @@
 use kataru::{Bookmark, Choices, Dialogue, Line, LoadYaml, Runner, Story, Validator};
-use maplit::hashmap;
 
 /// Tests basic $character commands.
 #[test]
@@ -26,7 +25,8 @@ fn test_choices() {
             Line::Dialogue(Dialogue {
                 name: "Alice".to_string(),
                 text: "Yes!".to_string(),
-                attributes: hashmap! {},
+                attributes: vec![],
+                seen: false,
             }),
         ),
         (
@@ -83,3 +83,68 @@ fn test_choices() {
         assert_eq!(&real_line, line);
     }
 }
+
+/// Tests that each embedded passage continues after the choices block.
+#[test]
+fn test_embedded_choice_breaks() {
+    let story: Story = Story::load_yml("./tests/data/choices").unwrap();
+    let tests = vec![
+        ("yes", vec!["Embedded yes.", "After choices."]),
+        ("no", vec!["Embedded no.", "Still no.", "After choices."]),
+        ("maybe", vec!["Embedded maybe.", "After choices."]),
+        ("later", vec!["Later.", "After choices."]),
+    ];
+
+    for (choice, expected) in &tests {
+        let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+        bookmark.init_state(&story);
+        bookmark.set_passage("EmbeddedChoices".to_string());
+
+        let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+        assert_eq!(
+            runner.next("").unwrap(),
+            Line::Choices(Choices {
+                choices: vec![
+                    "yes".to_string(),
+                    "no".to_string(),
+                    "maybe".to_string(),
+                    "later".to_string()
+                ],
+                ..Choices::default()
+            })
+        );
+        let mut texts = Vec::new();
+        let mut input = *choice;
+        while let Line::Dialogue(dialogue) = runner.next(input).unwrap() {
+            texts.push(dialogue.text);
+            input = "";
+        }
+        assert_eq!(&texts, expected);
+    }
+}
+
+/// Tests that a default reached with no visible choices continues after the choices block.
+#[test]
+fn test_default_breaks() {
+    let story: Story = Story::load_yml("./tests/data/choices").unwrap();
+    let tests = vec![
+        (
+            "HiddenDefaultLines",
+            vec!["Default lines.", "After choices."],
+        ),
+        ("HiddenDefaultPassage", vec!["Later.", "After choices."]),
+    ];
+
+    for (passage, expected) in &tests {
+        let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+        bookmark.init_state(&story);
+        bookmark.set_passage(passage.to_string());
+
+        let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+        let mut texts = Vec::new();
+        while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+            texts.push(dialogue.text);
+        }
+        assert_eq!(&texts, expected);
+    }
+}
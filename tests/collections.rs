@@ -0,0 +1,64 @@
+use kataru::{
+    Bookmark, Error, FromMessagePack, FromYaml, Line, LoadYaml, Runner, Story, Validator, Value,
+};
+
+/// Tests list and map state through set commands, conditionals and interpolation.
+#[test]
+fn test_collections() {
+    let story: Story = Story::load_yml("./tests/data/collections/valid").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    assert_eq!(
+        texts,
+        vec![
+            "I carry [sword, key] and 2 things.",
+            "I have the key.",
+            "Now I carry key."
+        ]
+    );
+    assert_eq!(
+        bookmark.value("met").unwrap(),
+        &Value::List(vec![Value::String("Alice".to_string())])
+    );
+
+    // Lists and maps survive saving in either format.
+    let yaml = serde_yaml::to_string(&bookmark).unwrap();
+    assert_eq!(Bookmark::from_yml(&yaml).unwrap(), bookmark);
+    let packed = rmp_serde::to_vec(&bookmark).unwrap();
+    assert_eq!(Bookmark::from_mp(&packed).unwrap(), bookmark);
+}
+
+/// Tests that the validator checks the types of list and map assignments.
+#[test]
+fn test_collection_types() {
+    let story: Story = Story::load_yml("./tests/data/collections/invalid").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let errors: Vec<Error> = Validator::new(&story, &mut bookmark)
+        .validate_all()
+        .into_iter()
+        .map(|diagnostic| diagnostic.error)
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            Error::TypeMismatch {
+                expected: "a map".to_string(),
+                found: Value::String("main".to_string()),
+            },
+            Error::TypeMismatch {
+                expected: "a list".to_string(),
+                found: Value::String("none".to_string()),
+            },
+        ]
+    );
+}
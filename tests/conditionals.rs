@@ -1,5 +1,4 @@
 use kataru::{Bookmark, Choices, Dialogue, Line, LoadYaml, Runner, Story, Validator};
-use maplit::hashmap;
 
 /// Tests basic $character commands.
 #[test]
@@ -26,7 +25,8 @@ fn test_conditionals() {
             Line::Dialogue(Dialogue {
                 name: "Alice".to_string(),
                 text: "Yes!".to_string(),
-                attributes: hashmap! {},
+                attributes: vec![],
+                seen: false,
             }),
         ),
         (
@@ -34,7 +34,8 @@ fn test_conditionals() {
             Line::Dialogue(Dialogue {
                 name: "Alice".to_string(),
                 text: "Success!".to_string(),
-                attributes: hashmap! {},
+                attributes: vec![],
+                seen: false,
             }),
         ),
     ];
@@ -44,3 +45,87 @@ fn test_conditionals() {
         assert_eq!(&real_line, line);
     }
 }
+
+/// Tests that taking a branch continues after the whole block.
+#[test]
+fn test_branch_breaks() {
+    let story: Story = Story::load_yml("./tests/data/conditionals").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("IfElse".to_string());
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    assert_eq!(
+        texts,
+        vec![
+            "First branch.",
+            "Still first branch.",
+            "Nested branch.",
+            "After branches."
+        ]
+    );
+}
+
+/// Tests that a bookmark saved inside a branch still skips the rest of the block.
+#[test]
+fn test_resume_in_branch() {
+    let story: Story = Story::load_yml("./tests/data/conditionals").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("IfElse".to_string());
+    bookmark.set_line(4);
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    assert_eq!(
+        texts,
+        vec!["Second branch.", "Nested branch.", "After branches."]
+    );
+}
+
+/// Tests that breaks of blocks left through their last branch are not followed later.
+#[test]
+fn test_stale_breaks() {
+    let story: Story = Story::load_yml("./tests/data/conditionals").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("StaleBreaks".to_string());
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    assert_eq!(
+        texts,
+        vec![
+            "Inner else.",
+            "After inner branches.",
+            "After outer branches."
+        ]
+    );
+}
+
+/// Tests that `and` and `or` skip their right side when the left side decides the result,
+/// so that it can guard against dividing by zero or indexing past the end.
+#[test]
+fn test_short_circuit_guards() {
+    let story: Story = Story::load_yml("./tests/data/conditionals").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("Guards".to_string());
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    assert_eq!(texts, vec!["Nothing to divide.", "No items."]);
+}
@@ -0,0 +1,41 @@
+use kataru::{Bookmark, FromMessagePack, Line, LoadYaml, Runner, Story, GLOBAL};
+
+/// Runs `story` from the start and returns the text of each dialogue line.
+fn play(story: &Story) -> Vec<String> {
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(story);
+    let mut runner: Runner = Runner::new(&mut bookmark, story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    texts
+}
+
+/// Tests that expressions are compiled when a story is loaded from YAML or MessagePack.
+#[test]
+fn test_compiled_exprs() {
+    let story: Story = Story::load_yml("./tests/data/collections/valid").unwrap();
+    let exprs = story[GLOBAL].exprs();
+    assert!(matches!(exprs.get("key in $items"), Some(Ok(_))));
+    assert!(matches!(exprs.get("len($items)"), Some(Ok(_))));
+    assert!(matches!(exprs.get("{main: started}"), Some(Ok(_))));
+
+    let packed = rmp_serde::to_vec(&story).unwrap();
+    let unpacked = Story::from_mp(&packed).unwrap();
+    assert_eq!(unpacked[GLOBAL].exprs().len(), exprs.len());
+    assert!(matches!(
+        unpacked[GLOBAL].exprs().get("key in $items"),
+        Some(Ok(_))
+    ));
+
+    assert_eq!(play(&unpacked), play(&story));
+    assert_eq!(
+        play(&story),
+        vec![
+            "I carry [sword, key] and 2 things.",
+            "I have the key.",
+            "Now I carry key."
+        ]
+    );
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "ffi")]
+
+use kataru::ffi::*;
+use kataru::{LineTag, OffsetUnit};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+unsafe fn read(text: *const c_char) -> String {
+    assert!(!text.is_null());
+    CStr::from_ptr(text).to_str().unwrap().to_string()
+}
+
+/// Tests playing through a story with the C interface.
+#[test]
+fn test_ffi() {
+    unsafe {
+        let path = CString::new("./tests/data/ffi").unwrap();
+        let story = kataru_story_load(path.as_ptr());
+        assert!(!story.is_null());
+        let bookmark = kataru_bookmark_new();
+        let runner = kataru_runner_new(story, bookmark);
+        assert!(!runner.is_null());
+
+        // Runners keep the story alive.
+        kataru_story_free(story);
+        kataru_bookmark_free(bookmark);
+
+        assert!(kataru_runner_next(runner, std::ptr::null()));
+        assert_eq!(kataru_runner_tag(runner), LineTag::Dialogue);
+        assert_eq!(read(kataru_dialogue_name(runner)), "Alice");
+        assert_eq!(read(kataru_dialogue_text(runner)), "Hello there.");
+        assert_eq!(kataru_dialogue_attributes_len(runner), 1);
+        let shake = kataru_dialogue_attribute(runner, 0);
+        assert_eq!(read(shake.name), "shake");
+        assert_eq!((shake.start, shake.end, shake.params_len), (0, 5, 1));
+        let strength = kataru_dialogue_attribute_param(runner, 0, 0);
+        assert_eq!(read(strength.name), "strength");
+        assert_eq!(strength.value.kind, KataruValueKind::Number);
+        assert_eq!(strength.value.number, 2.0);
+        assert!(kataru_dialogue_attribute(runner, 1).name.is_null());
+
+        assert!(kataru_runner_next(runner, std::ptr::null()));
+        assert_eq!(kataru_runner_tag(runner), LineTag::Command);
+        assert_eq!(read(kataru_command_name(runner)), "Wait");
+        assert_eq!(kataru_command_params_len(runner), 2);
+        let duration = kataru_command_param(runner, 0);
+        assert_eq!(read(duration.name), "duration");
+        assert_eq!(duration.value.number, 2.0);
+        let skippable = kataru_command_param(runner, 1);
+        assert_eq!(skippable.value.kind, KataruValueKind::Bool);
+        assert!(skippable.value.boolean);
+        assert_eq!(read(skippable.value.string), "true");
+
+        assert!(kataru_runner_next(runner, std::ptr::null()));
+        assert_eq!(kataru_runner_tag(runner), LineTag::Choices);
+        assert_eq!(kataru_choices_len(runner), 2);
+        assert!(kataru_choice(runner, 2).is_null());
+        let choice = CString::new("Leave").unwrap();
+        assert!(kataru_runner_next(runner, choice.as_ptr()));
+        assert_eq!(read(kataru_dialogue_text(runner)), "Farewell.");
+
+        // Saving the runner's bookmark records where it is.
+        let saved = kataru_runner_bookmark(runner);
+        let save_path = std::env::temp_dir().join("kataru_ffi_bookmark.yml");
+        let save_path = CString::new(save_path.to_str().unwrap()).unwrap();
+        assert!(kataru_bookmark_save(saved, save_path.as_ptr()));
+        kataru_bookmark_free(saved);
+        let loaded = kataru_bookmark_load(save_path.as_ptr());
+        assert!(!loaded.is_null());
+        kataru_bookmark_free(loaded);
+
+        kataru_runner_free(runner);
+    }
+}
+
+/// Tests measuring attribute offsets in UTF-16 code units with the C interface.
+#[test]
+fn test_ffi_offset_unit() {
+    unsafe {
+        let path = CString::new("./tests/data/ffi").unwrap();
+        let story = kataru_story_load(path.as_ptr());
+        let bookmark = kataru_bookmark_new();
+        let runner = kataru_runner_new(story, bookmark);
+        kataru_story_free(story);
+        kataru_bookmark_free(bookmark);
+
+        assert!(!kataru_runner_set_offset_unit(runner, 3));
+        assert!(read(kataru_last_error()).contains("offset unit"));
+        assert!(kataru_runner_set_offset_unit(
+            runner,
+            OffsetUnit::Utf16 as u32
+        ));
+        for _ in 0..3 {
+            assert!(kataru_runner_next(runner, std::ptr::null()));
+        }
+        let choice = CString::new("Stay").unwrap();
+        assert!(kataru_runner_next(runner, choice.as_ptr()));
+        assert_eq!(read(kataru_dialogue_text(runner)), "😀 Stay awhile.");
+        let shake = kataru_dialogue_attribute(runner, 0);
+        assert_eq!((shake.start, shake.end), (3, 7));
+
+        kataru_runner_free(runner);
+    }
+}
+
+/// Tests that failures return null and set the last error.
+#[test]
+fn test_ffi_errors() {
+    unsafe {
+        let path = CString::new("./tests/data/ffi/missing.mp").unwrap();
+        assert!(kataru_story_load(path.as_ptr()).is_null());
+        assert!(read(kataru_last_error()).contains("missing.mp"));
+    }
+}
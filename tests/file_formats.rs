@@ -1,4 +1,5 @@
-use kataru::{Bookmark, Dialogue, Line, LoadYaml, Runner, Story, Validator};
+use kataru::{story_from_sources, Bookmark, Dialogue, Line, LoadYaml, Runner, Story, Validator};
+use std::fs;
 
 /// Tests loading commented out story files and config-only story files.
 #[test]
@@ -24,3 +25,34 @@ fn test_story2() {
         assert_eq!(&runner.next(input).unwrap(), line);
     }
 }
+
+/// Tests parsing a story from the sources of its files, as when they aren't on disk.
+#[test]
+fn test_story_from_sources() {
+    let mut files: Vec<(String, String)> = fs::read_dir("tests/data/file_formats")
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path().to_string_lossy().to_string();
+            let source = fs::read_to_string(&path).unwrap();
+            (path, source)
+        })
+        .collect();
+    files.sort();
+    let story = story_from_sources(
+        files
+            .iter()
+            .map(|(file, source)| (file.as_str(), source.as_str())),
+    )
+    .unwrap();
+    assert_eq!(story, Story::load_yml("./tests/data/file_formats").unwrap());
+}
+
+/// Tests loading a story from a single section file.
+#[test]
+fn test_section_file() {
+    let story: Story = Story::load_yml("./tests/data/functions/story.yml").unwrap();
+    let directory: Story = Story::load_yml("./tests/data/functions").unwrap();
+    assert_eq!(story["global"].passages, directory["global"].passages);
+    let span = story["global"].span("Start", 0).unwrap();
+    assert_eq!((span.line, span.column), (16, 5));
+}
@@ -0,0 +1,144 @@
+use kataru::{
+    Bookmark, Choices, Error, Functions, Line, LoadYaml, Runner, Severity, Story, Validator, Value,
+};
+use std::sync::{Arc, Mutex};
+
+/// Game state kept outside of the bookmark.
+#[derive(Default)]
+struct Game {
+    items: Vec<String>,
+    reputation: f64,
+    /// Number of times the game was asked about its items.
+    item_checks: usize,
+}
+
+fn register(game: &Arc<Mutex<Game>>) -> Functions {
+    let mut functions = Functions::default();
+    functions.insert("time_of_day", |_args| {
+        Ok(Value::String("morning".to_string()))
+    });
+    functions.insert("greet", |args| match args {
+        [name] => Ok(Value::String(format!("Hi {}!", name))),
+        _ => Err(Error::WrongArgumentCount {
+            function: "greet".to_string(),
+            expected: 1,
+            found: args.len(),
+        }),
+    });
+    let items_game = game.clone();
+    functions.insert("has_item", move |args| {
+        let mut game = items_game.lock().unwrap();
+        game.item_checks += 1;
+        Ok(Value::Bool(
+            game.items
+                .iter()
+                .any(|item| args[0] == Value::String(item.clone())),
+        ))
+    });
+    let reputation_game = game.clone();
+    functions.insert("reputation", move |_args| {
+        Ok(Value::Number(reputation_game.lock().unwrap().reputation))
+    });
+    functions
+}
+
+fn text(line: Line) -> String {
+    match line {
+        Line::Dialogue(dialogue) => dialogue.text,
+        line => panic!("Expected dialogue, got {:?}", line),
+    }
+}
+
+/// Tests calling host functions from set commands, interpolation, conditionals and choices.
+#[test]
+fn test_host_functions() {
+    let story: Story = Story::load_yml("./tests/data/functions").unwrap();
+    let game = Arc::new(Mutex::new(Game::default()));
+    let functions = register(&game);
+    let new_bookmark = || {
+        let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+        bookmark.init_state(&story);
+        bookmark
+    };
+
+    // Validating checks calls without making them.
+    let mut bookmark = new_bookmark();
+    let diagnostics = Validator::new(&story, &mut bookmark)
+        .with_functions(&functions)
+        .validate_all();
+    assert_eq!(diagnostics, vec![]);
+    assert_eq!(game.lock().unwrap().item_checks, 0);
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.set_functions(functions.clone());
+    assert_eq!(text(runner.next("").unwrap()), "Hi Alice! It's morning.");
+    assert_eq!(text(runner.next("").unwrap()), "You need a key.");
+    assert_eq!(
+        runner.next("").unwrap(),
+        Line::Choices(Choices {
+            choices: vec!["Leave".to_string()],
+            ..Choices::default()
+        })
+    );
+
+    // Functions read the game's current state each time they are called.
+    {
+        let mut game = game.lock().unwrap();
+        game.items.push("key".to_string());
+        game.reputation = 20.;
+    }
+    let mut bookmark = new_bookmark();
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.set_functions(functions);
+    runner.next("").unwrap();
+    assert_eq!(text(runner.next("").unwrap()), "You have the key.");
+    assert_eq!(
+        runner.next("").unwrap(),
+        Line::Choices(Choices {
+            choices: vec!["Join the guild".to_string(), "Leave".to_string()],
+            ..Choices::default()
+        })
+    );
+    assert_eq!(
+        text(runner.next("Join the guild").unwrap()),
+        "Welcome to the guild."
+    );
+}
+
+/// Tests that functions are only found once registered.
+#[test]
+fn test_unregistered_function() {
+    let story: Story = Story::load_yml("./tests/data/functions").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    // Without a registry, unknown functions may still be registered later.
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+    let diagnostics = Validator::new(&story, &mut bookmark).validate_all();
+    assert_eq!(diagnostics.len(), 3);
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity == Severity::Warning));
+    assert_eq!(
+        diagnostics[0].error,
+        Error::UnknownFunction {
+            function: "greet".to_string()
+        }
+    );
+
+    // With one, they are errors.
+    let mut functions = Functions::default();
+    functions.insert("greet", |_args| Ok(Value::String("Hello.".to_string())));
+    let diagnostics = Validator::new(&story, &mut bookmark)
+        .with_functions(&functions)
+        .validate_all();
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity == Severity::Error));
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.register_function("greet", |_args| Ok(Value::String("Hello.".to_string())));
+    runner.register_function("time_of_day", |_args| Ok(Value::String("noon".to_string())));
+    assert_eq!(text(runner.next("").unwrap()), "Hello. It's noon.");
+}
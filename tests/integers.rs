@@ -0,0 +1,80 @@
+use kataru::{Bookmark, Error, FromYaml, Line, LoadYaml, Runner, Story, Validator, Value};
+
+/// Tests that integer state stays integral and only becomes a float when a float is involved.
+#[test]
+fn test_integers() {
+    let story: Story = Story::load_yml("./tests/data/integers/valid").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    assert_eq!(
+        texts,
+        vec![
+            "I have 3 gold and carry 3.5 kg.",
+            "Split two ways that's 1 each, or 1.5 exactly.",
+            "Exactly three.",
+            "Now I carry 0.5 kg."
+        ]
+    );
+    assert!(matches!(bookmark.value("gold").unwrap(), Value::Int(3)));
+    assert!(matches!(bookmark.value("weight").unwrap(), Value::Number(n) if *n == 4.));
+
+    // Integers stay integers after saving.
+    let yaml = serde_yaml::to_string(&bookmark).unwrap();
+    let loaded = Bookmark::from_yml(&yaml).unwrap();
+    assert!(matches!(loaded.value("gold").unwrap(), Value::Int(3)));
+    assert!(matches!(loaded.value("weight").unwrap(), Value::Number(_)));
+}
+
+/// Tests that assigning or adding a float to an integer variable makes it a float.
+#[test]
+fn test_integer_promotion() {
+    let story: Story = Story::load_yml("./tests/data/integers/valid").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("Promotion".to_string());
+
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    assert_eq!(texts, vec!["I have 10.5 gold.", "Now I have 2.5 gold."]);
+    assert!(matches!(bookmark.value("gold").unwrap(), Value::Number(n) if *n == 2.5));
+}
+
+/// Tests that the validator rejects non-numbers assigned or added to number variables.
+#[test]
+fn test_integer_types() {
+    let story: Story = Story::load_yml("./tests/data/integers/invalid").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let errors: Vec<Error> = Validator::new(&story, &mut bookmark)
+        .validate_all()
+        .into_iter()
+        .map(|diagnostic| diagnostic.error)
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            Error::TypeMismatch {
+                expected: "a number".to_string(),
+                found: Value::String("none".to_string()),
+            },
+            Error::TypeMismatch {
+                expected: "a number".to_string(),
+                found: Value::String("none".to_string()),
+            },
+        ]
+    );
+}
@@ -0,0 +1,106 @@
+use kataru::{
+    line_id, Bookmark, Error, Line, LoadYaml, Locale, Runner, SourceString, Story, StringTable,
+    Validator,
+};
+use std::sync::Arc;
+
+/// Tests listing every translatable string in a story with its ID.
+#[test]
+fn test_string_table() {
+    let story: Story = Story::load_yml("./tests/data/locale/story").unwrap();
+    let table = StringTable::new(&story);
+    let string = |id: &str, passage: &str, speaker: &str, text: &str, choice: bool| SourceString {
+        id: id.to_string(),
+        namespace: "global".to_string(),
+        passage: passage.to_string(),
+        speaker: speaker.to_string(),
+        text: text.to_string(),
+        choice,
+    };
+    let auto_id = |passage: &str, text: &str| line_id("global", passage, text).0;
+    assert_eq!(
+        table.strings,
+        vec![
+            string("bye", "Leave", "Alice", "Bye.", false),
+            string(
+                &auto_id("Shop", "Welcome."),
+                "Shop",
+                "Alice",
+                "Welcome.",
+                false
+            ),
+            string("greeting", "Start", "Alice", "Hello {$name}.", false),
+            string(
+                &auto_id("Start", "Where to?"),
+                "Start",
+                "",
+                "Where to?",
+                false
+            ),
+            string("shop", "Start", "", "Shop", true),
+            string(&auto_id("Start", "Leave"), "Start", "", "Leave", true),
+        ]
+    );
+
+    let csv = table.to_csv();
+    assert!(csv.starts_with(
+        "id,namespace,passage,speaker,text,translation\nbye,global,Leave,Alice,Bye.,\n"
+    ));
+    let po = table.to_po();
+    assert!(po.contains(
+        "#. global:Start Alice\nmsgctxt \"greeting\"\nmsgid \"Hello {$name}.\"\nmsgstr \"\"\n"
+    ));
+    let xliff = table.to_xliff("en");
+    assert!(xliff.contains("source-language=\"en\""));
+    assert!(xliff.contains("<trans-unit id=\"shop\">\n        <source>Shop</source>"));
+}
+
+/// Tests that dialogue and choices are translated, falling back to the source text.
+#[test]
+fn test_locale() {
+    let story: Story = Story::load_yml("./tests/data/locale/story").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+
+    let mut locale = Locale::load_yml("./tests/data/locale/fr.yml").unwrap();
+    locale
+        .strings
+        .insert(line_id("global", "Start", "Leave").0, "Partir".to_string());
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.set_locale(Arc::new(locale));
+    let text = |line: Line| match line {
+        Line::Dialogue(dialogue) => dialogue.text,
+        line => panic!("Expected dialogue, got {:?}", line),
+    };
+    assert_eq!(text(runner.next("").unwrap()), "Bonjour Bob.");
+    assert_eq!(text(runner.next("").unwrap()), "Where to?");
+    match runner.next("").unwrap() {
+        Line::Choices(choices) => assert_eq!(choices.choices, vec!["Boutique", "Partir"]),
+        line => panic!("Expected choices, got {:?}", line),
+    }
+    assert_eq!(runner.next("Leave").unwrap(), Line::InvalidChoice);
+    assert_eq!(text(runner.next("Partir").unwrap()), "Bye.");
+}
+
+/// Tests that the validator reports explicit IDs given to more than one line.
+#[test]
+fn test_duplicate_ids() {
+    let story: Story = Story::load_yml("./tests/data/locale/duplicates").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let mut validator = Validator::new(&story, &mut bookmark);
+    let error = validator.validate().unwrap_err();
+    assert!(matches!(
+        error.inner(),
+        Error::DuplicateLineId { id, passage, .. } if id == "greeting" && passage == "Start"
+    ));
+
+    let diagnostics = validator.validate_all();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].passage, "Welcome");
+    assert_eq!(diagnostics[0].line, 0);
+    assert_eq!(diagnostics[0].span.as_ref().unwrap().line, 14);
+}
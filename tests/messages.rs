@@ -0,0 +1,43 @@
+use kataru::{Bookmark, Line, LoadYaml, Locale, Runner, Story, Validator};
+use maplit::hashmap;
+use std::sync::Arc;
+
+/// Plays through the story, collecting dialogue text.
+fn play(story: &Story, locale: Locale) -> Vec<String> {
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(story);
+    Validator::new(story, &mut bookmark).validate().unwrap();
+
+    let mut runner: Runner = Runner::new(&mut bookmark, story).unwrap();
+    runner.set_locale(Arc::new(locale));
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    texts
+}
+
+/// Tests plural and select messages in the source language and in translations.
+#[test]
+fn test_messages() {
+    let story: Story = Story::load_yml("./tests/data/messages").unwrap();
+    assert_eq!(
+        play(&story, Locale::default()),
+        vec!["You have 1 coin.", "You have 5 coins.", "They left."]
+    );
+
+    // Russian uses different forms for 1, 2-4 and 5+.
+    let locale = Locale {
+        language: "ru".to_string(),
+        strings: hashmap! {
+            "coins".to_string() =>
+                "У вас {$coins, plural, one {# монета} few {# монеты} many {# монет} other {# монеты}}.".to_string(),
+            "more-coins".to_string() =>
+                "Теперь у вас {$coins, plural, one {# монета} few {# монеты} many {# монет} other {# монеты}}.".to_string(),
+        },
+    };
+    assert_eq!(
+        play(&story, locale),
+        vec!["У вас 1 монета.", "Теперь у вас 5 монет.", "They left."]
+    );
+}
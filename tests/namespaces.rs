@@ -14,6 +14,7 @@ fn test_namespaces() {
     Validator::new(&story, &mut bookmark).validate().unwrap();
 
     let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    assert_eq!(runner.section().unwrap().namespace(), "global");
 
     let tests = vec![
         (
@@ -141,4 +142,14 @@ fn test_namespaces() {
     for (input, line) in &tests {
         assert_eq!(&runner.next(input).unwrap(), line);
     }
+
+    // The runner is in the passage called from namespace1.
+    assert_eq!(
+        runner.section().unwrap().namespace(),
+        "namespace1:namespace2"
+    );
+    assert!(std::ptr::eq(
+        runner.passage().unwrap(),
+        story["namespace1:namespace2"].passage("Start").unwrap()
+    ));
 }
@@ -0,0 +1,27 @@
+use kataru::{Bookmark, Error, Line, LoadYaml, Runner, Story, Value};
+
+/// Tests that set commands with unsupported operand types are errors unless the runner is lenient.
+#[test]
+fn test_invalid_operations() {
+    let story: Story = Story::load_yml("./tests/data/operations").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let mut strict_bookmark = bookmark.clone();
+    let mut runner: Runner = Runner::new(&mut strict_bookmark, &story).unwrap();
+    assert_eq!(
+        runner.next("").map_err(|error| error.inner().clone()),
+        Err(Error::InvalidOperation {
+            operator: "-".to_string(),
+            lhs: Value::String("Bob".to_string()),
+            rhs: Value::Int(1),
+        })
+    );
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.set_lenient(true);
+    match runner.next("").unwrap() {
+        Line::Dialogue(dialogue) => assert_eq!(dialogue.text, "Hi Bob."),
+        line => panic!("Expected dialogue, got {:?}", line),
+    }
+}
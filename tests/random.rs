@@ -0,0 +1,53 @@
+use kataru::{Bookmark, FromYaml, Line, LoadYaml, Runner, Story, Validator};
+
+/// Plays until the story ends, collecting dialogue text.
+fn play(runner: &mut Runner) -> Vec<String> {
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    texts
+}
+
+fn new_bookmark(story: &Story, seed: u64) -> Bookmark {
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(story);
+    bookmark.set_seed(seed);
+    bookmark
+}
+
+/// Tests that a seed always produces the same rolls.
+#[test]
+fn test_random_seed() {
+    let story: Story = Story::load_yml("./tests/data/random").unwrap();
+
+    let mut bookmark = new_bookmark(&story, 42);
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+    let texts = play(&mut Runner::new(&mut bookmark, &story).unwrap());
+    assert_eq!(texts.len(), 4);
+    assert!(["1", "2", "3", "4", "5", "6"]
+        .iter()
+        .any(|roll| texts[0] == format!("I rolled {}.", roll)));
+
+    let mut replay = new_bookmark(&story, 42);
+    assert_eq!(play(&mut Runner::new(&mut replay, &story).unwrap()), texts);
+    assert_eq!(replay.rng, bookmark.rng);
+}
+
+/// Tests that a reloaded save replays the same rolls.
+#[test]
+fn test_random_saved() {
+    let story: Story = Story::load_yml("./tests/data/random").unwrap();
+    let mut bookmark = new_bookmark(&story, 7);
+    let mut runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.next("").unwrap();
+
+    let saved = serde_yaml::to_string(&*runner.bookmark).unwrap();
+    let texts = play(&mut runner);
+
+    let mut reloaded = Bookmark::from_yml(&saved).unwrap();
+    assert_eq!(
+        play(&mut Runner::new(&mut reloaded, &story).unwrap()),
+        texts
+    );
+}
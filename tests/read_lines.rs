@@ -0,0 +1,84 @@
+use kataru::{story_from_sources, Bookmark, FromYaml, Line, LoadYaml, ReadLines, Runner, Story};
+
+/// Plays through the story picking `choice`, returning each line of dialogue and whether it was seen.
+fn play(story: &Story, read_lines: ReadLines, choice: &str) -> (Vec<(String, bool)>, ReadLines) {
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(story);
+    let mut runner: Runner = Runner::new(&mut bookmark, story).unwrap();
+    runner.read_lines = read_lines;
+
+    let mut lines = Vec::new();
+    let mut input = "";
+    loop {
+        match runner.next(input).unwrap() {
+            Line::Dialogue(dialogue) => {
+                lines.push((dialogue.text, dialogue.seen));
+                input = "";
+            }
+            Line::Choices(_) => input = choice,
+            Line::End => break,
+            line => panic!("Unexpected line {:?}", line),
+        }
+    }
+    (lines, runner.read_lines)
+}
+
+/// Tests that dialogue read in an earlier playthrough is marked as seen.
+#[test]
+fn test_read_lines() {
+    let story: Story = Story::load_yml("./tests/data/rewind").unwrap();
+
+    let (lines, read_lines) = play(&story, ReadLines::new(), "Leave");
+    assert_eq!(
+        lines,
+        vec![
+            ("Gold 0.".to_string(), false),
+            ("Bye.".to_string(), false),
+            ("Back with 5 gold.".to_string(), false),
+        ]
+    );
+    assert_eq!(read_lines.len(), 3);
+
+    // Read lines are saved separately from the bookmark.
+    let saved = serde_yaml::to_string(&read_lines).unwrap();
+    let read_lines = ReadLines::from_yml(&saved).unwrap();
+
+    let (lines, read_lines) = play(&story, read_lines, "Shop");
+    assert_eq!(
+        lines
+            .iter()
+            .map(|(_text, seen)| *seen)
+            .collect::<Vec<bool>>(),
+        vec![true, false, false, true]
+    );
+    assert_eq!(read_lines.len(), 5);
+}
+
+/// Tests that lines stay read after other lines are added before them.
+#[test]
+fn test_read_lines_after_edit() {
+    let config = "---\nnamespace: global\ncharacters:\n  Alice:\n---\n";
+    let story = |lines: &str| {
+        let source = format!("{}Start:\n{}", config, lines);
+        story_from_sources(vec![("story.yml", source.as_str())]).unwrap()
+    };
+
+    let (_lines, read_lines) = play(
+        &story("  - Alice: Hello.\n  - Bye. #line:bye\n"),
+        ReadLines::new(),
+        "",
+    );
+    let (lines, _read_lines) = play(
+        &story("  - Alice: New line.\n  - Alice: Hello.\n  - Bye. #line:bye\n"),
+        read_lines,
+        "",
+    );
+    assert_eq!(
+        lines,
+        vec![
+            ("New line.".to_string(), false),
+            ("Hello.".to_string(), true),
+            ("Bye.".to_string(), true),
+        ]
+    );
+}
@@ -0,0 +1,46 @@
+use kataru::{Bookmark, Line, LoadYaml, Runner, Story, Validator, Value};
+
+fn text(line: Line) -> String {
+    match line {
+        Line::Dialogue(dialogue) => dialogue.text,
+        line => panic!("Expected dialogue, got {:?}", line),
+    }
+}
+
+/// Tests that rewinding restores the bookmark exactly, including state set by set commands,
+/// `onEnter` and `onExit`.
+#[test]
+fn test_rewind() {
+    let story: Story = Story::load_yml("./tests/data/rewind").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.set_history_limit(10);
+    assert_eq!(text(runner.next("").unwrap()), "Gold 0.");
+    assert!(matches!(runner.next("").unwrap(), Line::Choices(_)));
+    let before_shop = runner.bookmark().clone();
+
+    assert_eq!(text(runner.next("Shop").unwrap()), "Welcome, shop visit 1.");
+    let price = text(runner.next("").unwrap());
+    let before_return = runner.bookmark().clone();
+    assert_eq!(text(runner.next("").unwrap()), "Back with 3 gold.");
+    assert_eq!(runner.bookmark().value("Shop.exited"), Ok(&Value::Int(1)));
+
+    runner.rewind(1).unwrap();
+    assert_eq!(runner.bookmark(), &before_return);
+
+    // Rewinding past the choices replays them, rolling and visiting the same way.
+    runner.rewind(2).unwrap();
+    assert_eq!(runner.bookmark().state, before_shop.state);
+    assert_eq!(runner.bookmark().visits, before_shop.visits);
+    assert_eq!(runner.bookmark().position(), before_shop.position());
+    assert_eq!(text(runner.next("Shop").unwrap()), "Welcome, shop visit 1.");
+    assert_eq!(text(runner.next("").unwrap()), price);
+
+    assert!(runner.rewind(10).is_err());
+    runner.rewind(4).unwrap();
+    assert_eq!(runner.bookmark().value("gold"), Ok(&Value::Int(0)));
+    assert_eq!(text(runner.next("").unwrap()), "Gold 0.");
+}
@@ -164,12 +164,13 @@ fn test_state() {
                 ..Choices::default()
             }),
         ),
-        // Alice: Choice1
+        // Alice: Choice1, which was already shown.
         (
             "choice1 text",
             Line::Dialogue(Dialogue {
                 name: "Alice".to_string(),
                 text: "Choice1".to_string(),
+                seen: true,
                 ..Dialogue::default()
             }),
         ),
@@ -191,3 +192,20 @@ fn test_state() {
         assert_eq!(&runner.next(input).unwrap(), line);
     }
 }
+
+/// Tests that text which looks like an expression but wasn't meant as one stays plain text.
+#[test]
+fn test_prose_values() {
+    let story: Story = Story::load("./tests/data/state").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("Prose".to_string());
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    for text in &["rock and roll", "ten / 0", "sing in tune"] {
+        match runner.next("").unwrap() {
+            Line::Dialogue(dialogue) => assert_eq!(&dialogue.text, text),
+            line => panic!("Expected dialogue, found {:?}", line),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use kataru::{Bookmark, FromYaml, Line, LoadYaml, OwnedRunner, Runner, Story, Value, MAIN_THREAD};
+use std::{sync::Arc, thread};
+
+fn assert_send<T: Send>() {}
+
+/// Tests that owned runners can play the same shared story on different threads.
+#[test]
+fn test_owned_runners() {
+    assert_send::<OwnedRunner>();
+
+    let story = Arc::new(Story::load_yml("./tests/data/threads").unwrap());
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let mut bookmark = bookmark.clone();
+            bookmark
+                .state()
+                .unwrap()
+                .insert("npc".to_string(), Value::String(format!("guard {}", i)));
+            let mut runner = OwnedRunner::new(bookmark, Arc::clone(&story)).unwrap();
+            thread::spawn(move || {
+                let choice = if i % 2 == 0 { "Cheer" } else { "Insult" };
+                let mut texts = Vec::new();
+                let mut input = "";
+                loop {
+                    match runner.next(input).unwrap() {
+                        Line::Dialogue(dialogue) => {
+                            texts.push(dialogue.text);
+                            input = "";
+                        }
+                        Line::Choices(_) => input = choice,
+                        Line::End => break,
+                        line => panic!("Unexpected line {:?}", line),
+                    }
+                }
+                texts
+            })
+        })
+        .collect();
+
+    for (i, handle) in handles.into_iter().enumerate() {
+        let mood = if i % 2 == 0 { 1 } else { -1 };
+        assert_eq!(
+            handle.join().unwrap(),
+            vec![
+                format!("I am guard {}.", i),
+                format!("My mood is {}.", mood)
+            ]
+        );
+    }
+}
+
+/// Tests that a runner can't rewind past steps taken on another thread.
+#[test]
+fn test_thread_rewind() {
+    let story: Story = Story::load_yml("./tests/data/threads").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.history.set_limit(10);
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    assert_eq!(text(runner.next("").unwrap()), "I am nobody.");
+    assert!(matches!(runner.next("").unwrap(), Line::Choices(_)));
+
+    let mut runner: Runner = Runner::spawn(&mut bookmark, &story, "ambient", "Chatter").unwrap();
+    assert_eq!(text(runner.next("").unwrap()), "Psst, nobody.");
+    assert_eq!(text(runner.next("").unwrap()), "Still 10.");
+
+    // The main thread's last step was before the chatter's.
+    let mut runner: Runner = Runner::attach(&mut bookmark, &story, MAIN_THREAD).unwrap();
+    assert!(runner.rewind(1).is_err());
+    assert_eq!(runner.bookmark().thread(), MAIN_THREAD);
+
+    // The chatter can rewind its own steps, but not the main thread's before them.
+    let mut runner: Runner = Runner::attach(&mut bookmark, &story, "ambient").unwrap();
+    assert!(runner.rewind(3).is_err());
+    runner.rewind(1).unwrap();
+    assert_eq!(runner.bookmark().thread(), "ambient");
+    assert_eq!(runner.bookmark().value("mood").unwrap(), &Value::Int(0));
+    assert_eq!(text(runner.next("").unwrap()), "Still 10.");
+}
+
+fn text(line: Line) -> String {
+    match line {
+        Line::Dialogue(dialogue) => dialogue.text,
+        line => panic!("Expected dialogue, got {:?}", line),
+    }
+}
+
+/// Tests that runners can take turns on different threads of one bookmark with shared state.
+#[test]
+fn test_bookmark_threads() {
+    let story: Story = Story::load_yml("./tests/data/threads").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    assert_eq!(text(runner.next("").unwrap()), "I am nobody.");
+    assert!(matches!(runner.next("").unwrap(), Line::Choices(_)));
+
+    let mut runner: Runner = Runner::spawn(&mut bookmark, &story, "ambient", "Chatter").unwrap();
+    assert_eq!(text(runner.next("").unwrap()), "Psst, nobody.");
+    assert_eq!(runner.bookmark().thread(), "ambient");
+
+    // The main thread resumes at its choices.
+    let mut runner: Runner = Runner::attach(&mut bookmark, &story, MAIN_THREAD).unwrap();
+    assert_eq!(text(runner.next("Cheer").unwrap()), "My mood is 1.");
+
+    // Set commands on one thread are seen by the other.
+    runner.switch_thread("ambient").unwrap();
+    assert_eq!(text(runner.next("").unwrap()), "Still 11.");
+    assert_eq!(runner.next("").unwrap(), Line::End);
+
+    // Threads are saved with the bookmark.
+    let saved = serde_yaml::to_string(&bookmark).unwrap();
+    let mut bookmark = Bookmark::from_yml(&saved).unwrap();
+    assert_eq!(bookmark.thread(), "ambient");
+    assert!(bookmark.threads.contains_key(MAIN_THREAD));
+    let mut runner: Runner = Runner::attach(&mut bookmark, &story, MAIN_THREAD).unwrap();
+    assert_eq!(runner.next("").unwrap(), Line::End);
+    assert!(Runner::attach(&mut bookmark, &story, "missing").is_err());
+}
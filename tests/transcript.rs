@@ -0,0 +1,69 @@
+use kataru::{Bookmark, FromYaml, LoadYaml, Runner, Story, TranscriptEntry};
+
+fn entry(passage: &str, speaker: &str, text: &str, choice: bool) -> TranscriptEntry {
+    TranscriptEntry {
+        namespace: "global".to_string(),
+        passage: passage.to_string(),
+        speaker: speaker.to_string(),
+        text: text.to_string(),
+        choice,
+    }
+}
+
+/// Tests that dialogue and picked choices are logged, saved and rewound with the bookmark.
+#[test]
+fn test_transcript() {
+    let story: Story = Story::load_yml("./tests/data/rewind").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let mut runner: Runner = Runner::new(&mut bookmark, &story).unwrap();
+    runner.set_transcript_limit(3);
+    runner.set_history_limit(10);
+    runner.next("").unwrap();
+    runner.next("").unwrap();
+    runner.next("Leave").unwrap();
+    runner.next("").unwrap();
+
+    let transcript = &runner.bookmark().transcript;
+    assert_eq!(transcript.total(), 4);
+    assert_eq!(
+        transcript.iter().cloned().collect::<Vec<TranscriptEntry>>(),
+        vec![
+            entry("Start", "", "Leave", true),
+            entry("Leave", "Alice", "Bye.", false),
+            entry("Start", "Alice", "Back with 5 gold.", false),
+        ]
+    );
+    assert_eq!(
+        transcript
+            .recent(1)
+            .cloned()
+            .collect::<Vec<TranscriptEntry>>(),
+        vec![entry("Start", "Alice", "Back with 5 gold.", false)]
+    );
+
+    // Rewinding removes the entries logged since, but not the ones forgotten past the limit.
+    runner.rewind(2).unwrap();
+    assert!(runner.bookmark().transcript.is_empty());
+    assert_eq!(runner.bookmark().transcript.total(), 1);
+    runner.next("Shop").unwrap();
+    assert_eq!(
+        runner
+            .bookmark()
+            .transcript
+            .iter()
+            .cloned()
+            .collect::<Vec<TranscriptEntry>>(),
+        vec![
+            entry("Start", "", "Shop", true),
+            entry("Shop", "Alice", "Welcome, shop visit 1.", false),
+        ]
+    );
+
+    let saved = serde_yaml::to_string(&bookmark).unwrap();
+    assert_eq!(
+        Bookmark::from_yml(&saved).unwrap().transcript,
+        bookmark.transcript
+    );
+}
@@ -0,0 +1,156 @@
+use kataru::{Bookmark, Diagnostic, Error, LoadYaml, Severity, Story, Validator, Value};
+
+/// Tests that `validate_all` reports every error instead of stopping at the first.
+#[test]
+fn test_validate_all() {
+    let story: Story = Story::load_yml("./tests/data/validator").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let mut validator = Validator::new(&story, &mut bookmark);
+
+    // Failing fast keeps the typed error along with where it happened.
+    let error = validator.validate().unwrap_err();
+    assert!(matches!(error.inner(), Error::UnknownCharacter { .. }));
+    assert!(error.span().is_some());
+    match &error {
+        Error::Located(_, located) => assert!(matches!(
+            located.as_ref(),
+            Error::InPassage { namespace, line: 1..=2, .. } if namespace == "global"
+        )),
+        _ => panic!("Expected a located error, found {}", error),
+    }
+
+    let mut diagnostics: Vec<Diagnostic> = validator.validate_all();
+    diagnostics.sort_by(|a, b| (&a.passage, a.line).cmp(&(&b.passage, b.line)));
+
+    let locations: Vec<(&str, usize)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.passage.as_str(), diagnostic.line))
+        .collect();
+    assert_eq!(
+        locations,
+        vec![
+            ("Other", 0),
+            ("Start", 1),
+            ("Start", 2),
+            ("Start", 4),
+            ("Start", 6)
+        ]
+    );
+    for diagnostic in &diagnostics {
+        assert_eq!(diagnostic.namespace, "global");
+        assert_eq!(diagnostic.severity, Severity::Error);
+    }
+
+    // Each diagnostic points at the line in the YAML file.
+    let spans: Vec<(usize, usize)> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let span = diagnostic.span.as_ref().unwrap();
+            assert!(span.file.ends_with("story.yml"));
+            (span.line, span.column)
+        })
+        .collect();
+    assert_eq!(spans, vec![(23, 5), (13, 5), (14, 5), (16, 9), (19, 5)]);
+
+    // Each diagnostic keeps the typed error.
+    assert_eq!(
+        diagnostics[1].error,
+        Error::UnknownCharacter {
+            character: "Bob".to_string(),
+            namespace: "global".to_string()
+        }
+    );
+    assert_eq!(
+        diagnostics[3].error,
+        Error::UnknownPassage {
+            passage: "Missing".to_string(),
+            namespace: "global".to_string()
+        }
+    );
+
+    // Validating should not move the bookmark.
+    assert_eq!(bookmark.passage(), "Start");
+    assert_eq!(bookmark.line(), 0);
+}
+
+/// Tests that YAML syntax errors report the file, line and column.
+#[test]
+fn test_load_error_span() {
+    let error = Story::load_yml("./tests/data/invalid").unwrap_err();
+    let span = error.span().unwrap();
+    assert!(span.file.ends_with("story.yml"));
+    assert_eq!(span.line, 10);
+    assert!(matches!(error.inner(), Error::YamlSyntax { .. }));
+}
+
+/// Tests that expressions are checked without evaluating them,
+/// so errors that depend on the current state are left for when the story runs.
+#[test]
+fn test_validate_without_evaluating() {
+    let story: Story = Story::load_yml("./tests/data/static").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    let rng = bookmark.rng.clone();
+
+    let diagnostics = Validator::new(&story, &mut bookmark).validate_all();
+    let errors: Vec<(usize, &Error)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.line, &diagnostic.error))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                5,
+                &Error::WrongArgumentCount {
+                    function: "len".to_string(),
+                    expected: 1,
+                    found: 2
+                }
+            ),
+            (
+                7,
+                &Error::UndefinedVariable {
+                    var: "missing".to_string(),
+                    namespace: "global".to_string()
+                }
+            ),
+            (
+                9,
+                &Error::TypeMismatch {
+                    expected: "a number".to_string(),
+                    found: Value::String("sword".to_string())
+                }
+            ),
+        ]
+    );
+
+    // Nothing was rolled.
+    assert_eq!(bookmark.rng, rng);
+}
+
+/// Tests that failing fast locates an error in a nested block at the nested line, like `validate_all`.
+#[test]
+fn test_validate_nested_error() {
+    let story: Story = Story::load_yml("./tests/data/nested").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    let mut validator = Validator::new(&story, &mut bookmark);
+
+    let diagnostics = validator.validate_all();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, 3);
+    let span = diagnostics[0].span.clone().unwrap();
+    assert_eq!((span.line, span.column), (12, 9));
+
+    let error = validator.validate().unwrap_err();
+    assert_eq!(error.span(), Some(&span));
+    match &error {
+        Error::Located(_, located) => {
+            assert!(matches!(located.as_ref(), Error::InPassage { line: 4, .. }))
+        }
+        _ => panic!("Expected a located error, found {}", error),
+    }
+}
@@ -0,0 +1,135 @@
+use kataru::{Bookmark, Error, FromYaml, Line, LoadYaml, Runner, Severity, Story, Validator};
+
+/// Plays from the bookmark's position until the story ends, collecting dialogue text.
+fn play(story: &Story, bookmark: &mut Bookmark) -> Vec<String> {
+    let mut runner: Runner = Runner::new(bookmark, story).unwrap();
+    let mut texts = Vec::new();
+    while let Line::Dialogue(dialogue) = runner.next("").unwrap() {
+        texts.push(dialogue.text);
+    }
+    texts
+}
+
+/// Tests sequence, cycle and once lines over several visits.
+#[test]
+fn test_variations() {
+    let story: Story = Story::load_yml("./tests/data/variations").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    Validator::new(&story, &mut bookmark).validate().unwrap();
+
+    assert_eq!(
+        play(&story, &mut bookmark),
+        vec![
+            "First.",
+            "A.",
+            "Only once.",
+            "End of bark.",
+            "Second.",
+            "Still second.",
+            "B.",
+            "End of bark.",
+            "Last.",
+            "A.",
+            "End of bark.",
+            "Done."
+        ]
+    );
+    assert_eq!(bookmark.visits.len(), 3);
+    for (key, visits) in &bookmark.visits {
+        assert!(key.starts_with("global:Bark:"));
+        assert_eq!(*visits, 3);
+    }
+}
+
+/// Tests that visit counts are saved with the bookmark.
+#[test]
+fn test_variations_saved() {
+    let story: Story = Story::load_yml("./tests/data/variations").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("Bark".to_string());
+
+    assert_eq!(
+        play(&story, &mut bookmark),
+        vec!["First.", "A.", "Only once.", "End of bark."]
+    );
+
+    let saved = serde_yaml::to_string(&bookmark).unwrap();
+    let mut bookmark = Bookmark::from_yml(&saved).unwrap();
+    bookmark.set_line(0);
+    assert_eq!(
+        play(&story, &mut bookmark),
+        vec!["Second.", "Still second.", "B.", "End of bark."]
+    );
+}
+
+/// Tests that visit counts stay with their lines when the passage is edited.
+#[test]
+fn test_variations_edited() {
+    let story: Story = Story::load_yml("./tests/data/variations").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("Bark".to_string());
+    play(&story, &mut bookmark);
+
+    // The edited story adds a line before the variations.
+    let edited: Story = Story::load_yml("./tests/data/edited").unwrap();
+    bookmark.set_line(0);
+    assert_eq!(
+        play(&edited, &mut bookmark),
+        vec![
+            "A line added before the variations.",
+            "Second.",
+            "Still second.",
+            "B.",
+            "End of bark."
+        ]
+    );
+    assert_eq!(bookmark.visits.len(), 3);
+}
+
+/// Tests that each round of a shuffle plays every alternative once.
+#[test]
+fn test_shuffle() {
+    let story: Story = Story::load_yml("./tests/data/variations").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+    bookmark.set_passage("Shuffle".to_string());
+
+    for _round in 0..3 {
+        let mut texts = Vec::new();
+        for _visit in 0..3 {
+            bookmark.set_line(0);
+            texts.extend(play(&story, &mut bookmark));
+        }
+        texts.sort();
+        assert_eq!(texts, vec!["One.", "Three.", "Two."]);
+    }
+}
+
+/// Tests that commands named after a variation are reported, since lines can't call them.
+#[test]
+fn test_reserved_command() {
+    let story: Story = Story::load_yml("./tests/data/reserved").unwrap();
+    let mut bookmark: Bookmark = Bookmark::load_yml("./tests/data/bookmark.yml").unwrap();
+    bookmark.init_state(&story);
+
+    let error = Validator::new(&story, &mut bookmark)
+        .validate()
+        .unwrap_err();
+    assert!(matches!(error.inner(), Error::ReservedCommand { .. }));
+
+    let diagnostics = Validator::new(&story, &mut bookmark).validate_all();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, 0);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(
+        diagnostics[0].error,
+        Error::ReservedCommand {
+            command: "once".to_string(),
+            namespace: "global".to_string()
+        }
+    );
+}
@@ -0,0 +1,2 @@
+//! Kataru's JavaScript interface built as a WebAssembly module. See `kataru::wasm`.
+pub use kataru::wasm::*;